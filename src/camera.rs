@@ -26,15 +26,17 @@ impl CameraMovementKind {
     }
 }
 
+/// ups is the number of ticks run during the last real second, target_ups the number expected at the current simulation speed
 #[derive(Resource)]
 pub struct UpsCounter {
     pub ticks: u32,
     pub last_second: f64,
     pub ups: u32,
+    pub target_ups: f64,
 }
 
 pub fn display_fps_ups_system(
-    time: Res<Time>,
+    time: Res<Time<Real>>,
     diagnostics: Res<DiagnosticsStore>,
     mut counter: ResMut<UpsCounter>,
 ) {
@@ -48,7 +50,10 @@ pub fn display_fps_ups_system(
         // Récupère le FPS depuis le plugin
         if let Some(fps) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
             if let Some(fps_avg) = fps.smoothed() {
-                println!("FPS: {:.0} | UPS: {}", fps_avg, counter.ups);
+                println!(
+                    "FPS: {:.0} | UPS: {} / {:.0}",
+                    fps_avg, counter.ups, counter.target_ups
+                );
             }
         }
     }
@@ -62,7 +67,8 @@ pub fn handle_camera_inputs_system(
     input: Res<ButtonInput<KeyCode>>,
     mut input_mouse_wheel: MessageReader<MouseWheel>,
     player_query: Query<&Transform, With<Player>>,
-    time: Res<Time>,
+    // real time so the camera keeps moving while the simulation is paused or sped up
+    time: Res<Time<Real>>,
 ) {
     let Ok((mut camera_transform, mut projection, mut camera_movement)) = camera_query.single_mut()
     else {
@@ -0,0 +1,40 @@
+use crate::{
+    environment::{day_night::WorldClock, weather::Weather},
+    units::emotions::Emotions,
+};
+use serde::Serialize;
+
+/// variables given to the dialogue templates
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DialogueContext {
+    pub player_name: String,
+    pub npc_name: String,
+    /// "Soleil", "Pluie" or "Orage"
+    pub weather: &'static str,
+    pub day: u32,
+    pub hour: u32,
+    /// emotions of the npc, read as emotions.colere, emotions.joie, emotions.peur and emotions.tristesse
+    pub emotions: Emotions,
+}
+impl DialogueContext {
+    pub fn new(
+        player_name: &str,
+        npc_name: &str,
+        emotions: Emotions,
+        world_clock: &WorldClock,
+        weather: &Weather,
+    ) -> Self {
+        Self {
+            player_name: player_name.to_owned(),
+            npc_name: npc_name.to_owned(),
+            weather: weather.kind.dialogue_name(),
+            day: world_clock.day,
+            hour: world_clock.hours_minutes().0,
+            emotions,
+        }
+    }
+
+    pub fn to_tera(&self) -> Result<tera::Context, tera::Error> {
+        tera::Context::from_serialize(self)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod context;
+pub mod template;
+pub mod ui;
@@ -0,0 +1,194 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use std::{fmt, string::FromUtf8Error};
+use tera::Tera;
+
+const TEMPLATE_NAME: &str = "dialogue";
+/// rendered lines starting with this mark are choices, written `-> next_template: label`
+/// a choice without next template closes the dialogue
+pub const CHOICE_MARK: &str = "->";
+
+/// a `.tera` file of assets/dialogues, parsed once when loaded
+#[derive(Asset, TypePath, Debug)]
+pub struct DialogueTemplate {
+    tera: Tera,
+}
+impl DialogueTemplate {
+    pub fn parse(source: &str) -> Result<Self, tera::Error> {
+        let mut tera = Tera::default();
+        tera.add_raw_template(TEMPLATE_NAME, source)?;
+        Ok(Self { tera })
+    }
+
+    pub fn render(&self, context: &tera::Context) -> Result<DialoguePage, tera::Error> {
+        Ok(DialoguePage::parse(
+            &self.tera.render(TEMPLATE_NAME, context)?,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogueChoice {
+    pub label: String,
+    /// path of the template shown when this choice is picked
+    pub next: Option<String>,
+}
+
+/// what the dialogue box shows : the text said by the npc and the answers of the player
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DialoguePage {
+    pub text: String,
+    pub choices: Vec<DialogueChoice>,
+}
+impl DialoguePage {
+    /// splits a rendered template into its text and its choices, blank lines and indentation are dropped
+    pub fn parse(rendered: &str) -> Self {
+        let mut text_lines = Vec::new();
+        let mut choices = Vec::new();
+        for line in rendered
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let Some(choice) = line.strip_prefix(CHOICE_MARK) else {
+                text_lines.push(line);
+                continue;
+            };
+            let (next, label) = choice.split_once(':').unwrap_or(("", choice));
+            let next = next.trim();
+            choices.push(DialogueChoice {
+                label: label.trim().to_owned(),
+                next: (!next.is_empty()).then(|| next.to_owned()),
+            });
+        }
+        Self {
+            text: text_lines.join("\n"),
+            choices,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DialogueTemplateError {
+    Io(std::io::Error),
+    Utf8(FromUtf8Error),
+    Template(tera::Error),
+}
+impl fmt::Display for DialogueTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialogueTemplateError::Io(error) => write!(f, "cannot read the template: {error}"),
+            DialogueTemplateError::Utf8(error) => write!(f, "the template is not utf-8: {error}"),
+            DialogueTemplateError::Template(error) => write!(f, "invalid template: {error}"),
+        }
+    }
+}
+impl std::error::Error for DialogueTemplateError {}
+
+#[derive(Default)]
+pub struct DialogueTemplateLoader;
+
+impl AssetLoader for DialogueTemplateLoader {
+    type Asset = DialogueTemplate;
+    type Settings = ();
+    type Error = DialogueTemplateError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(DialogueTemplateError::Io)?;
+        let source = String::from_utf8(bytes).map_err(DialogueTemplateError::Utf8)?;
+        DialogueTemplate::parse(&source).map_err(DialogueTemplateError::Template)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tera"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dialogue::context::DialogueContext, units::emotions::Emotions};
+
+    #[test]
+    fn test_parse_page_with_choices() {
+        let page = DialoguePage::parse(
+            "
+            Bonjour, Player.
+
+            -> dialogues/npc_weather.tera: Quel temps fait-il ?
+            -> : Au revoir
+            ",
+        );
+        assert_eq!(page.text, "Bonjour, Player.");
+        assert_eq!(
+            page.choices,
+            vec![
+                DialogueChoice {
+                    label: "Quel temps fait-il ?".to_owned(),
+                    next: Some("dialogues/npc_weather.tera".to_owned()),
+                },
+                DialogueChoice {
+                    label: "Au revoir".to_owned(),
+                    next: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_shipped_greetings() {
+        let template =
+            DialogueTemplate::parse(include_str!("../../assets/dialogues/npc_greetings.tera"))
+                .unwrap();
+        let render = |weather, anger| {
+            let context = DialogueContext {
+                player_name: "Alice".to_owned(),
+                npc_name: "Bob".to_owned(),
+                weather,
+                day: 1,
+                hour: 8,
+                emotions: Emotions { anger, ..default() },
+            };
+            template.render(&context.to_tera().unwrap()).unwrap()
+        };
+
+        let page = render("Pluie", 0.6);
+        assert_eq!(
+            page.text,
+            "Pff, encore cette pluie... Franchement, ça commence à bien m'énerver. Qu'est-ce que tu veux, Alice ?"
+        );
+        assert_eq!(
+            page.choices,
+            vec![
+                DialogueChoice {
+                    label: "Quel temps va-t-il faire ?".to_owned(),
+                    next: Some("dialogues/npc_weather.tera".to_owned()),
+                },
+                DialogueChoice {
+                    label: "Au revoir".to_owned(),
+                    next: None,
+                },
+            ]
+        );
+        assert_eq!(
+            render("Pluie", 0.9).text,
+            "MAIS C'EST PAS POSSIBLE CETTE PLUIE INCESSANTE ! ÇA ME REND FOU !"
+        );
+        assert_eq!(
+            render("Soleil", 0.0).text,
+            "Ah, quel plaisir ce soleil ! Bonjour, Alice !"
+        );
+        assert_eq!(render("Orage", 0.0).text, "Bonjour, Alice.");
+    }
+}
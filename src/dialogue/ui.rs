@@ -0,0 +1,192 @@
+use crate::{
+    dialogue::{
+        context::DialogueContext,
+        template::{DialoguePage, DialogueTemplate, DialogueTemplateLoader},
+    },
+    environment::{day_night::WorldClock, weather::Weather},
+    map::TILE_SIZE,
+    units::{
+        Player, UNIT_REACH,
+        emotions::{Emotion, EmotionStimulus, Emotions, TALK_JOY},
+    },
+};
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// opens the dialogue of the closest unit within reach, or closes the current one
+pub const TALK_KEY: KeyCode = KeyCode::KeyE;
+
+/// loads the dialogue templates and shows them in an egui box when the player talks to a unit
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DialogueTemplate>()
+            .init_asset_loader::<DialogueTemplateLoader>()
+            .init_resource::<ActiveDialogue>()
+            .add_systems(Update, (talk_system, render_dialogue_system).chain())
+            .add_systems(EguiPrimaryContextPass, dialogue_box_system);
+    }
+}
+
+/// path of the template a unit starts its dialogues with, relative to assets
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Dialogue(pub String);
+
+#[derive(Debug, Clone)]
+pub struct DialogueState {
+    pub speaker: Entity,
+    pub template: Handle<DialogueTemplate>,
+    /// None until the template is loaded and rendered
+    pub page: Option<DialoguePage>,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct ActiveDialogue(pub Option<DialogueState>);
+
+fn is_within_reach(a: &Transform, b: &Transform) -> bool {
+    a.translation.xy().distance(b.translation.xy()) <= UNIT_REACH * TILE_SIZE.x
+}
+
+pub fn talk_system(
+    input: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut active_dialogue: ResMut<ActiveDialogue>,
+    player_query: Query<&Transform, With<Player>>,
+    speaker_query: Query<(Entity, &Transform, &Dialogue), Without<Player>>,
+    mut message_stimulus: MessageWriter<EmotionStimulus>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+
+    // the dialogue ends when the speaker is gone or too far
+    if let Some(state) = &active_dialogue.0
+        && !speaker_query
+            .get(state.speaker)
+            .is_ok_and(|(_, transform, _)| is_within_reach(player_transform, transform))
+    {
+        active_dialogue.0 = None;
+    }
+
+    if !input.just_pressed(TALK_KEY) {
+        return;
+    }
+    if active_dialogue.0.is_some() {
+        active_dialogue.0 = None;
+        return;
+    }
+    let closest_speaker = speaker_query
+        .iter()
+        .filter(|(_, transform, _)| is_within_reach(player_transform, transform))
+        .min_by(|(_, a, _), (_, b, _)| {
+            let a = a.translation.distance_squared(player_transform.translation);
+            let b = b.translation.distance_squared(player_transform.translation);
+            a.total_cmp(&b)
+        });
+    if let Some((speaker, _, dialogue)) = closest_speaker {
+        active_dialogue.0 = Some(DialogueState {
+            speaker,
+            template: asset_server.load(&dialogue.0),
+            page: None,
+        });
+        // being talked to cheers the npc up
+        message_stimulus.write(EmotionStimulus {
+            target: Some(speaker),
+            emotion: Emotion::Joy,
+            amount: TALK_JOY,
+        });
+    }
+}
+
+/// renders the template of the active dialogue once it is loaded
+pub fn render_dialogue_system(
+    mut active_dialogue: ResMut<ActiveDialogue>,
+    templates: Res<Assets<DialogueTemplate>>,
+    world_clock: Res<WorldClock>,
+    weather: Res<Weather>,
+    player_query: Query<&Name, With<Player>>,
+    speaker_query: Query<(&Name, Option<&Emotions>)>,
+) {
+    let Some(state) = &mut active_dialogue.0 else {
+        return;
+    };
+    if state.page.is_some() {
+        return;
+    }
+    let Some(template) = templates.get(&state.template) else {
+        return;
+    };
+    let (Ok(player_name), Ok((npc_name, emotions))) =
+        (player_query.single(), speaker_query.get(state.speaker))
+    else {
+        return;
+    };
+
+    let context = DialogueContext::new(
+        player_name,
+        npc_name,
+        emotions.copied().unwrap_or_default(),
+        &world_clock,
+        &weather,
+    );
+    match context
+        .to_tera()
+        .and_then(|context| template.render(&context))
+    {
+        Ok(page) => state.page = Some(page),
+        Err(error) => {
+            eprintln!("Dialogue failed: {error}");
+            active_dialogue.0 = None;
+        }
+    }
+}
+
+pub fn dialogue_box_system(
+    mut contexts: EguiContexts,
+    asset_server: Res<AssetServer>,
+    mut active_dialogue: ResMut<ActiveDialogue>,
+    name_query: Query<&Name>,
+) -> Result {
+    let Some(state) = &mut active_dialogue.0 else {
+        return Ok(());
+    };
+    let Some(page) = &state.page else {
+        return Ok(());
+    };
+    let title = name_query
+        .get(state.speaker)
+        .map_or("...".to_owned(), |name| name.to_string());
+
+    let mut picked_choice = None;
+    let mut close = false;
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -16.0))
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.label(&page.text);
+            ui.separator();
+            for choice in &page.choices {
+                if ui.button(&choice.label).clicked() {
+                    picked_choice = Some(choice.next.clone());
+                }
+            }
+            if page.choices.is_empty() && ui.button("Close").clicked() {
+                close = true;
+            }
+        });
+
+    match picked_choice {
+        Some(Some(next)) => {
+            state.template = asset_server.load(next);
+            state.page = None;
+        }
+        Some(None) => close = true,
+        None => {}
+    }
+    if close {
+        active_dialogue.0 = None;
+    }
+    Ok(())
+}
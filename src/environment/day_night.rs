@@ -0,0 +1,232 @@
+use crate::{DAY_DURATION, simulation::SimulationTick};
+use bevy::prelude::*;
+
+/// the first tick of a world is at 08:00
+pub const DAY_START_OFFSET_TICKS: u32 = DAY_DURATION / 3;
+// fractions of the day where each phase starts
+pub const DAWN_START: f32 = 0.2;
+pub const DAY_START: f32 = 0.3;
+pub const DUSK_START: f32 = 0.75;
+pub const NIGHT_START: f32 = 0.85;
+
+const NIGHT_COLOR: Color = Color::srgb(0.3, 0.35, 0.6);
+const DAWN_COLOR: Color = Color::srgb(1.0, 0.75, 0.6);
+const DUSK_COLOR: Color = Color::srgb(1.0, 0.6, 0.5);
+/// (fraction of the day, ambient color), the color is interpolated between two keys
+const AMBIENT_COLOR_CURVE: [(f32, Color); 8] = [
+    (0.0, NIGHT_COLOR),
+    (DAWN_START, NIGHT_COLOR),
+    ((DAWN_START + DAY_START) / 2.0, DAWN_COLOR),
+    (DAY_START, Color::WHITE),
+    (DUSK_START, Color::WHITE),
+    ((DUSK_START + NIGHT_START) / 2.0, DUSK_COLOR),
+    (NIGHT_START, NIGHT_COLOR),
+    (1.0, NIGHT_COLOR),
+];
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldClock>()
+            .add_message::<DayPhaseChanged>()
+            .add_systems(FixedUpdate, update_world_clock_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayPhase {
+    Night,
+    Dawn,
+    Day,
+    Dusk,
+}
+impl DayPhase {
+    pub fn from_time_of_day(time_of_day: f32) -> Self {
+        if time_of_day < DAWN_START {
+            DayPhase::Night
+        } else if time_of_day < DAY_START {
+            DayPhase::Dawn
+        } else if time_of_day < DUSK_START {
+            DayPhase::Day
+        } else if time_of_day < NIGHT_START {
+            DayPhase::Dusk
+        } else {
+            DayPhase::Night
+        }
+    }
+}
+
+/// time of the world, computed from the SimulationTick so it is restored with the saves
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WorldClock {
+    /// starts at 1
+    pub day: u32,
+    /// ticks since midnight, from 0 to DAY_DURATION - 1
+    pub tick_of_day: u32,
+    pub phase: DayPhase,
+}
+impl WorldClock {
+    pub fn from_tick(tick: u64) -> Self {
+        let ticks = tick + DAY_START_OFFSET_TICKS as u64;
+        let tick_of_day = (ticks % DAY_DURATION as u64) as u32;
+        Self {
+            day: (ticks / DAY_DURATION as u64) as u32 + 1,
+            tick_of_day,
+            phase: DayPhase::from_time_of_day(tick_of_day as f32 / DAY_DURATION as f32),
+        }
+    }
+
+    /// fraction of the day, 0.0 is midnight and 0.5 is noon
+    pub fn time_of_day(&self) -> f32 {
+        self.tick_of_day as f32 / DAY_DURATION as f32
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.phase == DayPhase::Night
+    }
+
+    /// intensity of the sun, 0.0 at night and 1.0 during the day, useful for solar output
+    pub fn daylight(&self) -> f32 {
+        let time_of_day = self.time_of_day();
+        match self.phase {
+            DayPhase::Night => 0.0,
+            DayPhase::Dawn => (time_of_day - DAWN_START) / (DAY_START - DAWN_START),
+            DayPhase::Day => 1.0,
+            DayPhase::Dusk => 1.0 - (time_of_day - DUSK_START) / (NIGHT_START - DUSK_START),
+        }
+    }
+
+    /// in game hours and minutes
+    pub fn hours_minutes(&self) -> (u32, u32) {
+        let minutes = (self.time_of_day() * 24.0 * 60.0) as u32;
+        (minutes / 60, minutes % 60)
+    }
+}
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self::from_tick(0)
+    }
+}
+
+/// written when the world enters a new DayPhase, systems react to Dawn and Dusk with it (solar output, enemy spawns...)
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayPhaseChanged {
+    pub day: u32,
+    pub previous: DayPhase,
+    pub phase: DayPhase,
+}
+
+pub fn update_world_clock_system(
+    simulation_tick: Res<SimulationTick>,
+    mut world_clock: ResMut<WorldClock>,
+    mut message_phase_changed: MessageWriter<DayPhaseChanged>,
+) {
+    let new_clock = WorldClock::from_tick(simulation_tick.0);
+    if new_clock.phase != world_clock.phase {
+        message_phase_changed.write(DayPhaseChanged {
+            day: new_clock.day,
+            previous: world_clock.phase,
+            phase: new_clock.phase,
+        });
+    }
+    *world_clock = new_clock;
+}
+
+/// color multiplied with the tilemap and the sprites at a given fraction of the day
+pub fn ambient_color(time_of_day: f32) -> Color {
+    let time_of_day = time_of_day.rem_euclid(1.0);
+    for window in AMBIENT_COLOR_CURVE.windows(2) {
+        let [(start, start_color), (end, end_color)] = [window[0], window[1]];
+        if time_of_day < end {
+            let t = if end > start {
+                (time_of_day - start) / (end - start)
+            } else {
+                0.0
+            };
+            return start_color.mix(&end_color, t);
+        }
+    }
+    NIGHT_COLOR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{message::Messages, system::RunSystemOnce};
+
+    #[test]
+    fn test_clock_from_tick() {
+        let clock = WorldClock::from_tick(0);
+        assert_eq!(clock.day, 1);
+        assert_eq!(clock.hours_minutes(), (8, 0));
+        assert_eq!(clock.phase, DayPhase::Day);
+
+        // midnight of the second day
+        let clock = WorldClock::from_tick((DAY_DURATION - DAY_START_OFFSET_TICKS) as u64);
+        assert_eq!(clock.day, 2);
+        assert_eq!(clock.tick_of_day, 0);
+        assert!(clock.is_night());
+        assert_eq!(clock.daylight(), 0.0);
+    }
+
+    #[test]
+    fn test_phases_follow_each_other() {
+        let mut phases = vec![WorldClock::from_tick(0).phase];
+        for tick in 0..DAY_DURATION as u64 {
+            let phase = WorldClock::from_tick(tick).phase;
+            if *phases.last().unwrap() != phase {
+                phases.push(phase);
+            }
+        }
+        assert_eq!(
+            phases,
+            vec![
+                DayPhase::Day,
+                DayPhase::Dusk,
+                DayPhase::Night,
+                DayPhase::Dawn,
+                DayPhase::Day
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambient_color_curve() {
+        assert_eq!(ambient_color(0.5), Color::WHITE);
+        assert_eq!(ambient_color(0.0), NIGHT_COLOR);
+        assert_eq!(ambient_color(0.95), NIGHT_COLOR);
+        assert_eq!(ambient_color((DAWN_START + DAY_START) / 2.0), DAWN_COLOR);
+    }
+
+    #[test]
+    fn test_phase_change_is_written_when_the_clock_crosses_dusk() {
+        let dusk_tick = (0..DAY_DURATION as u64)
+            .find(|tick| WorldClock::from_tick(*tick).phase == DayPhase::Dusk)
+            .unwrap();
+        let mut world = World::new();
+        world.init_resource::<Messages<DayPhaseChanged>>();
+        // the clock of a loaded world starts at the tick of the save
+        world.insert_resource(WorldClock::from_tick(dusk_tick - 1));
+        world.insert_resource(SimulationTick(dusk_tick - 1));
+        let phase_changes = |world: &mut World, tick| {
+            world.resource_mut::<SimulationTick>().0 = tick;
+            world.run_system_once(update_world_clock_system).unwrap();
+            world
+                .resource_mut::<Messages<DayPhaseChanged>>()
+                .drain()
+                .collect::<Vec<_>>()
+        };
+
+        assert!(phase_changes(&mut world, dusk_tick - 1).is_empty());
+        assert_eq!(
+            phase_changes(&mut world, dusk_tick),
+            vec![DayPhaseChanged {
+                day: 1,
+                previous: DayPhase::Day,
+                phase: DayPhase::Dusk,
+            }]
+        );
+        assert!(phase_changes(&mut world, dusk_tick + 1).is_empty());
+    }
+}
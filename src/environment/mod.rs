@@ -0,0 +1,3 @@
+pub mod day_night;
+pub mod render;
+pub mod weather;
@@ -0,0 +1,200 @@
+use crate::environment::{
+    day_night::{WorldClock, ambient_color},
+    weather::{Weather, WeatherKind},
+};
+use bevy::{prelude::*, sprite_render::TilemapChunkTileData};
+use rand::Rng;
+
+/// steps of the ambient color over a day, the tilemap is only rewritten when the step changes
+const AMBIENT_COLOR_STEPS: f32 = 240.0;
+const OVERCAST_COLOR: Color = Color::srgb(0.55, 0.6, 0.7);
+const RAIN_DROP_COLOR: Color = Color::srgba(0.6, 0.7, 1.0, 0.6);
+const RAIN_DROP_SIZE: Vec2 = Vec2::new(1.0, 6.0);
+const RAIN_LAYER: f32 = 10.0;
+/// pixels per second
+const RAIN_FALL_SPEED: f32 = 400.0;
+
+/// tints the tilemap and the AmbientTint sprites with the ambient color of the WorldClock and draws the weather, left out of headless simulations
+pub struct EnvironmentRenderPlugin;
+
+impl Plugin for EnvironmentRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (apply_ambient_color_system, update_rain_system));
+    }
+}
+
+/// sprite lit by the ambient color, holds its own tint under a white light so both can be combined
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct AmbientTint(pub Color);
+
+impl Default for AmbientTint {
+    fn default() -> Self {
+        Self(Color::WHITE)
+    }
+}
+
+/// particle of the rain, kept inside the view of the camera
+#[derive(Component)]
+pub struct RainDrop;
+
+/// how much the clouds darken the ambient color
+fn overcast(kind: WeatherKind) -> f32 {
+    match kind {
+        WeatherKind::Clear => 0.0,
+        WeatherKind::Rain => 0.3,
+        WeatherKind::Storm => 0.55,
+    }
+}
+
+/// (number of drops on screen, horizontal speed relative to the fall speed)
+fn rain_intensity(kind: WeatherKind) -> (usize, f32) {
+    match kind {
+        WeatherKind::Clear => (0, 0.0),
+        WeatherKind::Rain => (150, 0.1),
+        WeatherKind::Storm => (400, 0.5),
+    }
+}
+
+pub fn apply_ambient_color_system(
+    world_clock: Res<WorldClock>,
+    weather: Res<Weather>,
+    mut current_color: Local<Option<Color>>,
+    mut sprite_query: Query<(&mut Sprite, Ref<AmbientTint>)>,
+    mut tilemap_query: Query<&mut TilemapChunkTileData>,
+) {
+    let step = (world_clock.time_of_day() * AMBIENT_COLOR_STEPS).floor() / AMBIENT_COLOR_STEPS;
+    let color = ambient_color(step).mix(&OVERCAST_COLOR, overcast(weather.kind));
+    let color_changed = *current_color != Some(color);
+    *current_color = Some(color);
+
+    // the other sprites keep their color until the next step
+    for (mut sprite, tint) in sprite_query.iter_mut() {
+        if color_changed || tint.is_changed() {
+            sprite.color = multiply_colors(tint.0, color);
+        }
+    }
+
+    // rewriting the tiles uploads the whole chunk again, so new chunks are the only ones updated while the color is stable
+    for mut tile_data in tilemap_query.iter_mut() {
+        if !color_changed && !tile_data.is_added() {
+            continue;
+        }
+        if tile_data.0.iter().flatten().all(|tile| tile.color == color) {
+            continue;
+        }
+        for tile in tile_data.0.iter_mut().flatten() {
+            tile.color = color;
+        }
+    }
+}
+
+pub fn update_rain_system(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    camera_query: Query<(&GlobalTransform, &Projection), With<Camera>>,
+    mut drop_query: Query<(Entity, &mut Transform), With<RainDrop>>,
+    // the rain stops with the simulation when it is paused
+    time: Res<Time>,
+) {
+    let Ok((camera_transform, Projection::Orthographic(projection))) = camera_query.single() else {
+        return;
+    };
+    let view = Rect::from_center_size(
+        camera_transform.translation().xy() + projection.area.center(),
+        projection.area.size(),
+    );
+    let (drop_count, wind) = rain_intensity(weather.kind);
+    let mut rng = rand::rng();
+
+    let mut drops = drop_query.iter_mut();
+    let velocity = Vec2::new(wind, -1.0) * RAIN_FALL_SPEED;
+    let rotation = Quat::from_rotation_z(Vec2::NEG_Y.angle_to(velocity));
+    for _ in 0..drop_count {
+        let Some((_, mut transform)) = drops.next() else {
+            let position = random_point(&mut rng, view);
+            commands.spawn((
+                RainDrop,
+                Sprite {
+                    color: RAIN_DROP_COLOR,
+                    custom_size: Some(RAIN_DROP_SIZE),
+                    ..default()
+                },
+                Transform::from_translation(position.extend(RAIN_LAYER)).with_rotation(rotation),
+            ));
+            continue;
+        };
+        let mut position = transform.translation.xy() + velocity * time.delta_secs();
+        // a drop leaving the view falls again from the top, the camera may also have moved
+        if !view.contains(position) {
+            position = if position.y < view.min.y
+                && position.x >= view.min.x
+                && position.x <= view.max.x
+            {
+                Vec2::new(rng.random_range(view.min.x..=view.max.x), view.max.y)
+            } else {
+                random_point(&mut rng, view)
+            };
+        }
+        transform.translation = position.extend(RAIN_LAYER);
+        transform.rotation = rotation;
+    }
+    // drops left when the rain calms down
+    for (entity, _) in drops {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn multiply_colors(tint: Color, ambient_color: Color) -> Color {
+    let (tint, ambient_color) = (tint.to_linear(), ambient_color.to_linear());
+    LinearRgba::new(
+        tint.red * ambient_color.red,
+        tint.green * ambient_color.green,
+        tint.blue * ambient_color.blue,
+        tint.alpha * ambient_color.alpha,
+    )
+    .into()
+}
+
+fn random_point(rng: &mut impl Rng, rect: Rect) -> Vec2 {
+    Vec2::new(
+        rng.random_range(rect.min.x..=rect.max.x),
+        rng.random_range(rect.min.y..=rect.max.y),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ambient_color_only_touches_the_tinted_sprites_when_the_step_changes() {
+        let mut world = World::new();
+        world.init_resource::<WorldClock>();
+        world.init_resource::<Weather>();
+        let system = world.register_system(apply_ambient_color_system);
+        let ghost = world
+            .spawn((
+                Sprite::default(),
+                AmbientTint(Color::srgba(1.0, 1.0, 1.0, 0.5)),
+            ))
+            .id();
+        let drop = world
+            .spawn(Sprite::from_color(RAIN_DROP_COLOR, RAIN_DROP_SIZE))
+            .id();
+
+        world.run_system(system).unwrap();
+        assert_eq!(world.get::<Sprite>(ghost).unwrap().color.alpha(), 0.5);
+        assert_eq!(world.get::<Sprite>(drop).unwrap().color, RAIN_DROP_COLOR);
+
+        // a damage flash lasts until the next step
+        let flash = Color::srgb(1.0, 0.0, 0.0);
+        world.get_mut::<Sprite>(ghost).unwrap().color = flash;
+        world.run_system(system).unwrap();
+        assert_eq!(world.get::<Sprite>(ghost).unwrap().color, flash);
+
+        // a new tint is applied right away
+        world.get_mut::<AmbientTint>(ghost).unwrap().0 = Color::WHITE;
+        world.run_system(system).unwrap();
+        assert_eq!(world.get::<Sprite>(ghost).unwrap().color.alpha(), 1.0);
+    }
+}
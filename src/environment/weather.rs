@@ -0,0 +1,211 @@
+use crate::{
+    UPS_TARGET, environment::day_night::WorldClock, map::MapSeed, simulation::SimulationTick,
+};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::ops::Range;
+
+const MINUTE_TICKS: u64 = UPS_TARGET as u64 * 60;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .add_message::<WeatherChanged>()
+            // after the startup of the simulation, which restores the tick of a save
+            .add_systems(PostStartup, init_weather_system)
+            .add_systems(FixedUpdate, update_weather_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Storm,
+}
+impl WeatherKind {
+    /// name given to the dialogue templates
+    pub fn dialogue_name(&self) -> &'static str {
+        match self {
+            WeatherKind::Clear => "Soleil",
+            WeatherKind::Rain => "Pluie",
+            WeatherKind::Storm => "Orage",
+        }
+    }
+
+    /// range of ticks the weather lasts
+    pub fn duration_ticks(&self) -> Range<u64> {
+        match self {
+            WeatherKind::Clear => 3 * MINUTE_TICKS..8 * MINUTE_TICKS,
+            WeatherKind::Rain => MINUTE_TICKS..4 * MINUTE_TICKS,
+            WeatherKind::Storm => MINUTE_TICKS / 2..2 * MINUTE_TICKS,
+        }
+    }
+
+    /// (next weather, weight), the weights of a weather add up to 1.0
+    pub fn transitions(&self) -> [(WeatherKind, f32); 2] {
+        match self {
+            WeatherKind::Clear => [(WeatherKind::Rain, 0.8), (WeatherKind::Storm, 0.2)],
+            WeatherKind::Rain => [(WeatherKind::Clear, 0.6), (WeatherKind::Storm, 0.4)],
+            WeatherKind::Storm => [(WeatherKind::Rain, 0.7), (WeatherKind::Clear, 0.3)],
+        }
+    }
+
+    /// multiplier of the movement speed of the units
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 0.85,
+            WeatherKind::Storm => 0.7,
+        }
+    }
+
+    /// multiplier of the sunlight reaching the ground
+    pub fn solar_multiplier(&self) -> f32 {
+        match self {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 0.4,
+            WeatherKind::Storm => 0.1,
+        }
+    }
+}
+
+/// current weather, read by the units, the dialogues and the AI
+/// each weather is drawn from the map seed and its index so the same seed always gives the same forecast
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Weather {
+    pub kind: WeatherKind,
+    pub started_tick: u64,
+    pub ends_tick: u64,
+    /// number of weathers before this one
+    pub index: u64,
+}
+impl Weather {
+    /// weather of the first tick of a world, always clear
+    pub fn first(seed: u64) -> Self {
+        let kind = WeatherKind::Clear;
+        Self {
+            kind,
+            started_tick: 0,
+            ends_tick: weather_rng(seed, 0).random_range(kind.duration_ticks()),
+            index: 0,
+        }
+    }
+
+    pub fn next(&self, seed: u64) -> Self {
+        let index = self.index + 1;
+        let mut rng = weather_rng(seed, index);
+        let roll: f32 = rng.random();
+        let [(first_kind, first_weight), (second_kind, _)] = self.kind.transitions();
+        let kind = if roll < first_weight {
+            first_kind
+        } else {
+            second_kind
+        };
+        Self {
+            kind,
+            started_tick: self.ends_tick,
+            ends_tick: self.ends_tick + rng.random_range(kind.duration_ticks()),
+            index,
+        }
+    }
+
+    /// weather at any tick, replayed from the first one so saves do not need to store it
+    pub fn at_tick(seed: u64, tick: u64) -> Self {
+        let mut weather = Self::first(seed);
+        while tick >= weather.ends_tick {
+            weather = weather.next(seed);
+        }
+        weather
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.kind.speed_multiplier()
+    }
+
+    /// output of a solar panel between 0.0 and 1.0, combining the time of the day and the clouds
+    pub fn solar_power(&self, world_clock: &WorldClock) -> f32 {
+        world_clock.daylight() * self.kind.solar_multiplier()
+    }
+}
+impl Default for Weather {
+    fn default() -> Self {
+        Self::first(0)
+    }
+}
+
+fn weather_rng(seed: u64, index: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ index.wrapping_mul(0xD6E8_FEB8_6659_FD93))
+}
+
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeatherChanged {
+    pub previous: WeatherKind,
+    pub kind: WeatherKind,
+}
+
+fn init_weather_system(
+    mut weather: ResMut<Weather>,
+    map_seed: Res<MapSeed>,
+    simulation_tick: Res<SimulationTick>,
+) {
+    *weather = Weather::at_tick(map_seed.0, simulation_tick.0);
+}
+
+pub fn update_weather_system(
+    mut weather: ResMut<Weather>,
+    map_seed: Res<MapSeed>,
+    simulation_tick: Res<SimulationTick>,
+    mut message_weather_changed: MessageWriter<WeatherChanged>,
+) {
+    if simulation_tick.0 < weather.ends_tick {
+        return;
+    }
+    let previous = weather.kind;
+    *weather = weather.next(map_seed.0);
+    message_weather_changed.write(WeatherChanged {
+        previous,
+        kind: weather.kind,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weather_follows_its_transitions() {
+        let mut weather = Weather::first(42);
+        assert_eq!(weather.kind, WeatherKind::Clear);
+        for _ in 0..100 {
+            let next = weather.next(42);
+            assert_eq!(next.started_tick, weather.ends_tick);
+            assert!(
+                next.kind
+                    .duration_ticks()
+                    .contains(&(next.ends_tick - next.started_tick))
+            );
+            assert!(
+                weather
+                    .kind
+                    .transitions()
+                    .iter()
+                    .any(|(kind, _)| *kind == next.kind)
+            );
+            weather = next;
+        }
+    }
+
+    #[test]
+    fn test_weather_at_tick_is_deterministic() {
+        let weather = Weather::at_tick(7, 100_000);
+        assert!(weather.started_tick <= 100_000 && 100_000 < weather.ends_tick);
+        assert_eq!(weather, Weather::at_tick(7, 100_000));
+        assert_eq!(
+            Weather::at_tick(7, weather.started_tick),
+            Weather::at_tick(7, weather.ends_tick - 1)
+        );
+    }
+}
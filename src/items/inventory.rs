@@ -1,13 +1,15 @@
 use crate::items::{ItemType, Quality};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::mem::replace;
 
 // const DEFAULT_ITEM_STACK_LIMIT: u32 = 10000;
 // const DEFAULT_INVENTORY_SLOTS_QUANTITY_LIMIT: u32 = 10;
 const DEFAULT_ITEM_STACK_LIMIT: u32 = 10;
 const DEFAULT_INVENTORY_SLOTS_QUANTITY_LIMIT: u32 = 5;
+const CHEST_INVENTORY_SLOTS_QUANTITY_LIMIT: u32 = 16;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ItemStack {
     pub item_type: ItemType,
     pub quality: Quality,
@@ -29,12 +31,28 @@ impl ItemStack {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Inventory {
     pub slots: Vec<ItemStack>,
     pub slots_quantity_limit: u32,
 }
 impl Inventory {
+    pub fn with_slots(slots_quantity_limit: u32) -> Self {
+        Self {
+            slots: Vec::new(),
+            slots_quantity_limit,
+        }
+    }
+
+    /// quantity of an ItemType over all slots, whatever the quality
+    pub fn quantity(&self, item_type: ItemType) -> u32 {
+        self.slots
+            .iter()
+            .filter(|slot| slot.item_type == item_type)
+            .map(|slot| slot.quantity)
+            .sum()
+    }
+
     pub fn add(&mut self, item_stack: ItemStack) -> Result<(), ()> {
         // TODO: see if make that we fill existing slots as much as possible event if everything wont fit BUT we have to make sure it's ok to not add everything
         for slot in self.slots.iter_mut() {
@@ -68,6 +86,14 @@ impl Inventory {
         false
     }
 
+    /// whether all the item stacks fit together, each one may take a slot left by the previous ones
+    pub fn has_room_for(&self, item_stacks: &[ItemStack]) -> bool {
+        let mut inventory = self.clone();
+        item_stacks
+            .iter()
+            .all(|item_stack| inventory.add(*item_stack).is_ok())
+    }
+
     /// returns true if there is at least an empty slot or a slot of same type and quality with enough room for the desired quantity to add
     pub fn enough_room(&self, item_stack: ItemStack) -> bool {
         if self.slots.len() < self.slots_quantity_limit as usize {
@@ -97,16 +123,27 @@ impl Inventory {
 }
 impl Default for Inventory {
     fn default() -> Self {
-        Self {
-            slots: Vec::new(),
-            slots_quantity_limit: DEFAULT_INVENTORY_SLOTS_QUANTITY_LIMIT,
-        }
+        Self::with_slots(DEFAULT_INVENTORY_SLOTS_QUANTITY_LIMIT)
     }
 }
 #[derive(Component, Default)]
 pub struct InputInventory(pub Inventory);
 #[derive(Component, Default)]
 pub struct OutputInventory(pub Inventory);
+/// items burned by a `Burner` to power a machine
+#[derive(Component, Default)]
+pub struct FuelInventory(pub Inventory);
+/// items stored in a chest
+#[derive(Component)]
+pub struct ChestInventory(pub Inventory);
+impl Default for ChestInventory {
+    fn default() -> Self {
+        Self(Inventory::with_slots(CHEST_INVENTORY_SLOTS_QUANTITY_LIMIT))
+    }
+}
+/// modules plugged in a machine, only the first `MachineTier::module_slots()` modules have an effect
+#[derive(Component, Default)]
+pub struct ModuleInventory(pub Inventory);
 
 #[cfg(test)]
 mod tests {
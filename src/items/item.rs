@@ -1,22 +1,59 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use crate::UPS_TARGET;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ItemType {
     IronOre,
     CopperOre,
+    Coal,
+    Wood,
 
     IronPlate,
     CopperPlate,
 
     IronGear,
     CopperWire,
+    /// magazine of rounds shot by the turrets
+    Ammo,
+    /// consumed by the labs to research the technologies
+    SciencePack,
+
+    SpeedModule,
+    ProductivityModule,
+    EfficiencyModule,
+    QualityModule,
 }
+impl ItemType {
+    /// number of ticks a burner runs on one item of this type, None if it can't be burned
+    pub fn burn_time_ticks(&self) -> Option<u64> {
+        match self {
+            ItemType::Coal => Some(UPS_TARGET as u64 * 8), // 8 seconds
+            ItemType::Wood => Some(UPS_TARGET as u64 * 4), // 4 seconds
+            _ => None,
+        }
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub fn is_fuel(&self) -> bool {
+        self.burn_time_ticks().is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Quality {
     Perfect,
     Standard,
     Defective,
 }
 
+impl Quality {
+    pub fn upgraded(self) -> Self {
+        match self {
+            Quality::Defective => Quality::Standard,
+            Quality::Standard | Quality::Perfect => Quality::Perfect,
+        }
+    }
+}
+
 impl Default for Quality {
     fn default() -> Self {
         Quality::Standard
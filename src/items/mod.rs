@@ -1,5 +1,7 @@
 pub mod inventory;
 mod item;
 pub mod recipe;
+pub mod statistics;
+pub mod ui;
 
 pub use item::*;
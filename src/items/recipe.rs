@@ -1,11 +1,17 @@
 use crate::{
-    items::{ItemType, Quality, inventory::ItemStack},
+    items::{
+        ItemType, Quality,
+        inventory::{Inventory, ItemStack},
+    },
     map::machine::DEFAULT_ACTION_TIME_TICKS,
 };
 use bevy::ecs::resource::Resource;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 const DEFAULT_CRAFT_TIME_TICKS: u64 = DEFAULT_ACTION_TIME_TICKS;
+const DEFAULT_SMELT_TIME_TICKS: u64 = DEFAULT_ACTION_TIME_TICKS * 2;
+const MODULE_CRAFT_TIME_TICKS: u64 = DEFAULT_ACTION_TIME_TICKS * 10;
 
 // #[derive(Debug, Clone, PartialEq, Eq)]
 // pub struct RecipeItemStack {
@@ -13,24 +19,56 @@ const DEFAULT_CRAFT_TIME_TICKS: u64 = DEFAULT_ACTION_TIME_TICKS;
 //     pub quantity: u32,
 // }
 
+/// which kind of machine can process a recipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecipeCategory {
+    Crafting,
+    Smelting,
+}
+
 #[derive(Debug, Clone)]
 pub struct Recipe {
     pub inputs: Vec<ItemStack>,
     pub outputs: Vec<ItemStack>,
     pub base_craft_time_ticks: u64,
+    pub category: RecipeCategory,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RecipeId {
     IronPlateToIronGear,
     CopperPlateToCopperWire,
+    IronOreToIronPlate,
+    CopperOreToCopperPlate,
+    IronPlateToAmmo,
+    IronGearAndIronPlateToSciencePack,
+    IronGearAndCopperPlateToSpeedModule,
+    IronPlateAndCopperPlateToEfficiencyModule,
+    SpeedModuleAndSciencePackToProductivityModule,
+    EfficiencyModuleAndSciencePackToQualityModule,
 }
 
+/// sorted by RecipeId, so the recipe a machine picks doesn't change between runs
 #[derive(Resource)]
-pub struct RecipeBook(pub HashMap<RecipeId, Recipe>);
+pub struct RecipeBook(pub BTreeMap<RecipeId, Recipe>);
+impl RecipeBook {
+    /// returns the first smelting recipe, in the RecipeId order, whose inputs are all present in the inventory
+    pub fn find_smelting_recipe(&self, inventory: &Inventory) -> Option<RecipeId> {
+        self.0
+            .iter()
+            .filter(|(_, recipe)| recipe.category == RecipeCategory::Smelting)
+            .find(|(_, recipe)| {
+                recipe
+                    .inputs
+                    .iter()
+                    .all(|item_stack| inventory.enough_quantity(*item_stack))
+            })
+            .map(|(recipe_id, _)| *recipe_id)
+    }
+}
 impl Default for RecipeBook {
     fn default() -> Self {
-        let mut recipes = HashMap::new();
+        let mut recipes = BTreeMap::new();
 
         recipes.insert(
             RecipeId::IronPlateToIronGear,
@@ -46,9 +84,219 @@ impl Default for RecipeBook {
                     quality: Quality::Standard,
                 }],
                 base_craft_time_ticks: DEFAULT_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::IronPlateToAmmo,
+            Recipe {
+                inputs: vec![ItemStack {
+                    item_type: ItemType::IronPlate,
+                    quantity: 4,
+                    quality: Quality::Standard,
+                }],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::Ammo,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: DEFAULT_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::IronGearAndIronPlateToSciencePack,
+            Recipe {
+                inputs: vec![
+                    ItemStack {
+                        item_type: ItemType::IronGear,
+                        quantity: 1,
+                        quality: Quality::Standard,
+                    },
+                    ItemStack {
+                        item_type: ItemType::IronPlate,
+                        quantity: 1,
+                        quality: Quality::Standard,
+                    },
+                ],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::SciencePack,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: DEFAULT_CRAFT_TIME_TICKS * 5,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::IronOreToIronPlate,
+            Recipe {
+                inputs: vec![ItemStack {
+                    item_type: ItemType::IronOre,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::IronPlate,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: DEFAULT_SMELT_TIME_TICKS,
+                category: RecipeCategory::Smelting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::CopperOreToCopperPlate,
+            Recipe {
+                inputs: vec![ItemStack {
+                    item_type: ItemType::CopperOre,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::CopperPlate,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: DEFAULT_SMELT_TIME_TICKS,
+                category: RecipeCategory::Smelting,
+            },
+        );
+
+        // the modules are plugged in the machines by the workers
+        recipes.insert(
+            RecipeId::IronGearAndCopperPlateToSpeedModule,
+            Recipe {
+                inputs: vec![
+                    ItemStack {
+                        item_type: ItemType::IronGear,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                    ItemStack {
+                        item_type: ItemType::CopperPlate,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                ],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::SpeedModule,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: MODULE_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::IronPlateAndCopperPlateToEfficiencyModule,
+            Recipe {
+                inputs: vec![
+                    ItemStack {
+                        item_type: ItemType::IronPlate,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                    ItemStack {
+                        item_type: ItemType::CopperPlate,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                ],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::EfficiencyModule,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: MODULE_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::SpeedModuleAndSciencePackToProductivityModule,
+            Recipe {
+                inputs: vec![
+                    ItemStack {
+                        item_type: ItemType::SpeedModule,
+                        quantity: 1,
+                        quality: Quality::Standard,
+                    },
+                    ItemStack {
+                        item_type: ItemType::SciencePack,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                ],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::ProductivityModule,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: MODULE_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
+            },
+        );
+
+        recipes.insert(
+            RecipeId::EfficiencyModuleAndSciencePackToQualityModule,
+            Recipe {
+                inputs: vec![
+                    ItemStack {
+                        item_type: ItemType::EfficiencyModule,
+                        quantity: 1,
+                        quality: Quality::Standard,
+                    },
+                    ItemStack {
+                        item_type: ItemType::SciencePack,
+                        quantity: 5,
+                        quality: Quality::Standard,
+                    },
+                ],
+                outputs: vec![ItemStack {
+                    item_type: ItemType::QualityModule,
+                    quantity: 1,
+                    quality: Quality::Standard,
+                }],
+                base_craft_time_ticks: MODULE_CRAFT_TIME_TICKS,
+                category: RecipeCategory::Crafting,
             },
         );
 
         RecipeBook(recipes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smelting_recipe_follows_the_recipe_id_order() {
+        let recipe_book = RecipeBook::default();
+        let mut inventory = Inventory::default();
+        inventory
+            .add(ItemStack::new(ItemType::CopperOre, Quality::Standard, 1))
+            .unwrap();
+        assert_eq!(
+            recipe_book.find_smelting_recipe(&inventory),
+            Some(RecipeId::CopperOreToCopperPlate)
+        );
+
+        // with the ingredients of both recipes, the iron comes first
+        inventory
+            .add(ItemStack::new(ItemType::IronOre, Quality::Standard, 1))
+            .unwrap();
+        for _ in 0..10 {
+            assert_eq!(
+                RecipeBook::default().find_smelting_recipe(&inventory),
+                Some(RecipeId::IronOreToIronPlate)
+            );
+        }
+    }
+}
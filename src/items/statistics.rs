@@ -0,0 +1,287 @@
+use crate::{
+    UPS_TARGET,
+    items::{ItemType, inventory::ItemStack},
+    simulation::SimulationTick,
+};
+use bevy::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Write,
+    fs, io,
+    path::Path,
+};
+
+/// where the statistics window exports its CSV
+pub const STATISTICS_CSV_PATH: &str = "saves/statistics.csv";
+
+/// items produced and consumed during some ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItemFlow {
+    pub produced: u64,
+    pub consumed: u64,
+}
+
+/// duration covered by a TimeSeries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatisticsWindow {
+    OneMinute,
+    TenMinutes,
+    OneHour,
+}
+impl StatisticsWindow {
+    pub const ALL: [StatisticsWindow; 3] = [
+        StatisticsWindow::OneMinute,
+        StatisticsWindow::TenMinutes,
+        StatisticsWindow::OneHour,
+    ];
+    /// number of buckets of a window, one point of its graph each
+    pub const BUCKETS: u64 = 60;
+
+    pub fn ticks(&self) -> u64 {
+        let minutes = match self {
+            StatisticsWindow::OneMinute => 1,
+            StatisticsWindow::TenMinutes => 10,
+            StatisticsWindow::OneHour => 60,
+        };
+        minutes * 60 * UPS_TARGET as u64
+    }
+
+    pub fn bucket_ticks(&self) -> u64 {
+        self.ticks() / Self::BUCKETS
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatisticsWindow::OneMinute => "1m",
+            StatisticsWindow::TenMinutes => "10m",
+            StatisticsWindow::OneHour => "1h",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatisticsBucket {
+    pub start_tick: u64,
+    /// ticks recorded in the bucket, less than bucket_ticks while it is filling
+    pub ticks: u64,
+    pub flows: BTreeMap<ItemType, ItemFlow>,
+}
+impl StatisticsBucket {
+    /// items of one type produced and consumed per minute during the bucket
+    pub fn rate_per_minute(&self, item_type: ItemType) -> (f64, f64) {
+        let flow = self.flows.get(&item_type).copied().unwrap_or_default();
+        let minutes = self.ticks.max(1) as f64 / (UPS_TARGET as f64 * 60.0);
+        (
+            flow.produced as f64 / minutes,
+            flow.consumed as f64 / minutes,
+        )
+    }
+}
+
+/// rolling history of a StatisticsWindow, oldest bucket first, the last one is still filling
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeSeries {
+    pub buckets: VecDeque<StatisticsBucket>,
+}
+impl TimeSeries {
+    fn record(
+        &mut self,
+        window: StatisticsWindow,
+        tick: u64,
+        flows: &BTreeMap<ItemType, ItemFlow>,
+    ) {
+        let bucket_ticks = window.bucket_ticks();
+        if self
+            .buckets
+            .back()
+            .is_none_or(|bucket| tick >= bucket.start_tick + bucket_ticks)
+        {
+            self.buckets.push_back(StatisticsBucket {
+                start_tick: tick - tick % bucket_ticks,
+                ..default()
+            });
+            if self.buckets.len() > StatisticsWindow::BUCKETS as usize {
+                self.buckets.pop_front();
+            }
+        }
+        let Some(bucket) = self.buckets.back_mut() else {
+            return;
+        };
+        bucket.ticks += 1;
+        for (item_type, flow) in flows {
+            let total = bucket.flows.entry(*item_type).or_default();
+            total.produced += flow.produced;
+            total.consumed += flow.consumed;
+        }
+    }
+}
+
+/// every item produced and consumed by machines, fuel included : the totals since the start of the simulation,
+/// and the rolling time series of each StatisticsWindow ; the player can't craft, machines are the only producers
+#[derive(Resource, Default, Debug)]
+pub struct ProductionStatistics {
+    pub produced: HashMap<ItemType, u64>,
+    pub consumed: HashMap<ItemType, u64>,
+    /// what the current tick produced and consumed, moved to the time series at the end of the tick
+    pub current_tick: BTreeMap<ItemType, ItemFlow>,
+    series: [TimeSeries; 3],
+}
+impl ProductionStatistics {
+    pub fn record_produced(&mut self, item_stacks: &[ItemStack]) {
+        for item_stack in item_stacks {
+            *self.produced.entry(item_stack.item_type).or_default() += item_stack.quantity as u64;
+            self.current_tick
+                .entry(item_stack.item_type)
+                .or_default()
+                .produced += item_stack.quantity as u64;
+        }
+    }
+
+    pub fn record_consumed(&mut self, item_stacks: &[ItemStack]) {
+        for item_stack in item_stacks {
+            *self.consumed.entry(item_stack.item_type).or_default() += item_stack.quantity as u64;
+            self.current_tick
+                .entry(item_stack.item_type)
+                .or_default()
+                .consumed += item_stack.quantity as u64;
+        }
+    }
+
+    pub fn series(&self, window: StatisticsWindow) -> &TimeSeries {
+        &self.series[window as usize]
+    }
+
+    /// adds the current tick to every time series
+    pub fn end_tick(&mut self, tick: u64) {
+        let flows = std::mem::take(&mut self.current_tick);
+        for window in StatisticsWindow::ALL {
+            self.series[window as usize].record(window, tick, &flows);
+        }
+    }
+
+    /// one line per bucket of each window and item type : window,start_tick,ticks,item,produced,consumed
+    pub fn to_csv(&self) -> String {
+        let mut csv = "window,start_tick,ticks,item,produced,consumed\n".to_owned();
+        for window in StatisticsWindow::ALL {
+            for bucket in &self.series(window).buckets {
+                for (item_type, flow) in &bucket.flows {
+                    let _ = writeln!(
+                        csv,
+                        "{},{},{},{:?},{},{}",
+                        window.label(),
+                        bucket.start_tick,
+                        bucket.ticks,
+                        item_type,
+                        flow.produced,
+                        flow.consumed
+                    );
+                }
+            }
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_csv())
+    }
+
+    /// one line per item type with its totals and its rate per minute over `ticks` ticks
+    pub fn report(&self, ticks: u64) -> String {
+        let item_types: BTreeSet<ItemType> = self
+            .produced
+            .keys()
+            .chain(self.consumed.keys())
+            .copied()
+            .collect();
+        let minutes = ticks as f64 / (UPS_TARGET as f64 * 60.0);
+
+        let mut report = format!(
+            "{:<20} {:>10} {:>10} {:>12}\n",
+            "item", "produced", "consumed", "net / min"
+        );
+        for item_type in item_types {
+            let produced = self.produced.get(&item_type).copied().unwrap_or(0);
+            let consumed = self.consumed.get(&item_type).copied().unwrap_or(0);
+            let net_per_minute = if minutes > 0.0 {
+                (produced as f64 - consumed as f64) / minutes
+            } else {
+                0.0
+            };
+            report += &format!(
+                "{:<20} {:>10} {:>10} {:>12.1}\n",
+                format!("{:?}", item_type),
+                produced,
+                consumed,
+                net_per_minute
+            );
+        }
+        report
+    }
+}
+
+/// runs after every system of the tick, so the time series see everything it recorded
+pub fn end_statistics_tick_system(
+    mut statistics: ResMut<ProductionStatistics>,
+    simulation_tick: Res<SimulationTick>,
+) {
+    statistics.end_tick(simulation_tick.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Quality;
+
+    #[test]
+    fn test_report_lists_each_item_once() {
+        let mut statistics = ProductionStatistics::default();
+        statistics.record_produced(&[
+            ItemStack::new(ItemType::IronPlate, Quality::Standard, 2),
+            ItemStack::new(ItemType::IronPlate, Quality::Perfect, 1),
+        ]);
+        statistics.record_consumed(&[ItemStack::new(ItemType::IronOre, Quality::Standard, 3)]);
+        assert_eq!(statistics.produced[&ItemType::IronPlate], 3);
+
+        // one minute of simulation
+        let report = statistics.report(UPS_TARGET as u64 * 60);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // sorted by item type, ores first
+        assert!(lines[1].starts_with("IronOre"));
+        assert!(lines[1].ends_with("-3.0"));
+        assert!(lines[2].starts_with("IronPlate"));
+        assert!(lines[2].ends_with("3.0"));
+    }
+
+    #[test]
+    fn test_time_series_keep_the_last_buckets_of_each_window() {
+        let mut statistics = ProductionStatistics::default();
+        let window = StatisticsWindow::OneMinute;
+        let ticks = window.ticks() + window.bucket_ticks() * 2;
+        for tick in 0..ticks {
+            statistics.record_produced(&[ItemStack::new(ItemType::IronGear, Quality::Standard, 1)]);
+            statistics.end_tick(tick);
+        }
+        assert!(statistics.current_tick.is_empty());
+
+        let buckets = &statistics.series(window).buckets;
+        assert_eq!(buckets.len(), StatisticsWindow::BUCKETS as usize);
+        // the two oldest buckets are gone
+        assert_eq!(buckets[0].start_tick, window.bucket_ticks() * 2);
+        assert_eq!(
+            buckets[0].rate_per_minute(ItemType::IronGear),
+            (1800.0, 0.0)
+        );
+        // a bucket of the hour lasts a minute, the second one is still filling
+        let hour_buckets = &statistics.series(StatisticsWindow::OneHour).buckets;
+        assert_eq!(hour_buckets.len(), 2);
+        assert_eq!(hour_buckets[1].ticks, window.bucket_ticks() * 2);
+
+        let csv = statistics.to_csv();
+        assert!(csv.starts_with("window,start_tick,ticks,item,produced,consumed\n"));
+        assert!(csv.contains("\n1h,0,1800,IronGear,1800,0\n"));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::items::{
+    ItemType,
+    statistics::{ProductionStatistics, STATISTICS_CSV_PATH, StatisticsWindow, TimeSeries},
+};
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::{collections::BTreeSet, path::Path};
+
+/// opens or closes the production statistics
+pub const STATISTICS_KEY: KeyCode = KeyCode::KeyP;
+const CHART_SIZE: egui::Vec2 = egui::vec2(480.0, 140.0);
+/// colors of the lines, by order of the item types
+const LINE_COLORS: [egui::Color32; 8] = [
+    egui::Color32::from_rgb(230, 159, 0),
+    egui::Color32::from_rgb(86, 180, 233),
+    egui::Color32::from_rgb(0, 158, 115),
+    egui::Color32::from_rgb(240, 228, 66),
+    egui::Color32::from_rgb(0, 114, 178),
+    egui::Color32::from_rgb(213, 94, 0),
+    egui::Color32::from_rgb(204, 121, 167),
+    egui::Color32::from_rgb(200, 200, 200),
+];
+
+/// egui window with the production and consumption graphs of the last minute, ten minutes or hour
+pub struct StatisticsUiPlugin;
+
+impl Plugin for StatisticsUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StatisticsView>()
+            .add_systems(Update, toggle_statistics_system)
+            .add_systems(EguiPrimaryContextPass, statistics_window_system);
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct StatisticsView {
+    pub is_open: bool,
+    pub window: StatisticsWindow,
+}
+impl Default for StatisticsView {
+    fn default() -> Self {
+        Self {
+            is_open: false,
+            window: StatisticsWindow::TenMinutes,
+        }
+    }
+}
+
+pub fn toggle_statistics_system(
+    input: Res<ButtonInput<KeyCode>>,
+    mut statistics_view: ResMut<StatisticsView>,
+) {
+    if input.just_pressed(STATISTICS_KEY) {
+        statistics_view.is_open = !statistics_view.is_open;
+    }
+}
+
+/// one line per item type of the rate per minute, produced or consumed, in each bucket of the series
+fn line_chart(
+    ui: &mut egui::Ui,
+    series: &TimeSeries,
+    item_types: &[ItemType],
+    rate: impl Fn((f64, f64)) -> f64,
+) {
+    let (response, painter) = ui.allocate_painter(CHART_SIZE, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_stroke(
+        rect,
+        0.0,
+        ui.visuals().widgets.noninteractive.bg_stroke,
+        egui::StrokeKind::Inside,
+    );
+
+    let rates: Vec<Vec<f64>> = item_types
+        .iter()
+        .map(|item_type| {
+            series
+                .buckets
+                .iter()
+                .map(|bucket| rate(bucket.rate_per_minute(*item_type)))
+                .collect()
+        })
+        .collect();
+    let max_rate = rates.iter().flatten().copied().fold(0.0, f64::max);
+    if max_rate <= 0.0 {
+        return;
+    }
+    painter.text(
+        rect.left_top() + egui::vec2(4.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        format!("{max_rate:.0} / min"),
+        egui::FontId::monospace(10.0),
+        ui.visuals().weak_text_color(),
+    );
+
+    // the newest bucket is on the right edge, the window always covers the whole width
+    let step = rect.width() / (StatisticsWindow::BUCKETS - 1) as f32;
+    for (index, item_rates) in rates.iter().enumerate() {
+        let offset = StatisticsWindow::BUCKETS as usize - item_rates.len();
+        let points: Vec<egui::Pos2> = item_rates
+            .iter()
+            .enumerate()
+            .map(|(bucket, rate)| {
+                egui::pos2(
+                    rect.left() + (offset + bucket) as f32 * step,
+                    rect.bottom() - (rate / max_rate) as f32 * rect.height(),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.5, LINE_COLORS[index % LINE_COLORS.len()]),
+        ));
+    }
+}
+
+pub fn statistics_window_system(
+    mut contexts: EguiContexts,
+    mut statistics_view: ResMut<StatisticsView>,
+    statistics: Res<ProductionStatistics>,
+) -> Result {
+    if !statistics_view.is_open {
+        return Ok(());
+    }
+    let mut window = statistics_view.window;
+    let series = statistics.series(window);
+    let item_types: Vec<ItemType> = series
+        .buckets
+        .iter()
+        .flat_map(|bucket| bucket.flows.keys().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut is_open = true;
+    egui::Window::new("Production")
+        .open(&mut is_open)
+        .resizable(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.horizontal(|ui| {
+                for choice in StatisticsWindow::ALL {
+                    ui.selectable_value(&mut window, choice, choice.label());
+                }
+                ui.separator();
+                if ui.button("Export CSV").clicked() {
+                    match statistics.write_csv(Path::new(STATISTICS_CSV_PATH)) {
+                        Ok(()) => println!("Statistics exported to {STATISTICS_CSV_PATH}"),
+                        Err(error) => eprintln!("Statistics export failed: {error}"),
+                    }
+                }
+            });
+            ui.label("Produced");
+            line_chart(ui, series, &item_types, |(produced, _)| produced);
+            ui.label("Consumed");
+            line_chart(ui, series, &item_types, |(_, consumed)| consumed);
+
+            // legend, with the rate of the last complete bucket
+            let last_bucket = series.buckets.iter().rev().nth(1);
+            egui::Grid::new("statistics_legend").show(ui, |ui| {
+                for (index, item_type) in item_types.iter().enumerate() {
+                    let (produced, consumed) =
+                        last_bucket.map_or((0.0, 0.0), |bucket| bucket.rate_per_minute(*item_type));
+                    ui.colored_label(
+                        LINE_COLORS[index % LINE_COLORS.len()],
+                        format!("{item_type:?}"),
+                    );
+                    ui.label(format!("+{produced:.1} / min"));
+                    ui.label(format!("-{consumed:.1} / min"));
+                    ui.end_row();
+                }
+            });
+        });
+
+    statistics_view.window = window;
+    statistics_view.is_open = is_open;
+    Ok(())
+}
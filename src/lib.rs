@@ -1,6 +1,15 @@
 pub mod camera;
+pub mod dialogue;
+pub mod environment;
 pub mod items;
+pub mod logistics;
 pub mod map;
+#[cfg(feature = "profiler")]
+pub mod profiler;
+pub mod research;
+pub mod save;
+pub mod simulation;
+pub mod time_control;
 pub mod units;
 
 pub const UPS_TARGET: u32 = 30; // 30 ticks per second
@@ -0,0 +1,613 @@
+use crate::{
+    UPS_TARGET,
+    items::{
+        ItemType,
+        inventory::{
+            ChestInventory, FuelInventory, InputInventory, Inventory, ItemStack, ModuleInventory,
+            OutputInventory,
+        },
+        recipe::{RecipeBook, RecipeCategory},
+    },
+    logistics::network::{LogisticsChest, LogisticsNetworks, update_logistics_networks_system},
+    map::{
+        TileCoordinates, absolute_coord_to_tile_coord,
+        ghost::{Ghost, build_ghosts_system},
+        machine::{BeltMachine, Burner, CraftingMachine, FurnaceMachine},
+        modules::{MachineTier, ModuleEffect},
+    },
+    research::{
+        lab::Lab,
+        technology::{Research, Unlock},
+    },
+    simulation::SimulationTick,
+    units::{
+        pathfinding::resolve_goal_tiles_system,
+        turrets::Turret,
+        worker::{ActiveJob, Worker, assign_jobs_system, has_new_idle_worker, run_jobs_system},
+    },
+};
+use bevy::{ecs::query::QueryData, prelude::*};
+use std::collections::{HashMap, HashSet};
+
+/// ticks between two plannings of the job queue
+const JOB_PLANNING_INTERVAL_TICKS: u64 = UPS_TARGET as u64;
+/// most items a worker carries in one trip
+pub const WORKER_CARRY_QUANTITY: u32 = 5;
+/// a machine asks for an input item while it has less than this quantity
+const INPUT_STOCK: u32 = 5;
+/// a burner asks for fuel while it has less than this quantity
+const FUEL_STOCK: u32 = 2;
+
+pub struct JobsPlugin;
+
+impl Plugin for JobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<JobQueue>()
+            .init_resource::<LogisticsNetworks>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (update_logistics_networks_system, plan_jobs_system)
+                        .chain()
+                        .run_if(is_planning_tick),
+                    assign_jobs_system.run_if(is_planning_tick.or(has_new_idle_worker)),
+                    run_jobs_system,
+                    build_ghosts_system,
+                )
+                    .chain()
+                    // the workers head to their new target in the same tick
+                    .before(resolve_goal_tiles_system),
+            );
+    }
+}
+
+/// in the order the jobs are given to the workers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobKind {
+    /// brings fuel to the FuelInventory of a Burner
+    Refuel,
+    /// brings the items of its cost to a Ghost
+    Build,
+    /// brings items to a requester chest from the supply of its logistics network
+    Deliver,
+    /// brings unlocked modules to the ModuleInventory of a machine with free module slots
+    Equip,
+    /// brings items to the InputInventory of a machine, or to a chest when no machine needs them
+    Haul,
+}
+
+/// items to carry from a structure (or from the worker itself) to another one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Job {
+    pub kind: JobKind,
+    pub from: Entity,
+    pub to: Entity,
+    pub item_stack: ItemStack,
+}
+
+/// jobs waiting for a worker, planned again every JOB_PLANNING_INTERVAL_TICKS
+#[derive(Resource, Default, Debug)]
+pub struct JobQueue(pub Vec<Job>);
+
+/// items a job can be planned from, the quantities already promised to a job are removed
+struct JobSource {
+    entity: Entity,
+    tile: TileCoordinates,
+    item_stacks: Vec<ItemStack>,
+    /// chests only give items to the machines, the ghosts and the requesters, never to another chest
+    /// except the providers, which empty themselves in a storage chest of their network
+    is_chest: bool,
+    logistics_chest: Option<LogisticsChest>,
+    network: Option<usize>,
+}
+
+/// takes up to `quantity` items accepted by `accepts` from the source closest to the target,
+/// only from the supply of the network when one is given
+fn take_from_sources(
+    sources: &mut [JobSource],
+    target: Entity,
+    target_tile: TileCoordinates,
+    network: Option<usize>,
+    accepts: impl Fn(ItemType) -> bool,
+    quantity: u32,
+) -> Option<(Entity, ItemStack)> {
+    let (source, slot) = sources
+        .iter_mut()
+        .filter(|source| source.entity != target)
+        .filter(|source| network.is_none_or(|network| source.network == Some(network)))
+        .filter_map(|source| {
+            let slot = source
+                .item_stacks
+                .iter()
+                .position(|item_stack| item_stack.quantity > 0 && accepts(item_stack.item_type))?;
+            Some((source, slot))
+        })
+        .min_by_key(|(source, _)| {
+            (source.tile.x - target_tile.x).abs() + (source.tile.y - target_tile.y).abs()
+        })?;
+    let item_stack = &mut source.item_stacks[slot];
+    let taken = ItemStack {
+        quantity: item_stack.quantity.min(quantity),
+        ..*item_stack
+    };
+    item_stack.quantity -= taken.quantity;
+    Some((source.entity, taken))
+}
+
+/// items a machine takes in its InputInventory
+fn wanted_inputs(machine: &MachineNeedsItem, recipe_book: &RecipeBook) -> Vec<ItemType> {
+    let mut item_types: Vec<ItemType> = recipe_book
+        .0
+        .iter()
+        .filter(|(recipe_id, recipe)| {
+            machine
+                .crafting_machine
+                .is_some_and(|crafting_machine| crafting_machine.recipe_id == Some(**recipe_id))
+                || (machine.is_furnace && recipe.category == RecipeCategory::Smelting)
+        })
+        .flat_map(|(_, recipe)| recipe.inputs.iter().map(|item_stack| item_stack.item_type))
+        .collect();
+    if machine.is_turret {
+        item_types.push(ItemType::Ammo);
+    }
+    if machine.is_lab {
+        item_types.push(ItemType::SciencePack);
+    }
+    // a machine may need the same item for several recipes
+    item_types.sort();
+    item_types.dedup();
+    item_types
+}
+
+/// what a machine may ask the workers for
+#[derive(QueryData)]
+pub struct MachineNeeds {
+    entity: Entity,
+    transform: &'static Transform,
+    input_inventory: Option<&'static InputInventory>,
+    fuel_inventory: Option<&'static FuelInventory>,
+    crafting_machine: Option<&'static CraftingMachine>,
+    is_furnace: Has<FurnaceMachine>,
+    is_turret: Has<Turret>,
+    is_lab: Has<Lab>,
+    is_burner: Has<Burner>,
+    tier: Option<&'static MachineTier>,
+    module_inventory: Option<&'static ModuleInventory>,
+}
+
+/// machines fed by the workers
+type NeedyMachine = Or<(With<InputInventory>, With<Burner>, With<ModuleInventory>)>;
+
+fn is_module(item_type: ItemType) -> bool {
+    ModuleEffect::from_item_type(item_type).is_some()
+}
+
+fn is_planning_tick(simulation_tick: Res<SimulationTick>) -> bool {
+    simulation_tick
+        .0
+        .is_multiple_of(JOB_PLANNING_INTERVAL_TICKS)
+}
+
+/// rebuilds the job queue from what the burners, the ghosts and the machines need, and from what the machines produced
+pub fn plan_jobs_system(
+    mut job_queue: ResMut<JobQueue>,
+    output_query: Query<(Entity, &Transform, &OutputInventory), Without<BeltMachine>>,
+    chest_query: Query<(Entity, &Transform, &ChestInventory, Option<&LogisticsChest>)>,
+    ghost_query: Query<(Entity, &Transform, &Ghost)>,
+    machine_query: Query<MachineNeeds, NeedyMachine>,
+    worker_query: Query<(Entity, &Transform, &Inventory, &ActiveJob), With<Worker>>,
+    recipe_book: Res<RecipeBook>,
+    research: Res<Research>,
+    logistics_networks: Res<LogisticsNetworks>,
+) {
+    job_queue.0.clear();
+
+    // the targets and the items of the jobs in progress are not planned again
+    let mut reserved_targets: HashSet<(Entity, ItemType)> = HashSet::new();
+    let mut reserved_items: HashMap<(Entity, ItemType), u32> = HashMap::new();
+    for (_, _, _, active_job) in worker_query.iter() {
+        if let Some((job, _)) = active_job.0 {
+            reserved_targets.insert((job.to, job.item_stack.item_type));
+            *reserved_items
+                .entry((job.from, job.item_stack.item_type))
+                .or_default() += job.item_stack.quantity;
+        }
+    }
+
+    let mut sources: Vec<JobSource> = output_query
+        .iter()
+        .map(|(entity, transform, inventory)| (entity, transform, &inventory.0, None))
+        .chain(
+            chest_query
+                .iter()
+                // the items of a requester are kept for itself
+                .filter(|(_, _, _, logistics_chest)| {
+                    logistics_chest.is_none_or(LogisticsChest::is_supply)
+                })
+                .map(|(entity, transform, inventory, logistics_chest)| {
+                    (entity, transform, &inventory.0, Some(logistics_chest))
+                }),
+        )
+        .map(|(entity, transform, inventory, chest)| JobSource {
+            entity,
+            tile: absolute_coord_to_tile_coord((*transform).into()),
+            item_stacks: inventory
+                .slots
+                .iter()
+                .map(|item_stack| {
+                    let reserved = reserved_items
+                        .get_mut(&(entity, item_stack.item_type))
+                        .map_or(0, |reserved| {
+                            let taken = (*reserved).min(item_stack.quantity);
+                            *reserved -= taken;
+                            taken
+                        });
+                    ItemStack {
+                        quantity: item_stack.quantity - reserved,
+                        ..*item_stack
+                    }
+                })
+                .collect(),
+            is_chest: chest.is_some(),
+            logistics_chest: chest.flatten().cloned(),
+            network: logistics_networks.network_of(entity),
+        })
+        .collect();
+    // same order every run so the same jobs are planned
+    sources.sort_by_key(|source| (source.tile.y, source.tile.x));
+
+    let mut jobs = Vec::new();
+    let mut plan = |kind: JobKind,
+                    to: Entity,
+                    to_transform: &Transform,
+                    accepts: &dyn Fn(ItemType) -> bool,
+                    quantity: u32| {
+        let to_tile = absolute_coord_to_tile_coord((*to_transform).into());
+        // a requester is only fed by its own network
+        let network = (kind == JobKind::Deliver)
+            .then(|| logistics_networks.network_of(to))
+            .flatten();
+        if let Some((from, item_stack)) =
+            take_from_sources(&mut sources, to, to_tile, network, accepts, quantity)
+        {
+            jobs.push(Job {
+                kind,
+                from,
+                to,
+                item_stack,
+            });
+        }
+    };
+
+    let mut machines: Vec<_> = machine_query.iter().collect();
+    machines.sort_by_key(|machine| {
+        let tile = absolute_coord_to_tile_coord((*machine.transform).into());
+        (tile.y, tile.x)
+    });
+    for machine in machines.iter().filter(|machine| machine.is_burner) {
+        let Some(fuel_inventory) = machine.fuel_inventory else {
+            continue;
+        };
+        let fuel: u32 = fuel_inventory
+            .0
+            .slots
+            .iter()
+            .filter(|item_stack| item_stack.item_type.is_fuel())
+            .map(|item_stack| item_stack.quantity)
+            .sum();
+        let refueling = reserved_targets
+            .iter()
+            .any(|(target, item_type)| *target == machine.entity && item_type.is_fuel());
+        if fuel >= FUEL_STOCK || refueling {
+            continue;
+        }
+        plan(
+            JobKind::Refuel,
+            machine.entity,
+            machine.transform,
+            &|item_type: ItemType| item_type.is_fuel(),
+            WORKER_CARRY_QUANTITY,
+        );
+    }
+
+    let mut ghosts: Vec<_> = ghost_query.iter().collect();
+    ghosts.sort_by_key(|(_, _, ghost)| (ghost.blueprint.anchor.y, ghost.blueprint.anchor.x));
+    for (entity, transform, ghost) in ghosts {
+        for missing in &ghost.missing {
+            if reserved_targets.contains(&(entity, missing.item_type)) {
+                continue;
+            }
+            plan(
+                JobKind::Build,
+                entity,
+                transform,
+                &|item_type: ItemType| item_type == missing.item_type,
+                missing.quantity.min(WORKER_CARRY_QUANTITY),
+            );
+        }
+    }
+
+    let mut chests: Vec<_> = chest_query.iter().collect();
+    chests.sort_by_key(|(_, transform, ..)| {
+        let tile = absolute_coord_to_tile_coord((**transform).into());
+        (tile.y, tile.x)
+    });
+    for (entity, transform, chest_inventory, logistics_chest) in &chests {
+        let Some(logistics_chest) = logistics_chest else {
+            continue;
+        };
+        for missing in logistics_chest.missing(chest_inventory) {
+            if reserved_targets.contains(&(*entity, missing.item_type)) {
+                continue;
+            }
+            plan(
+                JobKind::Deliver,
+                *entity,
+                transform,
+                &|item_type: ItemType| item_type == missing.item_type,
+                missing.quantity.min(WORKER_CARRY_QUANTITY),
+            );
+        }
+    }
+
+    for machine in &machines {
+        let (Some(tier), Some(module_inventory)) = (machine.tier, machine.module_inventory) else {
+            continue;
+        };
+        let modules: u32 = module_inventory
+            .0
+            .slots
+            .iter()
+            .map(|item_stack| item_stack.quantity)
+            .sum();
+        let equipping = reserved_targets
+            .iter()
+            .any(|(target, item_type)| *target == machine.entity && is_module(*item_type));
+        if modules >= tier.module_slots() || equipping {
+            continue;
+        }
+        plan(
+            JobKind::Equip,
+            machine.entity,
+            machine.transform,
+            &|item_type: ItemType| {
+                is_module(item_type) && research.is_unlocked(Unlock::Module(item_type))
+            },
+            (tier.module_slots() - modules).min(WORKER_CARRY_QUANTITY),
+        );
+    }
+
+    for machine in &machines {
+        let Some(input_inventory) = machine.input_inventory else {
+            continue;
+        };
+        for item_type in wanted_inputs(machine, &recipe_book) {
+            let quantity = input_inventory.0.quantity(item_type);
+            if quantity >= INPUT_STOCK || reserved_targets.contains(&(machine.entity, item_type)) {
+                continue;
+            }
+            plan(
+                JobKind::Haul,
+                machine.entity,
+                machine.transform,
+                &|accepted: ItemType| accepted == item_type,
+                (INPUT_STOCK - quantity).min(WORKER_CARRY_QUANTITY),
+            );
+        }
+    }
+
+    // full trips of what no machine needs go to the closest chest with room, like the items left in the idle workers
+    // and the providers are emptied in a storage chest of their network
+    let closest_chest = |tile: TileCoordinates, item_stack: ItemStack, network: Option<usize>| {
+        chests
+            .iter()
+            .filter(|(entity, _, chest_inventory, logistics_chest)| {
+                let is_storage = match network {
+                    Some(network) => {
+                        logistics_networks.network_of(*entity) == Some(network)
+                            && *logistics_chest == Some(&LogisticsChest::Storage)
+                    }
+                    None => logistics_chest
+                        .is_none_or(|logistics_chest| *logistics_chest == LogisticsChest::Storage),
+                };
+                is_storage && chest_inventory.0.enough_room(item_stack)
+            })
+            .min_by_key(|(_, transform, ..)| {
+                let chest_tile = absolute_coord_to_tile_coord((**transform).into());
+                (chest_tile.x - tile.x).abs() + (chest_tile.y - tile.y).abs()
+            })
+            .map(|(entity, ..)| *entity)
+    };
+    for source in &sources {
+        let is_provider = source.logistics_chest == Some(LogisticsChest::Provider);
+        if source.is_chest && !is_provider {
+            continue;
+        }
+        for item_stack in &source.item_stacks {
+            // a provider gives everything, a machine waits for a full trip
+            let quantity = if is_provider {
+                item_stack.quantity.min(WORKER_CARRY_QUANTITY)
+            } else if item_stack.quantity >= WORKER_CARRY_QUANTITY {
+                WORKER_CARRY_QUANTITY
+            } else {
+                0
+            };
+            if quantity == 0 || (is_provider && source.network.is_none()) {
+                continue;
+            }
+            let item_stack = ItemStack {
+                quantity,
+                ..*item_stack
+            };
+            let network = source.network.filter(|_| is_provider);
+            if let Some(chest) = closest_chest(source.tile, item_stack, network) {
+                jobs.push(Job {
+                    kind: JobKind::Haul,
+                    from: source.entity,
+                    to: chest,
+                    item_stack,
+                });
+            }
+        }
+    }
+    // an idle worker still carrying items (from an interrupted job or a loaded save) empties itself
+    for (entity, transform, inventory, active_job) in worker_query.iter() {
+        if active_job.0.is_some() {
+            continue;
+        }
+        let tile = absolute_coord_to_tile_coord((*transform).into());
+        for item_stack in &inventory.slots {
+            if let Some(chest) = closest_chest(tile, *item_stack, None) {
+                jobs.push(Job {
+                    kind: JobKind::Haul,
+                    from: entity,
+                    to: chest,
+                    item_stack: *item_stack,
+                });
+            }
+        }
+    }
+    jobs.sort_by_key(|job| job.kind);
+    job_queue.0 = jobs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{Quality, recipe::RecipeId},
+        map::tile_coord_to_absolute_coord,
+        research::technology::TechnologyId,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn spawn_at(world: &mut World, tile: TileCoordinates, bundle: impl Bundle) -> Entity {
+        let absolute_coord = tile_coord_to_absolute_coord(tile);
+        world
+            .spawn((
+                Transform::from_xyz(absolute_coord.x, absolute_coord.y, 0.0),
+                bundle,
+            ))
+            .id()
+    }
+
+    #[test]
+    fn test_jobs_feed_machines_from_the_closest_source_first() {
+        let mut world = World::new();
+        world.insert_resource(JobQueue::default());
+        world.insert_resource(RecipeBook::default());
+        world.init_resource::<Research>();
+        world.insert_resource(LogisticsNetworks::default());
+
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 8);
+        let coal = ItemStack::new(ItemType::Coal, Quality::Standard, 8);
+        let mut chest_inventory = ChestInventory::default();
+        chest_inventory.0.add(iron_plates).unwrap();
+        chest_inventory.0.add(coal).unwrap();
+        let far_chest = spawn_at(&mut world, TileCoordinates { x: 10, y: 0 }, chest_inventory);
+        let mut chest_inventory = ChestInventory::default();
+        chest_inventory.0.add(iron_plates).unwrap();
+        let near_chest = spawn_at(&mut world, TileCoordinates { x: 2, y: 0 }, chest_inventory);
+        let assembler = spawn_at(
+            &mut world,
+            TileCoordinates { x: 0, y: 0 },
+            (
+                InputInventory::default(),
+                CraftingMachine::new(RecipeId::IronPlateToIronGear),
+            ),
+        );
+        let burner = spawn_at(
+            &mut world,
+            TileCoordinates { x: 0, y: 5 },
+            Burner::default(),
+        );
+
+        world.run_system_once(plan_jobs_system).unwrap();
+
+        // the refuel comes first, then the plates from the closest chest up to INPUT_STOCK
+        let job_queue = world.resource::<JobQueue>();
+        assert_eq!(
+            job_queue.0,
+            vec![
+                Job {
+                    kind: JobKind::Refuel,
+                    from: far_chest,
+                    to: burner,
+                    item_stack: ItemStack {
+                        quantity: 5,
+                        ..coal
+                    },
+                },
+                Job {
+                    kind: JobKind::Haul,
+                    from: near_chest,
+                    to: assembler,
+                    item_stack: ItemStack {
+                        quantity: INPUT_STOCK,
+                        ..iron_plates
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unlocked_modules_are_brought_to_the_free_module_slots() {
+        let mut world = World::new();
+        world.insert_resource(JobQueue::default());
+        world.insert_resource(RecipeBook::default());
+        let mut research = Research::default();
+        research.researched.insert(TechnologyId::Modules);
+        world.insert_resource(research);
+        world.insert_resource(LogisticsNetworks::default());
+
+        // the productivity modules wait for the Advanced modules research
+        let speed_modules = ItemStack::new(ItemType::SpeedModule, Quality::Standard, 3);
+        let mut output_inventory = OutputInventory::default();
+        output_inventory
+            .0
+            .add(ItemStack::new(
+                ItemType::ProductivityModule,
+                Quality::Standard,
+                3,
+            ))
+            .unwrap();
+        output_inventory.0.add(speed_modules).unwrap();
+        let module_assembler = spawn_at(
+            &mut world,
+            TileCoordinates { x: 0, y: 0 },
+            (
+                output_inventory,
+                CraftingMachine::new(RecipeId::IronGearAndCopperPlateToSpeedModule),
+            ),
+        );
+        let mut module_inventory = ModuleInventory::default();
+        module_inventory
+            .0
+            .add(ItemStack::new(ItemType::SpeedModule, Quality::Standard, 1))
+            .unwrap();
+        let mk3_assembler = spawn_at(
+            &mut world,
+            TileCoordinates { x: 4, y: 0 },
+            (MachineTier::Mk3, module_inventory),
+        );
+        // no module slot
+        spawn_at(
+            &mut world,
+            TileCoordinates { x: 8, y: 0 },
+            (MachineTier::Mk1, ModuleInventory::default()),
+        );
+
+        world.run_system_once(plan_jobs_system).unwrap();
+
+        assert_eq!(
+            world.resource::<JobQueue>().0,
+            vec![Job {
+                kind: JobKind::Equip,
+                from: module_assembler,
+                to: mk3_assembler,
+                item_stack: speed_modules,
+            }]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    items::{
+        ItemType,
+        inventory::{ChestInventory, ItemStack},
+    },
+    map::{Chest, TileCoordinates, absolute_coord_to_tile_coord},
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// two logistics chests closer than this, in tiles, are in the same network
+pub const LOGISTICS_LINK_RANGE: i32 = 16;
+
+/// role of a chest in its logistics network
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[require(Chest)]
+pub enum LogisticsChest {
+    /// gives its items to the requesters, what nobody requests is moved to a storage chest
+    Provider,
+    /// asks for items until it holds the desired quantity of each ItemType, whatever the quality
+    Requester(Vec<ItemStack>),
+    /// keeps what the providers do not need to hold, and gives it to the requesters
+    Storage,
+}
+impl LogisticsChest {
+    /// whether its items can be delivered to the requesters
+    pub fn is_supply(&self) -> bool {
+        !matches!(self, Self::Requester(_))
+    }
+
+    /// items of the requests missing from the chest
+    pub fn missing(&self, chest_inventory: &ChestInventory) -> Vec<ItemStack> {
+        let Self::Requester(requests) = self else {
+            return Vec::new();
+        };
+        requests
+            .iter()
+            .filter_map(|request| {
+                let quantity = chest_inventory.0.quantity(request.item_type);
+                (quantity < request.quantity).then_some(ItemStack {
+                    quantity: request.quantity - quantity,
+                    ..*request
+                })
+            })
+            .collect()
+    }
+}
+
+/// logistics chests linked to each other, the workers only deliver to a requester from the supply of its network
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogisticsNetwork {
+    pub chests: Vec<Entity>,
+    /// items held by the providers and the storage chests
+    pub stock: HashMap<ItemType, u32>,
+    /// items the requesters still miss
+    pub demand: HashMap<ItemType, u32>,
+}
+impl LogisticsNetwork {
+    pub fn stock(&self, item_type: ItemType) -> u32 {
+        self.stock.get(&item_type).copied().unwrap_or_default()
+    }
+
+    pub fn demand(&self, item_type: ItemType) -> u32 {
+        self.demand.get(&item_type).copied().unwrap_or_default()
+    }
+}
+
+/// every logistics network, rebuilt with the job queue
+#[derive(Resource, Debug, Default)]
+pub struct LogisticsNetworks {
+    pub networks: Vec<LogisticsNetwork>,
+    network_by_chest: HashMap<Entity, usize>,
+}
+impl LogisticsNetworks {
+    /// index in `networks` of the network of a logistics chest
+    pub fn network_of(&self, chest: Entity) -> Option<usize> {
+        self.network_by_chest.get(&chest).copied()
+    }
+
+    /// groups the chests linked by LOGISTICS_LINK_RANGE, the chests are sorted by tile so the networks keep their index
+    fn build(chests: &[(Entity, TileCoordinates, &LogisticsChest, &ChestInventory)]) -> Self {
+        let mut networks = Self::default();
+        for (start, (start_entity, ..)) in chests.iter().enumerate() {
+            if networks.network_by_chest.contains_key(start_entity) {
+                continue;
+            }
+            let index = networks.networks.len();
+            let mut network = LogisticsNetwork::default();
+            let mut open = vec![start];
+            networks.network_by_chest.insert(*start_entity, index);
+            while let Some(current) = open.pop() {
+                let (entity, tile, logistics_chest, chest_inventory) = chests[current];
+                network.chests.push(entity);
+                if logistics_chest.is_supply() {
+                    for item_stack in &chest_inventory.0.slots {
+                        *network.stock.entry(item_stack.item_type).or_default() +=
+                            item_stack.quantity;
+                    }
+                }
+                for missing in logistics_chest.missing(chest_inventory) {
+                    *network.demand.entry(missing.item_type).or_default() += missing.quantity;
+                }
+                for (next, (next_entity, next_tile, ..)) in chests.iter().enumerate() {
+                    let linked = (next_tile.x - tile.x).abs() <= LOGISTICS_LINK_RANGE
+                        && (next_tile.y - tile.y).abs() <= LOGISTICS_LINK_RANGE;
+                    if linked && !networks.network_by_chest.contains_key(next_entity) {
+                        networks.network_by_chest.insert(*next_entity, index);
+                        open.push(next);
+                    }
+                }
+            }
+            networks.networks.push(network);
+        }
+        networks
+    }
+}
+
+pub fn update_logistics_networks_system(
+    mut logistics_networks: ResMut<LogisticsNetworks>,
+    chest_query: Query<(Entity, &Transform, &LogisticsChest, &ChestInventory)>,
+) {
+    let mut chests: Vec<_> = chest_query
+        .iter()
+        .map(|(entity, transform, logistics_chest, chest_inventory)| {
+            let tile = absolute_coord_to_tile_coord((*transform).into());
+            (entity, tile, logistics_chest, chest_inventory)
+        })
+        .collect();
+    chests.sort_by_key(|(_, tile, ..)| (tile.y, tile.x));
+    *logistics_networks = LogisticsNetworks::build(&chests);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Quality;
+
+    #[test]
+    fn test_networks_link_close_chests_and_count_their_stock() {
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 6);
+        let mut provider_inventory = ChestInventory::default();
+        provider_inventory.0.add(iron_plates).unwrap();
+        let mut requester_inventory = ChestInventory::default();
+        requester_inventory.0.add(iron_plates).unwrap();
+        let provider = LogisticsChest::Provider;
+        let requester = LogisticsChest::Requester(vec![ItemStack {
+            quantity: 10,
+            ..iron_plates
+        }]);
+        let storage = LogisticsChest::Storage;
+        let empty = ChestInventory::default();
+        let chests = [
+            (
+                Entity::from_raw_u32(1).unwrap(),
+                TileCoordinates { x: 0, y: 0 },
+                &provider,
+                &provider_inventory,
+            ),
+            // linked to the first chest through the second one only
+            (
+                Entity::from_raw_u32(2).unwrap(),
+                TileCoordinates {
+                    x: LOGISTICS_LINK_RANGE * 2,
+                    y: 0,
+                },
+                &storage,
+                &empty,
+            ),
+            (
+                Entity::from_raw_u32(3).unwrap(),
+                TileCoordinates {
+                    x: LOGISTICS_LINK_RANGE,
+                    y: 0,
+                },
+                &requester,
+                &requester_inventory,
+            ),
+            (
+                Entity::from_raw_u32(4).unwrap(),
+                TileCoordinates {
+                    x: 0,
+                    y: LOGISTICS_LINK_RANGE + 1,
+                },
+                &storage,
+                &provider_inventory,
+            ),
+        ];
+
+        let networks = LogisticsNetworks::build(&chests);
+        assert_eq!(networks.networks.len(), 2);
+        assert_eq!(
+            networks.network_of(Entity::from_raw_u32(2).unwrap()),
+            Some(0)
+        );
+        assert_eq!(
+            networks.network_of(Entity::from_raw_u32(4).unwrap()),
+            Some(1)
+        );
+        // the items already in the requester are not part of the stock
+        assert_eq!(networks.networks[0].stock(ItemType::IronPlate), 6);
+        assert_eq!(networks.networks[0].demand(ItemType::IronPlate), 4);
+        assert_eq!(networks.networks[1].stock(ItemType::IronPlate), 6);
+        assert_eq!(networks.networks[1].demand(ItemType::IronPlate), 0);
+    }
+}
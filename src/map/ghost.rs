@@ -0,0 +1,170 @@
+use crate::{
+    items::{ItemType, inventory::ItemStack},
+    map::{Chunk, MapManager, STRUCTURE_LAYER, SpritePath, StructureLayerManager, TileCoordinates},
+    research::technology::{Research, StructureType, Unlock},
+    save::{SavedStructure, spawn_saved_structure},
+};
+use bevy::prelude::*;
+
+/// structure waiting to be built, the workers bring the items of its cost then it is replaced by the structure
+/// a ghost does not block the units, its tiles are only taken once it is built
+#[derive(Component, Debug, Clone)]
+pub struct Ghost {
+    pub blueprint: SavedStructure,
+    /// items still to bring, of any quality
+    pub missing: Vec<ItemStack>,
+}
+impl Ghost {
+    /// quantity of an ItemType still to bring
+    pub fn needs(&self, item_type: ItemType) -> u32 {
+        self.missing
+            .iter()
+            .filter(|item_stack| item_stack.item_type == item_type)
+            .map(|item_stack| item_stack.quantity)
+            .sum()
+    }
+
+    /// takes what the ghost needs from the item stack, returns the quantity taken
+    pub fn deliver(&mut self, item_stack: ItemStack) -> u32 {
+        let mut remaining = item_stack.quantity;
+        for missing in self
+            .missing
+            .iter_mut()
+            .filter(|missing| missing.item_type == item_stack.item_type)
+        {
+            let taken = missing.quantity.min(remaining);
+            missing.quantity -= taken;
+            remaining -= taken;
+        }
+        self.missing.retain(|missing| missing.quantity > 0);
+        item_stack.quantity - remaining
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn tiles(&self) -> Vec<TileCoordinates> {
+        self.blueprint
+            .footprint
+            .tiles(self.blueprint.anchor, self.blueprint.direction)
+    }
+}
+
+pub fn spawn_ghost(
+    commands: &mut Commands,
+    blueprint: SavedStructure,
+    cost: Vec<ItemStack>,
+) -> Entity {
+    let target_coord = blueprint
+        .footprint
+        .anchor_to_absolute_coord(blueprint.anchor, blueprint.direction);
+    let mut entity_commands = commands.spawn((
+        Name::new("Ghost"),
+        Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+        blueprint.footprint,
+        blueprint.direction,
+    ));
+    if let Some(sprite) = &blueprint.sprite {
+        entity_commands.insert(SpritePath(sprite.clone()));
+    }
+    entity_commands
+        .insert(Ghost {
+            blueprint,
+            missing: cost,
+        })
+        .id()
+}
+
+/// replaces the ghosts that received all their items by their structure
+/// the ghost of a structure gated by a technology waits for its research
+pub fn build_ghosts_system(
+    mut commands: Commands,
+    ghost_query: Query<(Entity, &Ghost)>,
+    mut map_manager: ResMut<MapManager>,
+    mut chunk_query: Query<&mut StructureLayerManager, With<Chunk>>,
+    research: Res<Research>,
+) {
+    for (ghost_entity, ghost) in ghost_query.iter() {
+        if !ghost.is_complete() {
+            continue;
+        }
+        if StructureType::of(&ghost.blueprint)
+            .is_some_and(|structure_type| !research.is_unlocked(Unlock::Structure(structure_type)))
+        {
+            continue;
+        }
+        let tiles = ghost.tiles();
+        // something was built on its tiles in the meantime
+        if !tiles.iter().all(|tile| map_manager.is_tile_walkable(*tile)) {
+            continue;
+        }
+        let structure_entity = spawn_saved_structure(&mut commands, &ghost.blueprint);
+        map_manager.register_structure(tiles, structure_entity, &mut chunk_query);
+        commands.entity(ghost_entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{Quality, inventory::ChestInventory},
+        map::{ChunkCoordinates, Footprint, LocalTileCoordinates, tile_coord_to_local_tile_coord},
+        save::SavedStructureKind,
+        units::Direction,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_ghost_is_built_once_it_received_its_cost() {
+        let mut world = World::new();
+        world.init_resource::<Research>();
+        let chunk_coord = ChunkCoordinates { x: 0, y: 0 };
+        let chunk_entity = world.spawn(Chunk { coord: chunk_coord }).id();
+        let mut map_manager = MapManager::default();
+        map_manager.insert_chunk(
+            chunk_coord,
+            chunk_entity,
+            Vec::<LocalTileCoordinates>::new(),
+        );
+        world.insert_resource(map_manager);
+
+        let tile = TileCoordinates { x: 2, y: 1 };
+        let blueprint = SavedStructure {
+            kind: SavedStructureKind::Chest,
+            anchor: tile,
+            direction: Direction::North,
+            footprint: Footprint::default(),
+            sprite: None,
+            machine: None,
+            inventory: None,
+            logistics_chest: None,
+            health: None,
+        };
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 4);
+        let ghost = world
+            .run_system_once(move |mut commands: Commands| {
+                spawn_ghost(&mut commands, blueprint.clone(), vec![iron_plates])
+            })
+            .unwrap();
+
+        // still waiting for its plates, the units walk through it
+        world.run_system_once(build_ghosts_system).unwrap();
+        assert!(world.get::<Ghost>(ghost).is_some());
+        assert!(world.resource::<MapManager>().is_tile_walkable(tile));
+
+        assert_eq!(
+            world.get_mut::<Ghost>(ghost).unwrap().deliver(iron_plates),
+            4
+        );
+        world.run_system_once(build_ghosts_system).unwrap();
+        assert!(world.get_entity(ghost).is_err());
+        let structure = world
+            .get::<StructureLayerManager>(chunk_entity)
+            .unwrap()
+            .structures[&tile_coord_to_local_tile_coord(tile, chunk_coord)];
+        assert!(world.get::<ChestInventory>(structure).is_some());
+        assert!(!world.resource::<MapManager>().is_tile_walkable(tile));
+    }
+}
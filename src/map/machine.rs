@@ -1,44 +1,66 @@
 use crate::{
     UPS_TARGET,
     items::{
-        inventory::{InputInventory, ItemStack, OutputInventory},
+        inventory::{
+            FuelInventory, InputInventory, Inventory, ItemStack, ModuleInventory, OutputInventory,
+        },
         recipe::{RecipeBook, RecipeId},
+        statistics::{ProductionStatistics, end_statistics_tick_system},
     },
     map::{
-        MapManager, Structure, StructureLayerManager, TileCoordinates, absolute_coord_to_tile_coord,
+        Chunk, Footprint, MapManager, Structure, StructureLayerManager, TileCoordinates,
+        modules::{MachineModifiers, MachineTier, update_machine_modifiers_system},
+        ports::{Port, PortKind, Ports},
     },
-    units::Direction,
+    research::technology::{Research, Unlock},
+    simulation::Headless,
+    units::{Direction, turrets::Turret},
 };
-use bevy::{prelude::*, sprite_render::TilemapChunk};
+use bevy::prelude::*;
 use std::f32::consts::{FRAC_PI_2, PI};
 
 pub const DEFAULT_ACTION_TIME_TICKS: u64 = UPS_TARGET as u64 * 1; // 1 second
+/// maximum number of items an output port can send each tick
+pub const MAX_TRANSFERRED_ITEMS_PER_TICK: u32 = 1;
 
 pub struct MachinePlugin;
 
 impl Plugin for MachinePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PostUpdate, orient_machines_system)
+        app.init_resource::<ProductionStatistics>()
+            .add_systems(PostUpdate, orient_machines_system)
+            .add_systems(FixedLast, end_statistics_tick_system)
             .add_systems(
                 FixedUpdate,
                 (
+                    update_machine_modifiers_system,
                     (
                         process_crafting_machines_system,
                         process_belt_machines_system,
                         process_mining_machines_system,
+                        process_furnace_machines_system,
                     ),
                     transfert_items_to_next_machine_system,
-                    print_machine_inventories_system,
-                ),
+                    // printing every machine each tick would flood the output of long headless runs
+                    print_machine_inventories_system.run_if(not(resource_exists::<Headless>)),
+                )
+                    .chain(),
             );
     }
 }
 
 #[derive(Component)]
+#[require(MachineTier, ModuleInventory, MachineModifiers, Ports)]
 pub struct Machine {
     pub action_time_ticks: u64,
     pub action_speed: f32,
     pub action_progress_ticks: u64,
+    /// accumulates MachineModifiers::productivity, a bonus output is given each time it reaches 1
+    pub productivity_progress: f32,
+    /// accumulates MachineModifiers::quality, the outputs get a better quality each time it reaches 1
+    pub quality_progress: f32,
+    /// set by the process systems when the action advanced during this tick, a blocked or starved machine doesn't pollute
+    pub worked_this_tick: bool,
 }
 impl Default for Machine {
     fn default() -> Self {
@@ -46,9 +68,80 @@ impl Default for Machine {
             action_time_ticks: DEFAULT_ACTION_TIME_TICKS,
             action_speed: 1.0,
             action_progress_ticks: 0,
+            productivity_progress: 0.0,
+            quality_progress: 0.0,
+            worked_this_tick: false,
         }
     }
 }
+impl Machine {
+    /// the outputs of the next finished action, upgraded when the quality progress will reach 1
+    fn next_action_outputs(
+        &self,
+        modifiers: &MachineModifiers,
+        outputs: &[ItemStack],
+    ) -> Vec<ItemStack> {
+        let upgrade_quality = self.quality_progress + modifiers.quality >= 1.0;
+        outputs
+            .iter()
+            .map(|item_stack| ItemStack {
+                quality: if upgrade_quality {
+                    item_stack.quality.upgraded()
+                } else {
+                    item_stack.quality
+                },
+                ..*item_stack
+            })
+            .collect()
+    }
+
+    /// whether the outputs of the next action fit in the inventory, as they will be produced : an upgraded stack needs its own slot
+    pub fn has_room_for_outputs(
+        &self,
+        modifiers: &MachineModifiers,
+        outputs: &[ItemStack],
+        output_inventory: &Inventory,
+    ) -> bool {
+        output_inventory.has_room_for(&self.next_action_outputs(modifiers, outputs))
+    }
+
+    /// adds the outputs of a finished action to the inventory, with the productivity and quality bonuses of the machine
+    /// the productivity bonus is lost if there is no room left for it ; returns the items actually added,
+    /// or None without changing anything if the outputs don't fit, the action then stays finished until there is room
+    pub fn add_action_outputs(
+        &mut self,
+        modifiers: &MachineModifiers,
+        outputs: &[ItemStack],
+        output_inventory: &mut Inventory,
+    ) -> Option<Vec<ItemStack>> {
+        if !self.has_room_for_outputs(modifiers, outputs, output_inventory) {
+            return None;
+        }
+        let outputs = self.next_action_outputs(modifiers, outputs);
+        self.productivity_progress += modifiers.productivity;
+        self.quality_progress += modifiers.quality;
+        if self.quality_progress >= 1.0 {
+            self.quality_progress -= 1.0;
+        }
+
+        let mut added_item_stacks = outputs.clone();
+        for item_stack in &outputs {
+            output_inventory
+                .add(*item_stack)
+                .expect("add_action_outputs(): room was checked");
+        }
+
+        if self.productivity_progress >= 1.0 {
+            self.productivity_progress -= 1.0;
+            for item_stack in outputs {
+                if output_inventory.add(item_stack).is_ok() {
+                    added_item_stacks.push(item_stack);
+                }
+            }
+        }
+        Some(added_item_stacks)
+    }
+}
 
 #[derive(Bundle)]
 pub struct MachineBaseBundle {
@@ -116,6 +209,61 @@ impl Default for MiningMachine {
     }
 }
 
+/// smelts whatever ore is in its InputInventory, the recipe is chosen from the RecipeBook when a new action starts
+#[derive(Component, Default)]
+#[require(Burner)]
+pub struct FurnaceMachine {
+    pub current_recipe_id: Option<RecipeId>,
+}
+#[derive(Bundle)]
+pub struct FurnaceMachineBundle {
+    pub base: MachineBaseBundle,
+    pub input_inventory: InputInventory,
+    pub output_inventory: OutputInventory,
+    pub fuel_inventory: FuelInventory,
+    pub ports: Ports,
+    pub furnace_machine: FurnaceMachine,
+}
+
+/// energy source that burns items from the FuelInventory, a machine with a Burner only makes progress while it has fuel
+#[derive(Component, Default)]
+#[require(FuelInventory)]
+pub struct Burner {
+    pub remaining_burn_ticks: f32,
+}
+impl Burner {
+    /// consumes `consumption` ticks of fuel (1.0 without modules), takes a new fuel item from the inventory if needed,
+    /// recorded in the statistics ; returns false if there is no fuel left
+    pub fn burn(
+        &mut self,
+        fuel_inventory: &mut Inventory,
+        consumption: f32,
+        statistics: &mut ProductionStatistics,
+    ) -> bool {
+        if self.remaining_burn_ticks <= 0.0 {
+            let Some(pos) = fuel_inventory
+                .slots
+                .iter()
+                .position(|slot| slot.item_type.is_fuel())
+            else {
+                return false;
+            };
+            let fuel = ItemStack {
+                quantity: 1,
+                ..fuel_inventory.slots[pos]
+            };
+            fuel_inventory.remove_quantity(fuel);
+            statistics.record_consumed(&[fuel]);
+            self.remaining_burn_ticks += fuel.item_type.burn_time_ticks().unwrap_or(0) as f32;
+        }
+        if self.remaining_burn_ticks <= 0.0 {
+            return false;
+        }
+        self.remaining_burn_ticks -= consumption;
+        true
+    }
+}
+
 pub fn process_belt_machines_system(
     mut machine_query: Query<
         (&mut Machine, &mut InputInventory, &mut OutputInventory),
@@ -124,11 +272,16 @@ pub fn process_belt_machines_system(
 ) {
     for (mut machine, mut input_inventory, mut output_inventory) in machine_query.iter_mut() {
         if machine.action_progress_ticks >= machine.action_time_ticks {
+            // the items wait in the input until the output has room for all of them
+            if !output_inventory.0.has_room_for(&input_inventory.0.slots) {
+                continue;
+            }
             let item_stacks = input_inventory.0.remove_all_item_stack();
             for item_stack in item_stacks {
-                output_inventory.0.add(item_stack).expect(
-                    "process_belt_machines_system(): transfer to output_inventory didn't work",
-                );
+                output_inventory
+                    .0
+                    .add(item_stack)
+                    .expect("process_belt_machines_system(): room was checked");
             }
             machine.action_progress_ticks = 0;
         }
@@ -150,15 +303,19 @@ pub fn process_belt_machines_system(
 pub fn process_crafting_machines_system(
     mut machine_query: Query<(
         &mut Machine,
+        &MachineModifiers,
         &CraftingMachine,
         &mut InputInventory,
         &mut OutputInventory,
     )>,
     recipe_book: Res<RecipeBook>,
+    research: Res<Research>,
+    mut statistics: ResMut<ProductionStatistics>,
 ) {
-    for (mut machine, crafting_machine, mut input_inventory, mut output_inventory) in
+    for (mut machine, modifiers, crafting_machine, mut input_inventory, mut output_inventory) in
         machine_query.iter_mut()
     {
+        machine.worked_this_tick = false;
         let Some(recipe_id) = crafting_machine.recipe_id else {
             continue;
         };
@@ -168,17 +325,22 @@ pub fn process_crafting_machines_system(
 
         // use machine.action_time_ticks instead of recipe.base_craft_time_ticks because machine.action_time_ticks change because of machine.action_speed
         if machine.action_progress_ticks >= machine.action_time_ticks {
-            for item_stack in &recipe.outputs {
-                output_inventory
-                    .0
-                    .add(*item_stack)
-                    .expect("add_item_stack() didn't work");
-            }
+            let Some(produced) =
+                machine.add_action_outputs(modifiers, &recipe.outputs, &mut output_inventory.0)
+            else {
+                continue;
+            };
+            statistics.record_produced(&produced);
             machine.action_progress_ticks = 0;
         }
 
-        // start a new craft if possible
+        // start a new craft if possible, a recipe gated by a technology waits for its research
         if machine.action_progress_ticks == 0 {
+            if !research.is_unlocked(Unlock::Recipe(recipe_id))
+                || !machine.has_room_for_outputs(modifiers, &recipe.outputs, &output_inventory.0)
+            {
+                continue;
+            }
             let mut items_present = true;
             for item_stack in &recipe.inputs {
                 if !input_inventory.0.enough_quantity(*item_stack) {
@@ -193,106 +355,289 @@ pub fn process_crafting_machines_system(
             for item_stack in &recipe.inputs {
                 input_inventory.0.remove_quantity(*item_stack);
             }
+            statistics.record_consumed(&recipe.inputs);
 
             // reset the crafting machine
             machine.action_time_ticks =
                 (recipe.base_craft_time_ticks as f32 / machine.action_speed) as u64;
             // TODO: see if need to change to 0
             machine.action_progress_ticks = 1;
+            machine.worked_this_tick = true;
         } else if machine.action_progress_ticks > 0 {
             machine.action_progress_ticks += 1;
+            machine.worked_this_tick = true;
         }
     }
 }
 
 pub fn process_mining_machines_system(
-    mut machine_query: Query<(&mut Machine, &MiningMachine, &mut OutputInventory)>,
+    mut machine_query: Query<(
+        &mut Machine,
+        &MachineModifiers,
+        &MiningMachine,
+        &mut OutputInventory,
+        Option<&mut Burner>,
+        Option<&mut FuelInventory>,
+    )>,
+    mut statistics: ResMut<ProductionStatistics>,
 ) {
-    for (mut machine, mining_machine, mut output_inventory) in machine_query.iter_mut() {
+    for (mut machine, modifiers, mining_machine, mut output_inventory, burner, fuel_inventory) in
+        machine_query.iter_mut()
+    {
+        machine.worked_this_tick = false;
         let Some(mined_item) = mining_machine.mined_item else {
             continue;
         };
 
         if machine.action_progress_ticks >= machine.action_time_ticks {
-            let new_item_stack = mined_item.clone();
-            output_inventory.0.add(new_item_stack).expect(
-                "process_mining_machines_system(): transfer to output_inventory didn't work",
-            );
+            let Some(produced) =
+                machine.add_action_outputs(modifiers, &[mined_item], &mut output_inventory.0)
+            else {
+                continue;
+            };
+            statistics.record_produced(&produced);
             machine.action_progress_ticks = 0;
         }
 
-        // start if previous action finised and if there is still room for more items
-        if let Some(mined_item) = mining_machine.mined_item {
-            if machine.action_progress_ticks == 0 && output_inventory.0.enough_room(mined_item) {
-                machine.action_time_ticks =
-                    (DEFAULT_ACTION_TIME_TICKS as f32 / machine.action_speed) as u64;
-                // TODO: see if need to change to 0
-                machine.action_progress_ticks = 1;
-            } else if machine.action_progress_ticks > 0 {
-                machine.action_progress_ticks += 1;
-            }
+        // don't start a new action if there is no room for more items
+        if machine.action_progress_ticks == 0
+            && !machine.has_room_for_outputs(modifiers, &[mined_item], &output_inventory.0)
+        {
+            continue;
+        }
+
+        // machines without a Burner use another energy source and are always powered
+        if let (Some(mut burner), Some(mut fuel_inventory)) = (burner, fuel_inventory)
+            && !burner.burn(
+                &mut fuel_inventory.0,
+                modifiers.consumption,
+                &mut statistics,
+            )
+        {
+            continue;
+        }
+
+        // start if previous action finised
+        if machine.action_progress_ticks == 0 {
+            machine.action_time_ticks =
+                (DEFAULT_ACTION_TIME_TICKS as f32 / machine.action_speed) as u64;
+            // TODO: see if need to change to 0
+            machine.action_progress_ticks = 1;
+        } else {
+            machine.action_progress_ticks += 1;
         }
+        machine.worked_this_tick = true;
     }
 }
 
-pub fn transfert_items_to_next_machine_system(
+pub fn process_furnace_machines_system(
     mut machine_query: Query<(
-        Entity,
-        &Transform,
         &mut Machine,
-        &Direction,
-        Option<&mut InputInventory>,
+        &MachineModifiers,
+        &mut FurnaceMachine,
+        &mut Burner,
+        &mut FuelInventory,
+        &mut InputInventory,
         &mut OutputInventory,
     )>,
-    chunk_query: Query<&StructureLayerManager, With<TilemapChunk>>,
-    map_manager: Res<MapManager>,
+    recipe_book: Res<RecipeBook>,
+    mut statistics: ResMut<ProductionStatistics>,
 ) {
-    // we find all transfer pairs
-    let mut transfer_pairs = Vec::new();
     for (
-        source_machine_entity,
-        transform,
-        _,
-        direction,
+        mut machine,
+        modifiers,
+        mut furnace_machine,
+        mut burner,
+        mut fuel_inventory,
         mut input_inventory,
         mut output_inventory,
-    ) in machine_query.iter()
+    ) in machine_query.iter_mut()
     {
-        let source_tile = absolute_coord_to_tile_coord((*transform).into());
-        let delta = direction.direction_to_vec2();
-        let target_tile = TileCoordinates {
-            x: source_tile.x + delta.x,
-            y: source_tile.y + delta.y,
-        };
-
-        if let Some(structure_entity) = map_manager.get_tile(target_tile, &chunk_query) {
-            if let Ok((target_machine_entity, _, _, _, _, _)) = machine_query.get(structure_entity)
+        machine.worked_this_tick = false;
+        if machine.action_progress_ticks >= machine.action_time_ticks {
+            if let Some(recipe) = furnace_machine
+                .current_recipe_id
+                .and_then(|recipe_id| recipe_book.0.get(&recipe_id))
             {
-                transfer_pairs.push((source_machine_entity, target_machine_entity))
+                let Some(produced) =
+                    machine.add_action_outputs(modifiers, &recipe.outputs, &mut output_inventory.0)
+                else {
+                    continue;
+                };
+                statistics.record_produced(&produced);
+            }
+            furnace_machine.current_recipe_id = None;
+            machine.action_progress_ticks = 0;
+        }
+
+        // start a new smelt if there is ore, fuel and room for the plates
+        if machine.action_progress_ticks == 0 {
+            let Some(recipe_id) = recipe_book.find_smelting_recipe(&input_inventory.0) else {
+                continue;
+            };
+            let recipe = &recipe_book.0[&recipe_id];
+            if !machine.has_room_for_outputs(modifiers, &recipe.outputs, &output_inventory.0) {
+                continue;
+            }
+            if !burner.burn(
+                &mut fuel_inventory.0,
+                modifiers.consumption,
+                &mut statistics,
+            ) {
+                continue;
+            }
+            for item_stack in &recipe.inputs {
+                input_inventory.0.remove_quantity(*item_stack);
+            }
+            statistics.record_consumed(&recipe.inputs);
+
+            furnace_machine.current_recipe_id = Some(recipe_id);
+            machine.action_time_ticks =
+                (recipe.base_craft_time_ticks as f32 / machine.action_speed) as u64;
+            machine.action_progress_ticks = 1;
+            machine.worked_this_tick = true;
+        } else if machine.action_progress_ticks > 0 {
+            // the smelt is paused while there is no fuel
+            if burner.burn(
+                &mut fuel_inventory.0,
+                modifiers.consumption,
+                &mut statistics,
+            ) {
+                machine.action_progress_ticks += 1;
+                machine.worked_this_tick = true;
             }
         }
     }
+}
+
+/// an output port facing an input port of another machine, collected before any item is moved
+struct TransferIntent {
+    source_tile: TileCoordinates,
+    target_tile: TileCoordinates,
+    source_entity: Entity,
+    source_port: Port,
+    target_entity: Entity,
+    target_port: Port,
+}
+
+/// transfers are done in two phases so the result doesn't depend on the query iteration order :
+/// 1. every output port facing an input port gives a TransferIntent
+/// 2. intents are resolved sorted by tile coordinates, each one moving at most MAX_TRANSFERRED_ITEMS_PER_TICK items
+pub fn transfert_items_to_next_machine_system(
+    mut machine_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Direction,
+            &Footprint,
+            &Ports,
+            Option<&mut InputInventory>,
+            Option<&mut FuelInventory>,
+            Option<&mut OutputInventory>,
+        ),
+        With<Machine>,
+    >,
+    chunk_query: Query<&StructureLayerManager, With<Chunk>>,
+    map_manager: Res<MapManager>,
+) {
+    // phase 1 : collect the intents
+    let mut intents = Vec::new();
+    for (source_machine_entity, transform, direction, footprint, ports, _, _, output_inventory) in
+        machine_query.iter()
+    {
+        if output_inventory.is_none_or(|output_inventory| output_inventory.0.slots.is_empty()) {
+            continue;
+        }
+        let source_anchor = footprint.absolute_coord_to_anchor((*transform).into(), *direction);
+        for source_port in ports.outputs() {
+            let source_tile = source_port.inside_tile(footprint, source_anchor, *direction);
+            let target_tile = source_port.outside_tile(footprint, source_anchor, *direction);
+            let Some(structure_entity) = map_manager.get_tile(target_tile, &chunk_query) else {
+                continue;
+            };
+            let Ok((
+                target_machine_entity,
+                target_transform,
+                target_direction,
+                target_footprint,
+                target_ports,
+                _,
+                _,
+                _,
+            )) = machine_query.get(structure_entity)
+            else {
+                continue;
+            };
+            if target_machine_entity == source_machine_entity {
+                continue;
+            }
 
-    // we do the transfers based on the transfer pairs
-    for (source_entity, target_entity) in transfer_pairs {
+            let target_anchor = target_footprint
+                .absolute_coord_to_anchor((*target_transform).into(), *target_direction);
+            if let Some(target_port) = target_ports.inputs().find(|target_port| {
+                target_port.outside_tile(target_footprint, target_anchor, *target_direction)
+                    == source_tile
+            }) {
+                intents.push(TransferIntent {
+                    source_tile,
+                    target_tile,
+                    source_entity: source_machine_entity,
+                    source_port: source_port.clone(),
+                    target_entity: target_machine_entity,
+                    target_port: target_port.clone(),
+                });
+            }
+        }
+    }
+
+    // phase 2 : resolve the intents in a stable order, only the items accepted by both ports are moved
+    intents.sort_by_key(|intent| {
+        (
+            intent.source_tile.y,
+            intent.source_tile.x,
+            intent.target_tile.y,
+            intent.target_tile.x,
+        )
+    });
+    for intent in intents {
         let Ok(
             [
-                (_, _, _, _, _, mut source_output_inventory),
-                (_, _, _, _, mut target_input_inventory, _),
+                (_, _, _, _, _, _, _, Some(mut source_output_inventory)),
+                (_, _, _, _, _, target_input_inventory, target_fuel_inventory, _),
             ],
-        ) = machine_query.get_many_mut([source_entity, target_entity])
+        ) = machine_query.get_many_mut([intent.source_entity, intent.target_entity])
         else {
             continue;
         };
+        let target_inventory = match intent.target_port.kind {
+            PortKind::Input => {
+                target_input_inventory.map(|inventory| &mut inventory.into_inner().0)
+            }
+            PortKind::Fuel => target_fuel_inventory.map(|inventory| &mut inventory.into_inner().0),
+            PortKind::Output => None,
+        };
+        let Some(target_inventory) = target_inventory else {
+            continue;
+        };
 
-        let item_stacks = source_output_inventory.0.remove_all_item_stack();
+        let mut remaining_items = MAX_TRANSFERRED_ITEMS_PER_TICK;
+        let item_stacks = source_output_inventory.0.slots.clone();
         for item_stack in item_stacks {
-            if let Some(target_input_inventory) = &mut target_input_inventory {
-                if !target_input_inventory.0.add(item_stack).is_ok() {
-                    source_output_inventory.0.add(item_stack).expect(
-                        "transfer didn't work and couldn't add items back in source_machine",
-                    );
-                }
+            if remaining_items == 0 {
+                break;
+            }
+            if !intent.source_port.accepts(item_stack.item_type)
+                || !intent.target_port.accepts(item_stack.item_type)
+            {
+                continue;
+            }
+            let moved_item_stack = ItemStack {
+                quantity: item_stack.quantity.min(remaining_items),
+                ..item_stack
+            };
+            if target_inventory.add(moved_item_stack).is_ok() {
+                source_output_inventory.0.remove_quantity(moved_item_stack);
+                remaining_items -= moved_item_stack.quantity;
             }
         }
     }
@@ -313,7 +658,10 @@ pub fn print_machine_inventories_system(
     }
 }
 
-pub fn orient_machines_system(mut query: Query<(&Direction, &mut Transform), With<Machine>>) {
+/// the turrets turn to their target instead
+type OrientedMachine = (With<Machine>, Without<Turret>);
+
+pub fn orient_machines_system(mut query: Query<(&Direction, &mut Transform), OrientedMachine>) {
     for (direction, mut transform) in query.iter_mut() {
         let angle = match direction {
             Direction::North => 0.0,       // up = sprite par défaut
@@ -325,3 +673,376 @@ pub fn orient_machines_system(mut query: Query<(&Direction, &mut Transform), Wit
         transform.rotation = Quat::from_rotation_z(angle);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{ItemType, Quality},
+        map::{
+            ChunkCoordinates, tile_coord_to_absolute_coord, tile_coord_to_chunk_coord,
+            tile_coord_to_local_tile_coord,
+        },
+        research::technology::TechnologyId,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    struct TestMachine {
+        tile: TileCoordinates,
+        direction: Direction,
+        input_slots_limit: u32,
+        output: Vec<ItemStack>,
+    }
+
+    fn test_machine(x: i32, y: i32, direction: Direction, output: Vec<ItemStack>) -> TestMachine {
+        TestMachine {
+            tile: TileCoordinates { x, y },
+            direction,
+            input_slots_limit: 5,
+            output,
+        }
+    }
+
+    /// spawns the machines in the given order, runs the transfers and returns the inventories of each tile
+    fn run_transfers(
+        machines: &[TestMachine],
+        order: &[usize],
+        ticks: u32,
+    ) -> Vec<(TileCoordinates, Vec<ItemStack>, Vec<ItemStack>)> {
+        let mut world = World::new();
+        let chunk_entity = world
+            .spawn(Chunk {
+                coord: ChunkCoordinates { x: 0, y: 0 },
+            })
+            .id();
+        let mut map_manager = MapManager::default();
+        map_manager
+            .chunks
+            .insert(ChunkCoordinates { x: 0, y: 0 }, chunk_entity);
+        world.insert_resource(map_manager);
+
+        let mut entities = Vec::new();
+        for &index in order {
+            let machine = &machines[index];
+            let absolute_coord = tile_coord_to_absolute_coord(machine.tile);
+            let mut input_inventory = InputInventory::default();
+            input_inventory.0.slots_quantity_limit = machine.input_slots_limit;
+            let mut output_inventory = OutputInventory::default();
+            for item_stack in &machine.output {
+                output_inventory.0.add(*item_stack).unwrap();
+            }
+            let entity = world
+                .spawn((
+                    Machine::default(),
+                    machine.direction,
+                    Footprint::default(),
+                    Transform::from_xyz(absolute_coord.x, absolute_coord.y, 0.0),
+                    input_inventory,
+                    output_inventory,
+                ))
+                .id();
+            let local_tile = tile_coord_to_local_tile_coord(
+                machine.tile,
+                tile_coord_to_chunk_coord(machine.tile),
+            );
+            world
+                .get_mut::<StructureLayerManager>(chunk_entity)
+                .unwrap()
+                .structures
+                .insert(local_tile, entity);
+            entities.push((machine.tile, entity));
+        }
+
+        for _ in 0..ticks {
+            world
+                .run_system_once(transfert_items_to_next_machine_system)
+                .unwrap();
+        }
+
+        let mut result: Vec<_> = entities
+            .into_iter()
+            .map(|(tile, entity)| {
+                (
+                    tile,
+                    world.get::<InputInventory>(entity).unwrap().0.slots.clone(),
+                    world
+                        .get::<OutputInventory>(entity)
+                        .unwrap()
+                        .0
+                        .slots
+                        .clone(),
+                )
+            })
+            .collect();
+        result.sort_by_key(|(tile, _, _)| (tile.y, tile.x));
+        result
+    }
+
+    #[test]
+    fn test_transfer_is_limited_per_tick() {
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 5);
+        let machines = [
+            test_machine(0, 1, Direction::North, vec![iron_plates]),
+            test_machine(0, 0, Direction::North, vec![]),
+        ];
+
+        let result = run_transfers(&machines, &[0, 1], 1);
+        assert_eq!(
+            result[0].1,
+            vec![ItemStack {
+                quantity: MAX_TRANSFERRED_ITEMS_PER_TICK,
+                ..iron_plates
+            }]
+        );
+        assert_eq!(
+            result[1].2,
+            vec![ItemStack {
+                quantity: 5 - MAX_TRANSFERRED_ITEMS_PER_TICK,
+                ..iron_plates
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transfer_is_independent_of_spawn_order() {
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 3);
+        let copper_plates = ItemStack::new(ItemType::CopperPlate, Quality::Standard, 3);
+        // two machines compete for the single input slot of the machine at (1, 1),
+        // which itself feeds a chain of belts going up
+        let mut machines = vec![
+            test_machine(1, 2, Direction::North, vec![iron_plates]),
+            test_machine(2, 1, Direction::West, vec![copper_plates]),
+            test_machine(1, 1, Direction::North, vec![iron_plates]),
+            test_machine(1, 0, Direction::West, vec![copper_plates]),
+            test_machine(0, 0, Direction::North, vec![]),
+        ];
+        machines[2].input_slots_limit = 1;
+
+        let expected = run_transfers(&machines, &[0, 1, 2, 3, 4], 4);
+        for order in [[4, 3, 2, 1, 0], [2, 0, 4, 1, 3], [1, 3, 0, 4, 2]] {
+            assert_eq!(run_transfers(&machines, &order, 4), expected);
+        }
+
+        // intents are resolved from the top left tile, so the machine at (2, 1) fills the slot first
+        let (_, contested_input, _) = &expected[2];
+        assert_eq!(contested_input.len(), 1);
+        assert_eq!(contested_input[0].item_type, ItemType::CopperPlate);
+    }
+
+    #[test]
+    fn test_upgraded_outputs_wait_for_a_free_slot() {
+        let mut machine = Machine::default();
+        let modifiers = MachineModifiers {
+            quality: 1.0,
+            ..default()
+        };
+        let iron_plate = ItemStack::new(ItemType::IronPlate, Quality::Standard, 1);
+        // the only slot still takes standard plates, but the next output will be perfect
+        let mut output_inventory = Inventory::with_slots(1);
+        output_inventory
+            .add(ItemStack::new(ItemType::IronPlate, Quality::Standard, 5))
+            .unwrap();
+        assert!(output_inventory.enough_room(iron_plate));
+        assert!(!machine.has_room_for_outputs(&modifiers, &[iron_plate], &output_inventory));
+        assert_eq!(
+            machine.add_action_outputs(&modifiers, &[iron_plate], &mut output_inventory),
+            None
+        );
+        assert_eq!(machine.quality_progress, 0.0);
+        assert_eq!(output_inventory.quantity(ItemType::IronPlate), 5);
+
+        // once the slot is emptied, the output is added with its upgrade
+        output_inventory.remove_all_item_stack();
+        let perfect_plate = ItemStack::new(ItemType::IronPlate, Quality::Perfect, 1);
+        assert_eq!(
+            machine.add_action_outputs(&modifiers, &[iron_plate], &mut output_inventory),
+            Some(vec![perfect_plate])
+        );
+        assert_eq!(output_inventory.slots, vec![perfect_plate]);
+    }
+
+    #[test]
+    fn test_burner_takes_a_fuel_item_when_its_burn_time_is_spent() {
+        let mut burner = Burner::default();
+        let mut fuel_inventory = Inventory::default();
+        let mut statistics = ProductionStatistics::default();
+        assert!(!burner.burn(&mut fuel_inventory, 1.0, &mut statistics));
+
+        fuel_inventory
+            .add(ItemStack::new(ItemType::Wood, Quality::Standard, 2))
+            .unwrap();
+        let wood_ticks = ItemType::Wood.burn_time_ticks().unwrap() as f32;
+        assert!(burner.burn(&mut fuel_inventory, 1.0, &mut statistics));
+        assert_eq!(burner.remaining_burn_ticks, wood_ticks - 1.0);
+        assert_eq!(fuel_inventory.quantity(ItemType::Wood), 1);
+
+        // a module raising the consumption spends the fuel faster, the next item is only taken once it is spent
+        while burner.remaining_burn_ticks > 0.0 {
+            assert!(burner.burn(&mut fuel_inventory, 2.0, &mut statistics));
+            assert_eq!(fuel_inventory.quantity(ItemType::Wood), 1);
+        }
+        assert!(burner.burn(&mut fuel_inventory, 2.0, &mut statistics));
+        assert_eq!(fuel_inventory.quantity(ItemType::Wood), 0);
+        assert_eq!(statistics.consumed[&ItemType::Wood], 2);
+    }
+
+    #[test]
+    fn test_furnace_pauses_without_fuel_and_resumes() {
+        let mut world = World::new();
+        world.insert_resource(RecipeBook::default());
+        world.init_resource::<ProductionStatistics>();
+        let mut input_inventory = Inventory::default();
+        input_inventory
+            .add(ItemStack::new(ItemType::IronOre, Quality::Standard, 1))
+            .unwrap();
+        let furnace = world
+            .spawn((
+                Machine::default(),
+                MachineModifiers::default(),
+                FurnaceMachine::default(),
+                // enough fuel left for the first ticks of the smelt only
+                Burner {
+                    remaining_burn_ticks: 10.0,
+                },
+                InputInventory(input_inventory),
+                OutputInventory::default(),
+            ))
+            .id();
+        let run_ticks = |world: &mut World, ticks: u32| {
+            for _ in 0..ticks {
+                world
+                    .run_system_once(process_furnace_machines_system)
+                    .unwrap();
+            }
+            world.get::<Machine>(furnace).unwrap().action_progress_ticks
+        };
+
+        let progress = run_ticks(&mut world, 30);
+        assert!(
+            progress > 0 && progress < world.get::<Machine>(furnace).unwrap().action_time_ticks
+        );
+        assert_eq!(run_ticks(&mut world, 30), progress);
+
+        world
+            .get_mut::<FuelInventory>(furnace)
+            .unwrap()
+            .0
+            .add(ItemStack::new(ItemType::Coal, Quality::Standard, 1))
+            .unwrap();
+        run_ticks(&mut world, DEFAULT_ACTION_TIME_TICKS as u32 * 2);
+        let output_inventory = world.get::<OutputInventory>(furnace).unwrap();
+        assert_eq!(output_inventory.0.quantity(ItemType::IronPlate), 1);
+        let statistics = world.resource::<ProductionStatistics>();
+        assert_eq!(statistics.consumed[&ItemType::Coal], 1);
+        assert_eq!(statistics.consumed[&ItemType::IronOre], 1);
+    }
+
+    #[test]
+    fn test_plugged_modules_speed_up_the_crafts_once_researched() {
+        let mut world = World::new();
+        world.insert_resource(RecipeBook::default());
+        world.init_resource::<Research>();
+        world.init_resource::<ProductionStatistics>();
+        let mut input_inventory = Inventory::default();
+        input_inventory
+            .add(ItemStack::new(ItemType::IronPlate, Quality::Standard, 20))
+            .unwrap();
+        let mut module_inventory = Inventory::default();
+        module_inventory
+            .add(ItemStack::new(ItemType::SpeedModule, Quality::Standard, 2))
+            .unwrap();
+        let assembler = world
+            .spawn((
+                Machine::default(),
+                MachineTier::Mk2,
+                ModuleInventory(module_inventory),
+                CraftingMachine::new(RecipeId::IronPlateToIronGear),
+                InputInventory(input_inventory),
+                OutputInventory::default(),
+            ))
+            .id();
+        let craft_time = |world: &mut World| {
+            world
+                .run_system_once(update_machine_modifiers_system)
+                .unwrap();
+            world
+                .run_system_once(process_crafting_machines_system)
+                .unwrap();
+            world
+                .get_mut::<Machine>(assembler)
+                .unwrap()
+                .action_progress_ticks = 0;
+            world.get::<Machine>(assembler).unwrap().action_time_ticks
+        };
+
+        // the modules wait for their research, only the tier counts
+        let base_time = DEFAULT_ACTION_TIME_TICKS as f32;
+        assert_eq!(
+            craft_time(&mut world),
+            (base_time / MachineTier::Mk2.base_speed()) as u64
+        );
+
+        world
+            .resource_mut::<Research>()
+            .researched
+            .insert(TechnologyId::Modules);
+        assert_eq!(
+            craft_time(&mut world),
+            (base_time / (MachineTier::Mk2.base_speed() * 2.0)) as u64
+        );
+        assert_eq!(
+            world.get::<Machine>(assembler).unwrap().action_speed,
+            MachineTier::Mk2.base_speed() * 2.0
+        );
+    }
+
+    #[test]
+    fn test_blocked_belt_keeps_its_items_until_the_output_has_room() {
+        let mut world = World::new();
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 3);
+        let mut input_inventory = Inventory::default();
+        input_inventory.add(iron_plates).unwrap();
+        let mut output_inventory = Inventory::with_slots(1);
+        output_inventory
+            .add(ItemStack::new(ItemType::IronGear, Quality::Standard, 1))
+            .unwrap();
+        let belt = world
+            .spawn((
+                Machine::default(),
+                BeltMachine,
+                InputInventory(input_inventory),
+                OutputInventory(output_inventory),
+            ))
+            .id();
+        let run_ticks = |world: &mut World, ticks: u64| {
+            for _ in 0..ticks {
+                world.run_system_once(process_belt_machines_system).unwrap();
+            }
+        };
+
+        run_ticks(&mut world, DEFAULT_ACTION_TIME_TICKS * 2);
+        assert_eq!(
+            world.get::<InputInventory>(belt).unwrap().0.slots,
+            vec![iron_plates]
+        );
+
+        world
+            .get_mut::<OutputInventory>(belt)
+            .unwrap()
+            .0
+            .remove_all_item_stack();
+        run_ticks(&mut world, 1);
+        assert!(
+            world
+                .get::<InputInventory>(belt)
+                .unwrap()
+                .0
+                .slots
+                .is_empty()
+        );
+        assert_eq!(
+            world.get::<OutputInventory>(belt).unwrap().0.slots,
+            vec![iron_plates]
+        );
+    }
+}
@@ -1,21 +1,31 @@
 use crate::{
     items::{
         ItemType, Quality,
-        inventory::{InputInventory, ItemStack, OutputInventory},
+        inventory::{ChestInventory, FuelInventory, InputInventory, ItemStack, OutputInventory},
         recipe::RecipeId,
     },
-    map::machine::{
-        BeltMachine, BeltMachineBundle, CraftingMachine, CraftingMachineBundle, Machine,
-        MachineBaseBundle, MiningMachine, MiningMachineBundle,
+    logistics::network::LogisticsChest,
+    map::{
+        machine::{
+            BeltMachine, BeltMachineBundle, CraftingMachine, CraftingMachineBundle, FurnaceMachine,
+            FurnaceMachineBundle, Machine, MachineBaseBundle, MiningMachine, MiningMachineBundle,
+        },
+        modules::MachineTier,
+        ports::Ports,
+        walkability::WalkabilityGrid,
+    },
+    research::lab::spawn_lab,
+    units::{
+        Direction, Unit,
+        combat::Health,
+        nests::{NEST_CHANCE, Nest, is_nest_chunk, spawn_nest},
+        turrets::{Turret, TurretTargeting, spawn_turret},
     },
-    units::{Direction, Unit, pathfinding::RecalculateFlowField},
 };
 use avian2d::prelude::{CoefficientCombine, Collider, Friction, RigidBody};
-use bevy::{
-    prelude::*,
-    sprite_render::{TileData, TilemapChunk, TilemapChunkTileData},
-};
-use rand::Rng;
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub const TILE_SIZE: Vec2 = Vec2 { x: 16.0, y: 16.0 };
@@ -31,6 +41,7 @@ pub struct MapPlugin;
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MapManager::default())
+            .init_resource::<MapSeed>()
             .add_systems(PostStartup, spawn_one_chunk)
             .add_systems(
                 FixedUpdate,
@@ -39,14 +50,17 @@ impl Plugin for MapPlugin {
                     spawn_chunks_around_units_system,
                 )
                     .chain(),
-            )
-            .add_systems(Update, update_tileset_image);
+            );
     }
 }
 
+/// seed of the map generation, each chunk gets its own generator from it (see chunk_rng)
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct MapSeed(pub u64);
+
 /// absolute_coord = (5.5 * TILE_SIZE.X, 0.5 * TILE_SIZE.y) | coord = (5.5, 0.5) | tile_coord = (5, 0)
 // #[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     pub x: f32,
     pub y: f32,
@@ -75,7 +89,7 @@ impl From<AbsoluteCoordinates> for Vec2 {
 }
 
 /// absolute_coord = (5.5 * TILE_SIZE.X, 0.5 * TILE_SIZE.y) | coord = (5.5, 0.5) | tile_coord = (5, 0)
-#[derive(Default, Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Default, Debug, Hash, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TileCoordinates {
     pub x: i32,
     pub y: i32,
@@ -89,12 +103,19 @@ pub struct LocalTileCoordinates {
 
 /// chunk_coord : (1,1) is 1 right and 1 down
 /// Chunkcoord {x: 2, y: 2} <=> TileCoordinates {x: 2*CHUNK_SIZE, y: 2*CHUNK_SIZE}
-#[derive(Default, Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Default, Debug, Hash, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChunkCoordinates {
     pub x: i32,
     pub y: i32,
 }
 
+/// a chunk of the map, it only holds the simulation data : the tilemap drawing it is added by MapRenderPlugin
+#[derive(Component, Debug, Clone, Copy)]
+#[require(StructureLayerManager, SourceLayerManager, Transform)]
+pub struct Chunk {
+    pub coord: ChunkCoordinates,
+}
+
 #[derive(Component, Default, Debug)]
 pub struct StructureLayerManager {
     pub structures: HashMap<LocalTileCoordinates, Entity>, // local TileCoordinates -> structure
@@ -109,12 +130,17 @@ pub struct SourceLayerManager {
 #[derive(Resource, Default)]
 pub struct MapManager {
     pub chunks: HashMap<ChunkCoordinates, Entity>,
+    /// tiles of structures that overlap chunks not spawned yet, moved into the chunk StructureLayerManager when it is spawned
+    pub pending_structures: HashMap<ChunkCoordinates, Vec<(LocalTileCoordinates, Entity)>>,
+    pub walkability: WalkabilityGrid,
+    /// pollution of each chunk, emitted by the working machines and spread to the neighbour chunks
+    pub pollution: HashMap<ChunkCoordinates, f32>,
 }
 impl MapManager {
     pub fn get_tile(
         &self,
         tile: TileCoordinates,
-        chunk_query: &Query<&StructureLayerManager, With<TilemapChunk>>,
+        chunk_query: &Query<&StructureLayerManager, With<Chunk>>,
     ) -> Option<Entity> {
         let chunk_coord = tile_coord_to_chunk_coord(tile);
         if let Some(chunk_entity) = self.chunks.get(&chunk_coord) {
@@ -126,17 +152,86 @@ impl MapManager {
         None
     }
 
-    pub fn is_tile_walkable(
-        &self,
-        tile: TileCoordinates,
-        chunk_query: &Query<&StructureLayerManager, With<TilemapChunk>>,
-    ) -> bool {
-        self.get_tile(tile, chunk_query).is_none()
+    pub fn is_tile_walkable(&self, tile: TileCoordinates) -> bool {
+        self.walkability.is_walkable(tile)
+    }
+
+    /// to call once the entity of a chunk is spawned, with the tiles of its structure layer
+    pub fn insert_chunk(
+        &mut self,
+        chunk_coord: ChunkCoordinates,
+        chunk_entity: Entity,
+        structure_tiles: impl IntoIterator<Item = LocalTileCoordinates>,
+    ) {
+        self.chunks.insert(chunk_coord, chunk_entity);
+        self.walkability.insert_chunk(chunk_coord, structure_tiles);
+    }
+
+    /// registers every tile covered by a structure, even across chunk boundaries
+    pub fn register_structure(
+        &mut self,
+        tiles: impl IntoIterator<Item = TileCoordinates>,
+        structure_entity: Entity,
+        chunk_query: &mut Query<&mut StructureLayerManager, With<Chunk>>,
+    ) {
+        for tile in tiles {
+            let chunk_coord = tile_coord_to_chunk_coord(tile);
+            let local_tile = tile_coord_to_local_tile_coord(tile, chunk_coord);
+            if let Some(chunk_entity) = self.chunks.get(&chunk_coord)
+                && let Ok(mut structure_manager) = chunk_query.get_mut(*chunk_entity)
+            {
+                structure_manager
+                    .structures
+                    .insert(local_tile, structure_entity);
+                self.walkability.set_walkable(tile, false);
+            } else {
+                self.pending_structures
+                    .entry(chunk_coord)
+                    .or_default()
+                    .push((local_tile, structure_entity));
+            }
+        }
+    }
+
+    pub fn unregister_structure(
+        &mut self,
+        tiles: impl IntoIterator<Item = TileCoordinates>,
+        structure_entity: Entity,
+        chunk_query: &mut Query<&mut StructureLayerManager, With<Chunk>>,
+    ) {
+        for tile in tiles {
+            let chunk_coord = tile_coord_to_chunk_coord(tile);
+            let local_tile = tile_coord_to_local_tile_coord(tile, chunk_coord);
+            if let Some(chunk_entity) = self.chunks.get(&chunk_coord)
+                && let Ok(mut structure_manager) = chunk_query.get_mut(*chunk_entity)
+                && structure_manager.structures.get(&local_tile) == Some(&structure_entity)
+            {
+                structure_manager.structures.remove(&local_tile);
+                self.walkability.set_walkable(tile, true);
+            }
+            if let Some(pending) = self.pending_structures.get_mut(&chunk_coord) {
+                pending.retain(|&(pending_tile, entity)| {
+                    pending_tile != local_tile || entity != structure_entity
+                });
+            }
+        }
+    }
+
+    /// removes and returns the structure tiles waiting for this chunk to be spawned
+    pub fn take_pending_structures(
+        &mut self,
+        chunk_coord: ChunkCoordinates,
+    ) -> Vec<(LocalTileCoordinates, Entity)> {
+        self.pending_structures
+            .remove(&chunk_coord)
+            .unwrap_or_default()
     }
 }
 
 #[derive(Component, Default)]
 #[require(
+    Footprint,
+    Health,
     RigidBody::Static,
     Collider::rectangle(TILE_SIZE.x, TILE_SIZE.y),
     Friction {
@@ -146,73 +241,362 @@ impl MapManager {
     },
 )]
 pub struct Structure;
+
+/// size in tiles of a structure facing North, the anchor is the covered tile with the smallest x and y
+/// local coordinates go from (0, 0) to (width - 1, height - 1), local y = -1 is the row just in front of the structure
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Footprint {
+    pub width: u32,
+    pub height: u32,
+}
+impl Footprint {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// size in tiles once rotated
+    pub fn size(&self, direction: Direction) -> IVec2 {
+        match direction {
+            Direction::North | Direction::South => {
+                IVec2::new(self.width as i32, self.height as i32)
+            }
+            Direction::East | Direction::West => IVec2::new(self.height as i32, self.width as i32),
+        }
+    }
+
+    /// collider of the structure before the rotation of its Transform is applied
+    pub fn collider(&self) -> Collider {
+        Collider::rectangle(
+            self.width as f32 * TILE_SIZE.x,
+            self.height as f32 * TILE_SIZE.y,
+        )
+    }
+
+    pub fn local_to_tile_coord(
+        &self,
+        anchor: TileCoordinates,
+        direction: Direction,
+        local: IVec2,
+    ) -> TileCoordinates {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let offset = match direction {
+            Direction::North => IVec2::new(local.x, local.y),
+            Direction::East => IVec2::new(h - 1 - local.y, local.x),
+            Direction::South => IVec2::new(w - 1 - local.x, h - 1 - local.y),
+            Direction::West => IVec2::new(local.y, w - 1 - local.x),
+        };
+        TileCoordinates {
+            x: anchor.x + offset.x,
+            y: anchor.y + offset.y,
+        }
+    }
+
+    /// every tile covered by the structure
+    pub fn tiles(&self, anchor: TileCoordinates, direction: Direction) -> Vec<TileCoordinates> {
+        let size = self.size(direction);
+        let mut tiles = Vec::with_capacity((size.x * size.y) as usize);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                tiles.push(TileCoordinates {
+                    x: anchor.x + x,
+                    y: anchor.y + y,
+                });
+            }
+        }
+        tiles
+    }
+
+    /// tile just outside the middle of the front edge, where the structure outputs its items
+    pub fn front_tile(&self, anchor: TileCoordinates, direction: Direction) -> TileCoordinates {
+        self.local_to_tile_coord(anchor, direction, IVec2::new(self.width as i32 / 2, -1))
+    }
+
+    pub fn anchor_to_absolute_coord(
+        &self,
+        anchor: TileCoordinates,
+        direction: Direction,
+    ) -> AbsoluteCoordinates {
+        let size = self.size(direction).as_vec2();
+        AbsoluteCoordinates {
+            x: (anchor.x as f32 + size.x / 2.0) * TILE_SIZE.x,
+            y: -(anchor.y as f32 + size.y / 2.0) * TILE_SIZE.y,
+        }
+    }
+
+    pub fn absolute_coord_to_anchor(
+        &self,
+        absolute_coord: AbsoluteCoordinates,
+        direction: Direction,
+    ) -> TileCoordinates {
+        let size = self.size(direction).as_vec2();
+        TileCoordinates {
+            x: (absolute_coord.x / TILE_SIZE.x - size.x / 2.0).round() as i32,
+            y: (-absolute_coord.y / TILE_SIZE.y - size.y / 2.0).round() as i32,
+        }
+    }
+}
+impl Default for Footprint {
+    fn default() -> Self {
+        Self::new(1, 1)
+    }
+}
+
 #[derive(Component)]
 pub struct Wall;
 
+/// stores items brought by the workers, and gives them back for the other jobs
+#[derive(Component, Default)]
+#[require(ChestInventory)]
+pub struct Chest;
+
 #[derive(Component)]
 pub struct Source(pub ItemStack);
+
+/// image of an entity, relative to the assets folder ; the Sprite is only loaded by MapRenderPlugin
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct SpritePath(pub String);
+impl SpritePath {
+    pub fn structure(file_name: &str) -> Self {
+        Self(PATH_STRUCTURES_PNG.to_owned() + file_name)
+    }
+
+    pub fn source(file_name: &str) -> Self {
+        Self(PATH_SOURCES_PNG.to_owned() + file_name)
+    }
+}
 // #[derive(Component)]
 // pub struct IronOre();
 
 pub fn spawn_one_chunk(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut map_manager: ResMut<MapManager>,
-    mut message_recalculate: MessageWriter<RecalculateFlowField>,
+    mut chunk_query: Query<&mut StructureLayerManager, With<Chunk>>,
+    map_seed: Res<MapSeed>,
 ) -> () {
-    let mut rng = rand::rng();
     let chunk_coord = ChunkCoordinates { x: 0, y: 0 };
+    // already loaded from a save
+    if map_manager.chunks.contains_key(&chunk_coord) {
+        return;
+    }
+
+    // 2x2 assembler overlapping the chunk on its left, making the ammo of the turret
+    let footprint = Footprint::new(2, 2);
+    let direction = Direction::North;
+    let anchor = TileCoordinates { x: -1, y: 3 };
+    let target_coord = footprint.anchor_to_absolute_coord(anchor, direction);
+    let transform = Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
+    let bundle = CraftingMachineBundle {
+        base: MachineBaseBundle {
+            name: Name::new("Assembler"),
+            structure: Structure,
+            direction,
+            transform,
+            machine: Machine::default(),
+        },
+        input_inventory: InputInventory::default(),
+        output_inventory: OutputInventory::default(),
+        crafting_machine: CraftingMachine::new(RecipeId::IronPlateToAmmo),
+    };
+    let machine_entity = commands
+        .spawn((
+            bundle,
+            footprint,
+            footprint.collider(),
+            MachineTier::Mk2,
+            SpritePath::structure("crafter.png"),
+        ))
+        .id();
+    map_manager.register_structure(
+        footprint.tiles(anchor, direction),
+        machine_entity,
+        &mut chunk_query,
+    );
+
+    // chest next to the spawn, where the workers store what no machine needs
+    // it is also the storage of the logistics network of the spawn
+    let tile_coord = TileCoordinates { x: 2, y: 0 };
+    let chest_entity = spawn_chest(&mut commands, tile_coord, ChestInventory::default());
+    commands
+        .entity(chest_entity)
+        .insert(LogisticsChest::Storage);
+    map_manager.register_structure([tile_coord], chest_entity, &mut chunk_query);
+
+    // turret guarding the spawn, the workers bring it the ammo of the assembler
+    let tile_coord = TileCoordinates { x: 4, y: 1 };
+    let mut input_inventory = InputInventory::default();
+    input_inventory
+        .0
+        .add(ItemStack::new(ItemType::Ammo, Quality::Standard, 5))
+        .expect("add_item_stack() didn't work");
+    let turret_entity = spawn_turret(
+        &mut commands,
+        tile_coord,
+        Turret::new(TurretTargeting::Nearest),
+        input_inventory,
+    );
+    map_manager.register_structure([tile_coord], turret_entity, &mut chunk_query);
+
+    // lab with the science packs of the first technologies
+    let tile_coord = TileCoordinates { x: 6, y: 1 };
+    let mut input_inventory = InputInventory::default();
+    input_inventory
+        .0
+        .add(ItemStack::new(ItemType::SciencePack, Quality::Standard, 10))
+        .expect("add_item_stack() didn't work");
+    let lab_entity = spawn_lab(&mut commands, tile_coord, input_inventory);
+    map_manager.register_structure([tile_coord], lab_entity, &mut chunk_query);
+
+    generate_chunk(&mut commands, &mut map_manager, map_seed.0, chunk_coord);
+}
+
+/// random number generator of a chunk, the same seed always generates the same chunk whatever the order chunks are generated in
+pub fn chunk_rng(seed: u64, chunk_coord: ChunkCoordinates) -> StdRng {
+    let chunk_seed = seed
+        ^ (chunk_coord.x as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (chunk_coord.y as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    StdRng::seed_from_u64(chunk_seed)
+}
+
+/// spawns the entity of a chunk, the tilemap drawing it is added by MapRenderPlugin
+pub fn spawn_chunk_entity(
+    commands: &mut Commands,
+    chunk_coord: ChunkCoordinates,
+    structure_layer_manager: StructureLayerManager,
+    source_layer_manager: SourceLayerManager,
+) -> Entity {
+    let chunk_center_x =
+        (chunk_coord.x as f32 * CHUNK_SIZE.x as f32 + CHUNK_SIZE.x as f32 / 2.0) * TILE_SIZE.x;
+    let chunk_center_y =
+        -(chunk_coord.y as f32 * CHUNK_SIZE.y as f32 + CHUNK_SIZE.y as f32 / 2.0) * TILE_SIZE.y;
+    let chunk_transform =
+        Transform::from_translation(Vec3::new(chunk_center_x, chunk_center_y, TILE_LAYER));
+
+    commands
+        .spawn((
+            Chunk { coord: chunk_coord },
+            structure_layer_manager,
+            source_layer_manager,
+            chunk_transform,
+        ))
+        .id()
+}
+
+pub fn spawn_wall(commands: &mut Commands, tile_coord: TileCoordinates) -> Entity {
+    let target_coord = tile_coord_to_absolute_coord(tile_coord);
+    commands
+        .spawn((
+            Structure,
+            Wall,
+            SpritePath::structure("wall.png"),
+            Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+        ))
+        .id()
+}
+
+pub fn spawn_chest(
+    commands: &mut Commands,
+    tile_coord: TileCoordinates,
+    chest_inventory: ChestInventory,
+) -> Entity {
+    let target_coord = tile_coord_to_absolute_coord(tile_coord);
+    commands
+        .spawn((
+            Name::new("Chest"),
+            Structure,
+            Chest,
+            chest_inventory,
+            SpritePath::structure("chest.png"),
+            Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+        ))
+        .id()
+}
+
+pub fn spawn_source(
+    commands: &mut Commands,
+    tile_coord: TileCoordinates,
+    item_stack: ItemStack,
+) -> Entity {
+    let target_coord = tile_coord_to_absolute_coord(tile_coord);
+    commands
+        .spawn((
+            Source(item_stack),
+            SpritePath::source("iron_ore.png"),
+            Transform::from_xyz(target_coord.x, target_coord.y, SOURCE_LAYER),
+        ))
+        .id()
+}
+
+/// generates the walls, sources and machines of a chunk from the map seed and spawns it
+pub fn generate_chunk(
+    commands: &mut Commands,
+    map_manager: &mut MapManager,
+    seed: u64,
+    chunk_coord: ChunkCoordinates,
+) -> Entity {
+    let mut rng = chunk_rng(seed, chunk_coord);
     let mut structure_layer_manager = StructureLayerManager::default();
     let mut source_layer_manager = SourceLayerManager::default();
+    // structures from neighbour chunks that overlap this one
+    for (local_tile_coord, structure_entity) in map_manager.take_pending_structures(chunk_coord) {
+        structure_layer_manager
+            .structures
+            .insert(local_tile_coord, structure_entity);
+    }
     for x in 0..CHUNK_SIZE.x {
         for y in 0..CHUNK_SIZE.y {
             let local_tile_coord = LocalTileCoordinates {
                 x: x as i32,
                 y: y as i32,
             };
+            if structure_layer_manager
+                .structures
+                .contains_key(&local_tile_coord)
+            {
+                continue;
+            }
 
             let is_wall = rng.random_bool(0.2);
-            let is_source = rng.random_bool(0.2);
+            let is_resource = rng.random_bool(0.2);
             if (local_tile_coord.x > 2) && (local_tile_coord.y > 2) {
                 let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
-                let target_coord = tile_coord_to_absolute_coord(tile_coord);
                 if is_wall {
-                    let transform =
-                        Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
-                    let wall_entity = commands
-                        .spawn((
-                            Structure,
-                            Wall,
-                            Sprite::from_image(
-                                asset_server.load(PATH_STRUCTURES_PNG.to_owned() + "wall.png"),
-                            ),
-                            transform,
-                        ))
-                        .id();
+                    let wall_entity = spawn_wall(commands, tile_coord);
                     structure_layer_manager
                         .structures
                         .insert(local_tile_coord, wall_entity);
-                } else if is_source {
-                    let transform =
-                        Transform::from_xyz(target_coord.x, target_coord.y, SOURCE_LAYER);
+                } else if is_resource {
                     let item_stack = ItemStack {
                         item_type: ItemType::IronOre,
                         quality: Quality::Standard,
                         quantity: 3,
                     };
-                    let source_entity = commands
-                        .spawn((
-                            Source(item_stack),
-                            // IronOre,
-                            Sprite::from_image(
-                                asset_server.load(PATH_SOURCES_PNG.to_owned() + "iron_ore.png"),
-                            ),
-                            transform,
-                        ))
-                        .id();
+                    let source_entity = spawn_source(commands, tile_coord, item_stack);
                     source_layer_manager
                         .sources
                         .insert(local_tile_coord, source_entity);
+
+                    if local_tile_coord.x < 5 && local_tile_coord.y < 5 {
+                        let target_coord = tile_coord_to_absolute_coord(tile_coord);
+                        let transform =
+                            Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
+                        let bundle = MiningMachineBundle {
+                            base: MachineBaseBundle {
+                                name: Name::new("Mining machine"),
+                                structure: Structure,
+                                direction: Direction::North,
+                                transform,
+                                machine: Machine::default(),
+                            },
+                            output_inventory: OutputInventory::default(),
+                            mining_machine: MiningMachine::new(item_stack),
+                        };
+                        let machine_entity = commands
+                            .spawn((bundle, SpritePath::structure("mining_machine.png")))
+                            .id();
+                        structure_layer_manager
+                            .structures
+                            .insert(local_tile_coord, machine_entity);
+                    }
                 }
             }
         }
@@ -241,16 +625,12 @@ pub fn spawn_one_chunk(
         belt_machine: BeltMachine,
     };
     let machine_entity = commands
-        .spawn((
-            bundle,
-            Sprite::from_image(
-                asset_server.load(PATH_STRUCTURES_PNG.to_owned() + "belt_machine.png"),
-            ),
-        ))
+        .spawn((bundle, SpritePath::structure("belt_machine.png")))
         .id();
     structure_layer_manager
         .structures
         .insert(local_tile_coord, machine_entity);
+
     let local_tile_coord = LocalTileCoordinates { x: 1, y: 0 };
     let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
     let target_coord = tile_coord_to_absolute_coord(tile_coord);
@@ -268,68 +648,76 @@ pub fn spawn_one_chunk(
         crafting_machine: CraftingMachine::new(RecipeId::IronPlateToIronGear),
     };
     let machine_entity = commands
-        .spawn((
-            bundle,
-            Sprite::from_image(
-                asset_server.load(PATH_STRUCTURES_PNG.to_owned() + "crafting_machine.png"),
-            ),
-        ))
+        .spawn((bundle, SpritePath::structure("crafting_machine.png")))
         .id();
     structure_layer_manager
         .structures
         .insert(local_tile_coord, machine_entity);
 
-    message_recalculate.write_default();
-
-    let tile_display_size = UVec2::splat(TILE_SIZE.x as u32);
-    let chunk_center_x = (chunk_coord.x as f32 * CHUNK_SIZE.x as f32 + CHUNK_SIZE.x as f32 / 2.0)
-        * tile_display_size.x as f32;
-    let chunk_center_y = -(chunk_coord.y as f32 * CHUNK_SIZE.y as f32 + CHUNK_SIZE.y as f32 / 2.0)
-        * tile_display_size.y as f32;
-
-    let chunk_transform =
-        Transform::from_translation(Vec3::new(chunk_center_x, chunk_center_y, TILE_LAYER));
-
-    let tile_data: Vec<Option<TileData>> = (0..CHUNK_SIZE.element_product())
-        // .map(|_| rng.random_range(0..5))
-        .map(|_| rng.random_range(1..2))
-        .map(|i| {
-            if i == 0 {
-                None
-            } else {
-                Some(TileData::from_tileset_index(i - 1))
-            }
-        })
-        .collect();
-    let chunk_entity = commands
-        .spawn((
-            TilemapChunk {
-                chunk_size: CHUNK_SIZE,
-                tile_display_size,
-                tileset: asset_server.load("textures/array_texture.png"),
-                ..default()
-            },
-            TilemapChunkTileData(tile_data),
-            structure_layer_manager,
-            source_layer_manager,
-            chunk_transform,
-        ))
+    let local_tile_coord = LocalTileCoordinates { x: 1, y: 2 };
+    let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
+    let target_coord = tile_coord_to_absolute_coord(tile_coord);
+    let transform = Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
+    let mut input_inventory = InputInventory::default();
+    input_inventory
+        .0
+        .add(ItemStack::new(ItemType::IronOre, Quality::Standard, 10))
+        .expect("add_item_stack() didn't work");
+    let mut fuel_inventory = FuelInventory::default();
+    fuel_inventory
+        .0
+        .add(ItemStack::new(ItemType::Coal, Quality::Standard, 5))
+        .expect("add_item_stack() didn't work");
+    let bundle = FurnaceMachineBundle {
+        base: MachineBaseBundle {
+            name: Name::new("Furnace machine"),
+            structure: Structure,
+            direction: Direction::North,
+            transform,
+            machine: Machine::default(),
+        },
+        input_inventory,
+        output_inventory: OutputInventory::default(),
+        fuel_inventory,
+        ports: Ports::furnace(),
+        furnace_machine: FurnaceMachine::default(),
+    };
+    let machine_entity = commands
+        .spawn((bundle, SpritePath::structure("default_machine.png")))
         .id();
-    map_manager.chunks.insert(chunk_coord, chunk_entity);
-}
+    structure_layer_manager
+        .structures
+        .insert(local_tile_coord, machine_entity);
 
-fn update_tileset_image(
-    chunk_query: Single<&TilemapChunk>,
-    mut events: MessageReader<AssetEvent<Image>>,
-    mut images: ResMut<Assets<Image>>,
-) {
-    let chunk = *chunk_query;
-    for event in events.read() {
-        if event.is_loaded_with_dependencies(chunk.tileset.id()) {
-            let image = images.get_mut(&chunk.tileset).unwrap();
-            image.reinterpret_stacked_2d_as_array(4);
+    // enemy nest in the chunks far from the spawn
+    if is_nest_chunk(chunk_coord) && rng.random_bool(NEST_CHANCE) {
+        let local_tile_coord = LocalTileCoordinates {
+            x: rng.random_range(3..CHUNK_SIZE.x as i32),
+            y: rng.random_range(3..CHUNK_SIZE.y as i32),
+        };
+        if !structure_layer_manager
+            .structures
+            .contains_key(&local_tile_coord)
+            && !source_layer_manager.sources.contains_key(&local_tile_coord)
+        {
+            let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
+            let nest_entity = spawn_nest(commands, tile_coord, Nest::default());
+            structure_layer_manager
+                .structures
+                .insert(local_tile_coord, nest_entity);
         }
     }
+
+    let structure_tiles: Vec<LocalTileCoordinates> =
+        structure_layer_manager.structures.keys().copied().collect();
+    let chunk_entity = spawn_chunk_entity(
+        commands,
+        chunk_coord,
+        structure_layer_manager,
+        source_layer_manager,
+    );
+    map_manager.insert_chunk(chunk_coord, chunk_entity, structure_tiles);
+    chunk_entity
 }
 
 // ========= coordinates conversion =========
@@ -445,11 +833,9 @@ pub fn coord_to_chunk_coord(coord: Coordinates) -> ChunkCoordinates {
 
 fn spawn_chunks_around_units_system(
     unit_query: Query<&Transform, With<Unit>>,
-    // chunk_query: Query<(&StructureLayerManager, &SourceLayerManager), With<TilemapChunk>>,
     mut map_manager: ResMut<MapManager>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut message_recalculate: MessageWriter<RecalculateFlowField>,
+    map_seed: Res<MapSeed>,
 ) {
     const SIZE: i32 = 2;
 
@@ -462,206 +848,76 @@ fn spawn_chunks_around_units_system(
                     continue;
                 }
 
-                let mut rng = rand::rng();
-                let mut structure_layer_manager = StructureLayerManager::default();
-                let mut source_layer_manager = SourceLayerManager::default();
-                for x in 0..CHUNK_SIZE.x {
-                    for y in 0..CHUNK_SIZE.y {
-                        let local_tile_coord = LocalTileCoordinates {
-                            x: x as i32,
-                            y: y as i32,
-                        };
-
-                        let is_wall = rng.random_bool(0.2);
-                        let is_resource = rng.random_bool(0.2);
-                        if (local_tile_coord.x > 2) && (local_tile_coord.y > 2) {
-                            let tile_coord =
-                                local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
-                            let target_coord = tile_coord_to_absolute_coord(tile_coord);
-                            if is_wall {
-                                let transform = Transform::from_xyz(
-                                    target_coord.x,
-                                    target_coord.y,
-                                    STRUCTURE_LAYER,
-                                );
-                                let wall_entity = commands
-                                    .spawn((
-                                        Structure,
-                                        Wall,
-                                        Sprite::from_image(
-                                            asset_server
-                                                .load(PATH_STRUCTURES_PNG.to_owned() + "wall.png"),
-                                        ),
-                                        transform,
-                                    ))
-                                    .id();
-                                structure_layer_manager
-                                    .structures
-                                    .insert(local_tile_coord, wall_entity);
-                            } else if is_resource {
-                                let transform = Transform::from_xyz(
-                                    target_coord.x,
-                                    target_coord.y,
-                                    SOURCE_LAYER,
-                                );
-                                let item_stack = ItemStack {
-                                    item_type: ItemType::IronOre,
-                                    quality: Quality::Standard,
-                                    quantity: 3,
-                                };
-                                let source = Source(item_stack);
-                                let source_entity = commands
-                                    .spawn((
-                                        source,
-                                        // IronOre,
-                                        Sprite::from_image(
-                                            asset_server
-                                                .load(PATH_SOURCES_PNG.to_owned() + "iron_ore.png"),
-                                        ),
-                                        transform,
-                                    ))
-                                    .id();
-                                source_layer_manager
-                                    .sources
-                                    .insert(local_tile_coord, source_entity);
-
-                                if local_tile_coord.x < 5 && local_tile_coord.y < 5 {
-                                    let bundle = MiningMachineBundle {
-                                        base: MachineBaseBundle {
-                                            name: Name::new("Mining machine"),
-                                            structure: Structure,
-                                            direction: Direction::North,
-                                            transform,
-                                            machine: Machine::default(),
-                                        },
-                                        output_inventory: OutputInventory::default(),
-                                        mining_machine: MiningMachine::new(item_stack),
-                                    };
-                                    let machine_entity = commands
-                                        .spawn((
-                                            bundle,
-                                            Sprite::from_image(asset_server.load(
-                                                PATH_STRUCTURES_PNG.to_owned()
-                                                    + "mining_machine.png",
-                                            )),
-                                        ))
-                                        .id();
-                                    structure_layer_manager
-                                        .structures
-                                        .insert(local_tile_coord, machine_entity);
-                                }
-                            }
-                        }
-                    }
+                generate_chunk(&mut commands, &mut map_manager, map_seed.0, chunk_coord);
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_footprint_front_tile() {
+        let anchor = TileCoordinates { x: 0, y: 0 };
+
+        // 1x1 structures output in the tile given by their direction
+        let footprint = Footprint::default();
+        for direction in [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ] {
+            let delta = direction.direction_to_vec2();
+            assert_eq!(
+                footprint.front_tile(anchor, direction),
+                TileCoordinates {
+                    x: delta.x,
+                    y: delta.y
                 }
+            );
+        }
 
-                let local_tile_coord = LocalTileCoordinates { x: 1, y: 1 };
-                let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
-                let target_coord = tile_coord_to_absolute_coord(tile_coord);
-                let transform =
-                    Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
-                let item_stack = ItemStack::new(ItemType::IronPlate, Quality::Perfect, 10);
-                let mut input_inventory = InputInventory::default();
-                input_inventory
-                    .0
-                    .add(item_stack)
-                    .expect("add_item_stack() didn't work");
-                let bundle = BeltMachineBundle {
-                    base: MachineBaseBundle {
-                        name: Name::new("Belt machine"),
-                        structure: Structure,
-                        direction: Direction::North,
-                        transform,
-                        machine: Machine::default(),
-                    },
-                    input_inventory,
-                    output_inventory: OutputInventory::default(),
-                    belt_machine: BeltMachine,
-                };
-                let machine_entity = commands
-                    .spawn((
-                        bundle,
-                        Sprite::from_image(
-                            asset_server.load(PATH_STRUCTURES_PNG.to_owned() + "belt_machine.png"),
-                        ),
-                    ))
-                    .id();
-                structure_layer_manager
-                    .structures
-                    .insert(local_tile_coord, machine_entity);
-                let local_tile_coord = LocalTileCoordinates { x: 1, y: 0 };
-                let tile_coord = local_tile_coord_to_tile_coord(local_tile_coord, chunk_coord);
-                let target_coord = tile_coord_to_absolute_coord(tile_coord);
-                let transform =
-                    Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER);
-                let bundle = CraftingMachineBundle {
-                    base: MachineBaseBundle {
-                        name: Name::new("Crafting machine"),
-                        structure: Structure,
-                        direction: Direction::South,
-                        transform,
-                        machine: Machine::default(),
-                    },
-                    input_inventory: InputInventory::default(),
-                    output_inventory: OutputInventory::default(),
-                    crafting_machine: CraftingMachine::new(RecipeId::IronPlateToIronGear),
-                };
-                let machine_entity = commands
-                    .spawn((
-                        bundle,
-                        Sprite::from_image(
-                            asset_server
-                                .load(PATH_STRUCTURES_PNG.to_owned() + "crafting_machine.png"),
-                        ),
-                    ))
-                    .id();
-                structure_layer_manager
-                    .structures
-                    .insert(local_tile_coord, machine_entity);
-
-                message_recalculate.write_default();
-
-                let tile_display_size = UVec2::splat(TILE_SIZE.x as u32);
-                let chunk_center_x = (chunk_coord.x as f32 * CHUNK_SIZE.x as f32
-                    + CHUNK_SIZE.x as f32 / 2.0)
-                    * tile_display_size.x as f32;
-                let chunk_center_y = -(chunk_coord.y as f32 * CHUNK_SIZE.y as f32
-                    + CHUNK_SIZE.y as f32 / 2.0)
-                    * tile_display_size.y as f32;
-
-                let chunk_transform = Transform::from_translation(Vec3::new(
-                    chunk_center_x,
-                    chunk_center_y,
-                    TILE_LAYER,
-                ));
-
-                let tile_data: Vec<Option<TileData>> = (0..CHUNK_SIZE.element_product())
-                    // .map(|_| rng.random_range(0..5))
-                    .map(|_| rng.random_range(1..2))
-                    .map(|i| {
-                        if i == 0 {
-                            None
-                        } else {
-                            Some(TileData::from_tileset_index(i - 1))
-                        }
-                    })
-                    .collect();
-                let chunk_entity = commands
-                    .spawn((
-                        TilemapChunk {
-                            chunk_size: CHUNK_SIZE,
-                            tile_display_size,
-                            tileset: asset_server.load("textures/array_texture.png"),
-                            ..default()
-                        },
-                        TilemapChunkTileData(tile_data),
-                        structure_layer_manager,
-                        source_layer_manager,
-                        chunk_transform,
-                    ))
-                    .id();
-                map_manager.chunks.insert(chunk_coord, chunk_entity);
-            }
+        // 3x2 structure, the front edge is 3 tiles long
+        let footprint = Footprint::new(3, 2);
+        assert_eq!(
+            footprint.front_tile(anchor, Direction::North),
+            TileCoordinates { x: 1, y: -1 }
+        );
+        assert_eq!(
+            footprint.front_tile(anchor, Direction::East),
+            TileCoordinates { x: 2, y: 1 }
+        );
+        assert_eq!(
+            footprint.front_tile(anchor, Direction::South),
+            TileCoordinates { x: 1, y: 2 }
+        );
+        assert_eq!(
+            footprint.front_tile(anchor, Direction::West),
+            TileCoordinates { x: -1, y: 1 }
+        );
+    }
+
+    #[test]
+    fn test_footprint_anchor_round_trip() {
+        let footprint = Footprint::new(3, 2);
+        let anchor = TileCoordinates { x: -5, y: 7 };
+        for direction in [Direction::North, Direction::East] {
+            let absolute_coord = footprint.anchor_to_absolute_coord(anchor, direction);
+            assert_eq!(
+                footprint.absolute_coord_to_anchor(absolute_coord, direction),
+                anchor
+            );
+            assert_eq!(
+                footprint.tiles(anchor, direction).len(),
+                (footprint.width * footprint.height) as usize
+            );
         }
+
+        // 1x1 footprints are placed like any other tile
+        let footprint = Footprint::default();
+        let absolute_coord = footprint.anchor_to_absolute_coord(anchor, Direction::North);
+        assert_eq!(absolute_coord, tile_coord_to_absolute_coord(anchor));
     }
 }
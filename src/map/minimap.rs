@@ -0,0 +1,455 @@
+use crate::{
+    camera::{CameraMovement, CameraMovementKind},
+    items::ItemType,
+    logistics::network::LogisticsChest,
+    map::{
+        CHUNK_SIZE, Chest, Chunk, ChunkCoordinates, LocalTileCoordinates, MapManager, Source,
+        SourceLayerManager, StructureLayerManager, TILE_SIZE, Wall,
+        machine::{BeltMachine, CraftingMachine, FurnaceMachine, MiningMachine},
+    },
+    research::lab::Lab,
+    units::{Player, Unit, combat::Hostile, nests::Nest, turrets::Turret, worker::Worker},
+};
+use bevy::{ecs::query::QueryData, prelude::*};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::collections::{HashMap, HashSet};
+
+/// opens or closes the full screen map
+pub const FULL_MAP_KEY: KeyCode = KeyCode::Tab;
+const MINIMAP_SIZE: f32 = 200.0;
+/// screen points per tile of the minimap
+const MINIMAP_ZOOM: f32 = 2.0;
+/// screen points per tile of the full map
+const FULL_MAP_ZOOM_RANGE: (f32, f32) = (0.25, 16.0);
+const FULL_MAP_DEFAULT_ZOOM: f32 = 4.0;
+/// chunk textures redrawn each frame, the others wait for the next frames
+const CHUNKS_REDRAWN_PER_FRAME: usize = 8;
+const UNIT_MARKER_RADIUS: f32 = 2.5;
+
+const GROUND_COLOR: egui::Color32 = egui::Color32::from_rgb(58, 84, 48);
+const UNEXPLORED_COLOR: egui::Color32 = egui::Color32::from_rgb(16, 16, 20);
+
+/// low resolution views of the spawned chunks : a minimap around the camera and a full screen map
+/// each chunk is an egui texture with one pixel per tile, redrawn only when its structures or sources change
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>()
+            .add_systems(Update, (toggle_full_map_system, mark_changed_chunks_system))
+            .add_systems(
+                EguiPrimaryContextPass,
+                (
+                    redraw_chunk_textures_system,
+                    minimap_window_system,
+                    full_map_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// part of the map shown in a rectangle of the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapView {
+    /// in tiles, x to the right and y down like the TileCoordinates
+    pub center: Vec2,
+    pub points_per_tile: f32,
+}
+impl MapView {
+    pub fn to_screen(&self, rect: egui::Rect, map_position: Vec2) -> egui::Pos2 {
+        let offset = (map_position - self.center) * self.points_per_tile;
+        rect.center() + egui::vec2(offset.x, offset.y)
+    }
+
+    pub fn to_map(&self, rect: egui::Rect, screen_position: egui::Pos2) -> Vec2 {
+        let offset = screen_position - rect.center();
+        self.center + Vec2::new(offset.x, offset.y) / self.points_per_tile
+    }
+
+    /// changes the zoom by factor, the point of the map under screen_position stays in place
+    pub fn zoom_at(&mut self, rect: egui::Rect, screen_position: egui::Pos2, factor: f32) {
+        let anchor = self.to_map(rect, screen_position);
+        self.points_per_tile =
+            (self.points_per_tile * factor).clamp(FULL_MAP_ZOOM_RANGE.0, FULL_MAP_ZOOM_RANGE.1);
+        self.center += anchor - self.to_map(rect, screen_position);
+    }
+}
+
+/// map position of a world translation, in tiles : the tile (x, y) covers [x, x + 1[ * [y, y + 1[
+fn world_to_map(translation: Vec3) -> Vec2 {
+    Vec2::new(translation.x / TILE_SIZE.x, -translation.y / TILE_SIZE.y)
+}
+
+fn map_to_world(map_position: Vec2) -> Vec2 {
+    Vec2::new(map_position.x * TILE_SIZE.x, -map_position.y * TILE_SIZE.y)
+}
+
+#[derive(Resource, Default)]
+pub struct Minimap {
+    /// one pixel per tile of each spawned chunk
+    textures: HashMap<ChunkCoordinates, egui::TextureHandle>,
+    /// chunks spawned or changed since their texture was drawn
+    changed_chunks: HashSet<ChunkCoordinates>,
+    pub is_full_map_open: bool,
+    pub full_map_view: Option<MapView>,
+}
+
+/// to keep the camera inputs for the full map while it is open
+pub fn is_full_map_open(minimap: Res<Minimap>) -> bool {
+    minimap.is_full_map_open
+}
+
+pub fn toggle_full_map_system(input: Res<ButtonInput<KeyCode>>, mut minimap: ResMut<Minimap>) {
+    if input.just_pressed(FULL_MAP_KEY) {
+        minimap.is_full_map_open = !minimap.is_full_map_open;
+    }
+}
+
+type ChangedChunk = Or<(
+    Added<Chunk>,
+    Changed<StructureLayerManager>,
+    Changed<SourceLayerManager>,
+)>;
+
+pub fn mark_changed_chunks_system(
+    mut minimap: ResMut<Minimap>,
+    chunk_query: Query<&Chunk, ChangedChunk>,
+) {
+    for chunk in chunk_query.iter() {
+        minimap.changed_chunks.insert(chunk.coord);
+    }
+}
+
+/// what the minimap tells apart among the structures
+#[derive(QueryData)]
+pub struct MinimapStructure {
+    is_wall: Has<Wall>,
+    is_nest: Has<Nest>,
+    is_turret: Has<Turret>,
+    is_lab: Has<Lab>,
+    is_mining: Has<MiningMachine>,
+    is_furnace: Has<FurnaceMachine>,
+    is_crafting: Has<CraftingMachine>,
+    is_belt: Has<BeltMachine>,
+    is_logistics_chest: Has<LogisticsChest>,
+    is_chest: Has<Chest>,
+}
+impl MinimapStructureItem<'_, '_> {
+    fn color(&self) -> egui::Color32 {
+        let (r, g, b) = if self.is_wall {
+            (120, 120, 120)
+        } else if self.is_nest {
+            (150, 30, 130)
+        } else if self.is_turret {
+            (230, 90, 90)
+        } else if self.is_lab {
+            (110, 220, 220)
+        } else if self.is_mining {
+            (230, 180, 40)
+        } else if self.is_furnace {
+            (210, 90, 40)
+        } else if self.is_crafting {
+            (70, 130, 230)
+        } else if self.is_belt {
+            (220, 210, 130)
+        } else if self.is_logistics_chest {
+            (180, 90, 230)
+        } else if self.is_chest {
+            (150, 100, 50)
+        } else {
+            (200, 200, 200)
+        };
+        egui::Color32::from_rgb(r, g, b)
+    }
+}
+
+fn source_color(item_type: ItemType) -> egui::Color32 {
+    let (r, g, b) = match item_type {
+        ItemType::IronOre => (120, 150, 180),
+        ItemType::CopperOre => (200, 120, 70),
+        ItemType::Coal => (30, 30, 30),
+        ItemType::Wood => (30, 110, 40),
+        _ => (200, 200, 110),
+    };
+    egui::Color32::from_rgb(r, g, b)
+}
+
+/// redraws the textures of the changed chunks, a few per frame
+pub fn redraw_chunk_textures_system(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    map_manager: Res<MapManager>,
+    chunk_query: Query<(&StructureLayerManager, &SourceLayerManager), With<Chunk>>,
+    structure_query: Query<MinimapStructure>,
+    source_query: Query<&Source>,
+) -> Result {
+    // the chunks of a loaded save replace the previous ones
+    minimap
+        .textures
+        .retain(|chunk_coord, _| map_manager.chunks.contains_key(chunk_coord));
+    if minimap.changed_chunks.is_empty() {
+        return Ok(());
+    }
+    let ctx = contexts.ctx_mut()?;
+
+    let redrawn: Vec<ChunkCoordinates> = minimap
+        .changed_chunks
+        .iter()
+        .take(CHUNKS_REDRAWN_PER_FRAME)
+        .copied()
+        .collect();
+    for chunk_coord in redrawn {
+        minimap.changed_chunks.remove(&chunk_coord);
+        let Some((structure_manager, source_manager)) = map_manager
+            .chunks
+            .get(&chunk_coord)
+            .and_then(|chunk_entity| chunk_query.get(*chunk_entity).ok())
+        else {
+            continue;
+        };
+
+        let mut pixels = Vec::with_capacity(CHUNK_SIZE.element_product() as usize);
+        for y in 0..CHUNK_SIZE.y as i32 {
+            for x in 0..CHUNK_SIZE.x as i32 {
+                let local_tile = LocalTileCoordinates { x, y };
+                let structure_color = structure_manager
+                    .structures
+                    .get(&local_tile)
+                    .and_then(|entity| structure_query.get(*entity).ok())
+                    .map(|structure| structure.color());
+                let source_color = || {
+                    source_manager
+                        .sources
+                        .get(&local_tile)
+                        .and_then(|entity| source_query.get(*entity).ok())
+                        .map(|source| source_color(source.0.item_type))
+                };
+                pixels.push(
+                    structure_color
+                        .or_else(source_color)
+                        .unwrap_or(GROUND_COLOR),
+                );
+            }
+        }
+        let image = egui::ColorImage::new([CHUNK_SIZE.x as usize, CHUNK_SIZE.y as usize], pixels);
+
+        if let Some(texture) = minimap.textures.get_mut(&chunk_coord) {
+            texture.set(image, egui::TextureOptions::NEAREST);
+        } else {
+            let texture = ctx.load_texture(
+                format!("minimap_{}_{}", chunk_coord.x, chunk_coord.y),
+                image,
+                egui::TextureOptions::NEAREST,
+            );
+            minimap.textures.insert(chunk_coord, texture);
+        }
+    }
+    Ok(())
+}
+
+/// chunk textures and units seen through the view, clipped to rect
+fn paint_map(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    view: &MapView,
+    minimap: &Minimap,
+    units: &[(Vec2, egui::Color32)],
+) {
+    let painter = painter.with_clip_rect(rect);
+    painter.rect_filled(rect, 0.0, UNEXPLORED_COLOR);
+    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+    for (chunk_coord, texture) in &minimap.textures {
+        let min = Vec2::new(
+            (chunk_coord.x * CHUNK_SIZE.x as i32) as f32,
+            (chunk_coord.y * CHUNK_SIZE.y as i32) as f32,
+        );
+        let chunk_rect = egui::Rect::from_min_max(
+            view.to_screen(rect, min),
+            view.to_screen(rect, min + CHUNK_SIZE.as_vec2()),
+        );
+        if chunk_rect.intersects(rect) {
+            painter.image(texture.id(), chunk_rect, uv, egui::Color32::WHITE);
+        }
+    }
+    for (position, color) in units {
+        painter.circle_filled(view.to_screen(rect, *position), UNIT_MARKER_RADIUS, *color);
+    }
+}
+
+#[derive(QueryData)]
+pub struct UnitMarker {
+    transform: &'static Transform,
+    is_player: Has<Player>,
+    is_worker: Has<Worker>,
+    is_hostile: Has<Hostile>,
+}
+
+/// the camera is never a unit, but the queries of both read their Transform
+type MapCamera = (With<Camera2d>, Without<Unit>);
+
+/// map position and marker color of each unit
+fn unit_markers(unit_query: &Query<UnitMarker, With<Unit>>) -> Vec<(Vec2, egui::Color32)> {
+    unit_query
+        .iter()
+        .map(|unit| {
+            let color = if unit.is_player {
+                egui::Color32::WHITE
+            } else if unit.is_hostile {
+                egui::Color32::RED
+            } else if unit.is_worker {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::LIGHT_BLUE
+            };
+            (world_to_map(unit.transform.translation), color)
+        })
+        .collect()
+}
+
+/// minimap centered on the camera, a click opens the full map
+pub fn minimap_window_system(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    camera_query: Query<&Transform, MapCamera>,
+    unit_query: Query<UnitMarker, With<Unit>>,
+) -> Result {
+    if minimap.is_full_map_open {
+        return Ok(());
+    }
+    let Ok(camera_transform) = camera_query.single() else {
+        return Ok(());
+    };
+    let view = MapView {
+        center: world_to_map(camera_transform.translation),
+        points_per_tile: MINIMAP_ZOOM,
+    };
+    let units = unit_markers(&unit_query);
+
+    let mut clicked = false;
+    egui::Window::new("Minimap")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .show(contexts.ctx_mut()?, |ui| {
+            let (response, painter) =
+                ui.allocate_painter(egui::Vec2::splat(MINIMAP_SIZE), egui::Sense::click());
+            paint_map(&painter, response.rect, &view, &minimap, &units);
+            clicked = response.clicked();
+        });
+
+    if clicked {
+        minimap.is_full_map_open = true;
+    }
+    Ok(())
+}
+
+/// full screen map, dragged to pan and scrolled to zoom ; a click moves the free camera there
+pub fn full_map_system(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    mut camera_query: Query<(&mut Transform, &mut CameraMovement), MapCamera>,
+    unit_query: Query<UnitMarker, With<Unit>>,
+) -> Result {
+    if !minimap.is_full_map_open {
+        // the next opening starts again on the camera
+        minimap.full_map_view = None;
+        return Ok(());
+    }
+    let Ok((mut camera_transform, mut camera_movement)) = camera_query.single_mut() else {
+        return Ok(());
+    };
+    let mut view = minimap.full_map_view.unwrap_or(MapView {
+        center: world_to_map(camera_transform.translation),
+        points_per_tile: FULL_MAP_DEFAULT_ZOOM,
+    });
+    let units = unit_markers(&unit_query);
+
+    let mut target = None;
+    egui::CentralPanel::default()
+        .frame(egui::Frame::NONE)
+        .show(contexts.ctx_mut()?, |ui| {
+            let (response, painter) =
+                ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
+            let rect = response.rect;
+
+            let drag = response.drag_delta();
+            view.center -= Vec2::new(drag.x, drag.y) / view.points_per_tile;
+            if let Some(hover_position) = response.hover_pos() {
+                let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+                if scroll != 0.0 {
+                    view.zoom_at(rect, hover_position, (scroll * 0.005).exp());
+                }
+            }
+            if response.clicked()
+                && let Some(click_position) = response.interact_pointer_pos()
+            {
+                target = Some(view.to_map(rect, click_position));
+            }
+
+            paint_map(&painter, rect, &view, &minimap, &units);
+            let camera_position = view.to_screen(rect, world_to_map(camera_transform.translation));
+            painter.circle_stroke(
+                camera_position,
+                UNIT_MARKER_RADIUS * 3.0,
+                egui::Stroke::new(1.5, egui::Color32::WHITE),
+            );
+            painter.text(
+                rect.left_top() + egui::vec2(8.0, 8.0),
+                egui::Align2::LEFT_TOP,
+                "drag to pan, scroll to zoom, click to move the camera",
+                egui::FontId::proportional(14.0),
+                egui::Color32::WHITE,
+            );
+        });
+
+    if let Some(target) = target {
+        let world_position = map_to_world(target);
+        camera_transform.translation.x = world_position.x;
+        camera_transform.translation.y = world_position.y;
+        camera_movement.0 = CameraMovementKind::FreeCamera;
+        minimap.is_full_map_open = false;
+    } else {
+        minimap.full_map_view = Some(view);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_view_zoom_keeps_the_point_under_the_cursor() {
+        let rect = egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(800.0, 600.0));
+        let mut view = MapView {
+            center: Vec2::new(10.0, -5.0),
+            points_per_tile: 4.0,
+        };
+        assert_eq!(view.to_screen(rect, view.center), rect.center());
+        assert_eq!(
+            view.to_map(rect, view.to_screen(rect, Vec2::new(3.0, 7.0))),
+            Vec2::new(3.0, 7.0)
+        );
+
+        let cursor = egui::pos2(600.0, 100.0);
+        let under_cursor = view.to_map(rect, cursor);
+        view.zoom_at(rect, cursor, 2.0);
+        assert_eq!(view.points_per_tile, 8.0);
+        assert!(view.to_map(rect, cursor).distance(under_cursor) < 1e-4);
+
+        // the zoom stays in its range
+        view.zoom_at(rect, cursor, 100.0);
+        assert_eq!(view.points_per_tile, FULL_MAP_ZOOM_RANGE.1);
+
+        // the tile (2, 3) is drawn right and below the tile (0, 0)
+        assert_eq!(
+            world_to_map(Vec3::new(2.5, -3.5, 0.0) * TILE_SIZE.extend(1.0)),
+            Vec2::new(2.5, 3.5)
+        );
+        assert_eq!(
+            map_to_world(Vec2::new(2.5, 3.5)),
+            Vec2::new(2.5, -3.5) * TILE_SIZE
+        );
+    }
+}
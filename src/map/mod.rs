@@ -1,4 +1,11 @@
+pub mod ghost;
 pub mod machine;
 mod map;
+pub mod minimap;
+pub mod modules;
+pub mod pollution;
+pub mod ports;
+pub mod render;
+pub mod walkability;
 
 pub use map::*;
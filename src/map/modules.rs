@@ -0,0 +1,191 @@
+use crate::{
+    items::{
+        ItemType,
+        inventory::{Inventory, ModuleInventory},
+    },
+    map::machine::Machine,
+    research::technology::{Research, Unlock},
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const MIN_SPEED_MULTIPLIER: f32 = 0.2;
+const MIN_CONSUMPTION_MULTIPLIER: f32 = 0.2;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MachineTier {
+    #[default]
+    Mk1,
+    Mk2,
+    Mk3,
+}
+impl MachineTier {
+    pub fn base_speed(&self) -> f32 {
+        match self {
+            MachineTier::Mk1 => 1.0,
+            MachineTier::Mk2 => 1.5,
+            MachineTier::Mk3 => 2.0,
+        }
+    }
+
+    pub fn module_slots(&self) -> u32 {
+        match self {
+            MachineTier::Mk1 => 0,
+            MachineTier::Mk2 => 2,
+            MachineTier::Mk3 => 4,
+        }
+    }
+}
+
+/// bonuses given by one module, they are added together before being applied to the machine
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ModuleEffect {
+    pub speed: f32,
+    pub productivity: f32,
+    pub consumption: f32,
+    pub quality: f32,
+}
+impl ModuleEffect {
+    pub fn from_item_type(item_type: ItemType) -> Option<Self> {
+        let effect = match item_type {
+            ItemType::SpeedModule => ModuleEffect {
+                speed: 0.5,
+                consumption: 0.7,
+                ..default()
+            },
+            ItemType::ProductivityModule => ModuleEffect {
+                speed: -0.15,
+                productivity: 0.1,
+                consumption: 0.4,
+                ..default()
+            },
+            ItemType::EfficiencyModule => ModuleEffect {
+                consumption: -0.3,
+                ..default()
+            },
+            ItemType::QualityModule => ModuleEffect {
+                speed: -0.05,
+                quality: 0.1,
+                ..default()
+            },
+            _ => return None,
+        };
+        Some(effect)
+    }
+}
+
+/// final multipliers of a machine computed from its MachineTier and its modules
+/// productivity and quality are the fraction of actions that give a bonus output / a better quality output
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct MachineModifiers {
+    pub speed: f32,
+    pub productivity: f32,
+    pub consumption: f32,
+    pub quality: f32,
+}
+impl MachineModifiers {
+    pub fn new(tier: MachineTier, modules: &Inventory) -> Self {
+        let mut total = ModuleEffect::default();
+        let mut remaining_slots = tier.module_slots();
+        for slot in modules.slots.iter() {
+            let Some(effect) = ModuleEffect::from_item_type(slot.item_type) else {
+                continue;
+            };
+            let quantity = slot.quantity.min(remaining_slots);
+            remaining_slots -= quantity;
+            total.speed += effect.speed * quantity as f32;
+            total.productivity += effect.productivity * quantity as f32;
+            total.consumption += effect.consumption * quantity as f32;
+            total.quality += effect.quality * quantity as f32;
+        }
+
+        Self {
+            speed: tier.base_speed() * (1.0 + total.speed).max(MIN_SPEED_MULTIPLIER),
+            productivity: total.productivity.max(0.0),
+            consumption: (1.0 + total.consumption).max(MIN_CONSUMPTION_MULTIPLIER),
+            quality: total.quality.max(0.0),
+        }
+    }
+}
+impl Default for MachineModifiers {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            productivity: 0.0,
+            consumption: 1.0,
+            quality: 0.0,
+        }
+    }
+}
+
+/// the modules not unlocked by the research are ignored, every machine is updated when a technology is researched
+pub fn update_machine_modifiers_system(
+    mut machine_query: Query<(
+        &mut Machine,
+        Ref<MachineTier>,
+        Ref<ModuleInventory>,
+        &mut MachineModifiers,
+    )>,
+    research: Res<Research>,
+) {
+    for (mut machine, tier, module_inventory, mut modifiers) in machine_query.iter_mut() {
+        if !research.is_changed() && !tier.is_changed() && !module_inventory.is_changed() {
+            continue;
+        }
+        let mut modules = module_inventory.0.clone();
+        modules
+            .slots
+            .retain(|slot| research.is_unlocked(Unlock::Module(slot.item_type)));
+        *modifiers = MachineModifiers::new(*tier, &modules);
+        machine.action_speed = modifiers.speed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::{Quality, inventory::ItemStack};
+
+    #[test]
+    fn test_modifiers_without_modules() {
+        let inventory = Inventory::default();
+        assert_eq!(
+            MachineModifiers::new(MachineTier::Mk1, &inventory),
+            MachineModifiers::default()
+        );
+        assert_eq!(
+            MachineModifiers::new(MachineTier::Mk3, &inventory).speed,
+            MachineTier::Mk3.base_speed()
+        );
+    }
+
+    #[test]
+    fn test_modifiers_limited_by_module_slots() {
+        let mut inventory = Inventory::default();
+        inventory
+            .slots
+            .push(ItemStack::new(ItemType::SpeedModule, Quality::Standard, 3));
+
+        // Mk1 has no module slots
+        let modifiers = MachineModifiers::new(MachineTier::Mk1, &inventory);
+        assert_eq!(modifiers.speed, 1.0);
+
+        // Mk2 only uses 2 of the 3 modules
+        let modifiers = MachineModifiers::new(MachineTier::Mk2, &inventory);
+        assert_eq!(modifiers.speed, MachineTier::Mk2.base_speed() * 2.0);
+        assert!((modifiers.consumption - 2.4).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_modifiers_are_clamped() {
+        let mut inventory = Inventory::default();
+        inventory.slots.push(ItemStack::new(
+            ItemType::EfficiencyModule,
+            Quality::Standard,
+            4,
+        ));
+        let modifiers = MachineModifiers::new(MachineTier::Mk3, &inventory);
+        assert_eq!(modifiers.consumption, MIN_CONSUMPTION_MULTIPLIER);
+        assert_eq!(modifiers.productivity, 0.0);
+    }
+}
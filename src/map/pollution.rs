@@ -0,0 +1,270 @@
+use crate::{
+    UPS_TARGET,
+    map::{
+        ChunkCoordinates, MapManager, absolute_coord_to_chunk_coord,
+        machine::{BeltMachine, Machine, transfert_items_to_next_machine_system},
+        modules::MachineModifiers,
+    },
+    simulation::SimulationTick,
+};
+use bevy::prelude::*;
+
+/// pollution emitted by a working machine each tick, multiplied by the consumption of its modules
+pub const MACHINE_POLLUTION_PER_TICK: f32 = 0.01;
+/// ticks between two spreads of the pollution
+const POLLUTION_SPREAD_INTERVAL_TICKS: u64 = UPS_TARGET as u64;
+/// part of the pollution of a chunk given to each of its 4 neighbours at each spread
+const POLLUTION_SPREAD_RATE: f32 = 0.05;
+/// part of the pollution of a chunk absorbed by the ground at each spread
+const POLLUTION_ABSORPTION_RATE: f32 = 0.02;
+/// a chunk with less pollution than this is clean
+const MIN_POLLUTION: f32 = 0.01;
+
+pub struct PollutionPlugin;
+
+impl Plugin for PollutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                emit_pollution_system.after(transfert_items_to_next_machine_system),
+                spread_pollution_system.run_if(is_spread_tick),
+            )
+                .chain(),
+        );
+    }
+}
+
+impl MapManager {
+    pub fn pollution(&self, chunk_coord: ChunkCoordinates) -> f32 {
+        self.pollution
+            .get(&chunk_coord)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn add_pollution(&mut self, chunk_coord: ChunkCoordinates, amount: f32) {
+        *self.pollution.entry(chunk_coord).or_default() += amount;
+    }
+
+    /// moves a part of the pollution of each chunk to its spawned neighbours, and absorbs a part of it
+    /// the chunks are taken sorted by (y, x) : the float sums must not depend on the HashMap order to stay deterministic
+    pub fn spread_pollution(&mut self) {
+        let mut polluted: Vec<(ChunkCoordinates, f32)> = self
+            .pollution
+            .iter()
+            .map(|(chunk_coord, pollution)| (*chunk_coord, *pollution))
+            .collect();
+        polluted.sort_by_key(|(chunk_coord, _)| (chunk_coord.y, chunk_coord.x));
+
+        let mut spread = self.pollution.clone();
+        for (chunk_coord, pollution) in &polluted {
+            let neighbours: Vec<ChunkCoordinates> = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .into_iter()
+                .map(|(x, y)| ChunkCoordinates {
+                    x: chunk_coord.x + x,
+                    y: chunk_coord.y + y,
+                })
+                .filter(|neighbour| self.chunks.contains_key(neighbour))
+                .collect();
+            let given = pollution * POLLUTION_SPREAD_RATE;
+            *spread.entry(*chunk_coord).or_default() -=
+                given * neighbours.len() as f32 + pollution * POLLUTION_ABSORPTION_RATE;
+            for neighbour in neighbours {
+                *spread.entry(neighbour).or_default() += given;
+            }
+        }
+        spread.retain(|_, pollution| *pollution >= MIN_POLLUTION);
+        self.pollution = spread;
+    }
+}
+
+/// the machines whose action advanced during this tick pollute their chunk, the belts don't
+pub fn emit_pollution_system(
+    machine_query: Query<(&Machine, &MachineModifiers, &Transform), Without<BeltMachine>>,
+    mut map_manager: ResMut<MapManager>,
+) {
+    for (machine, modifiers, transform) in machine_query.iter() {
+        if !machine.worked_this_tick {
+            continue;
+        }
+        map_manager.add_pollution(
+            absolute_coord_to_chunk_coord((*transform).into()),
+            MACHINE_POLLUTION_PER_TICK * modifiers.consumption,
+        );
+    }
+}
+
+fn is_spread_tick(simulation_tick: Res<SimulationTick>) -> bool {
+    simulation_tick
+        .0
+        .is_multiple_of(POLLUTION_SPREAD_INTERVAL_TICKS)
+}
+
+pub fn spread_pollution_system(mut map_manager: ResMut<MapManager>) {
+    map_manager.spread_pollution();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{
+            ItemType, Quality,
+            inventory::{InputInventory, Inventory, ItemStack, OutputInventory},
+            recipe::{RecipeBook, RecipeId},
+            statistics::ProductionStatistics,
+        },
+        map::{
+            chunk_coord_to_tile_coord,
+            machine::{
+                BeltMachine, Burner, CraftingMachine, FurnaceMachine, MiningMachine,
+                process_belt_machines_system, process_crafting_machines_system,
+                process_furnace_machines_system, process_mining_machines_system,
+            },
+            tile_coord_to_absolute_coord,
+        },
+        research::technology::Research,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_pollution_spreads_to_spawned_chunks_only_and_fades() {
+        let mut map_manager = MapManager::default();
+        let origin = ChunkCoordinates { x: 0, y: 0 };
+        let east = ChunkCoordinates { x: 1, y: 0 };
+        map_manager
+            .chunks
+            .insert(origin, Entity::from_raw_u32(1).unwrap());
+        map_manager
+            .chunks
+            .insert(east, Entity::from_raw_u32(2).unwrap());
+        map_manager.add_pollution(origin, 100.0);
+
+        map_manager.spread_pollution();
+        assert_eq!(map_manager.pollution(east), 100.0 * POLLUTION_SPREAD_RATE);
+        assert_eq!(map_manager.pollution(ChunkCoordinates { x: -1, y: 0 }), 0.0);
+        assert!(map_manager.pollution(origin) + map_manager.pollution(east) < 100.0);
+
+        for _ in 0..1000 {
+            map_manager.spread_pollution();
+        }
+        assert!(map_manager.pollution.is_empty());
+    }
+
+    #[test]
+    fn test_pollution_spread_is_independent_of_the_hash_map_order() {
+        let west = ChunkCoordinates { x: 0, y: 0 };
+        let shared = ChunkCoordinates { x: 1, y: 0 };
+        let east = ChunkCoordinates { x: 2, y: 0 };
+        let spread_from_two_sources = |order: [ChunkCoordinates; 3]| {
+            let mut map_manager = MapManager::default();
+            for (index, chunk_coord) in order.into_iter().enumerate() {
+                map_manager
+                    .chunks
+                    .insert(chunk_coord, Entity::from_raw_u32(index as u32 + 1).unwrap());
+            }
+            map_manager.add_pollution(west, 0.3);
+            map_manager.add_pollution(east, 1e7);
+            map_manager.add_pollution(shared, 0.7);
+            for _ in 0..10 {
+                map_manager.spread_pollution();
+            }
+            [west, shared, east].map(|chunk_coord| map_manager.pollution(chunk_coord).to_bits())
+        };
+
+        // each MapManager has its own HashMap hasher, so its own iteration order
+        let expected = spread_from_two_sources([west, shared, east]);
+        for _ in 0..20 {
+            assert_eq!(spread_from_two_sources([east, shared, west]), expected);
+            assert_eq!(spread_from_two_sources([shared, west, east]), expected);
+        }
+    }
+
+    #[test]
+    fn test_only_the_machines_that_worked_this_tick_pollute() {
+        let mut world = World::new();
+        world.init_resource::<MapManager>();
+        world.insert_resource(RecipeBook::default());
+        world.init_resource::<Research>();
+        world.init_resource::<ProductionStatistics>();
+        let chunk_transform = |x| {
+            let tile = chunk_coord_to_tile_coord(ChunkCoordinates { x, y: 0 });
+            let absolute_coord = tile_coord_to_absolute_coord(tile);
+            Transform::from_xyz(absolute_coord.x, absolute_coord.y, 0.0)
+        };
+        let iron_ore = ItemStack::new(ItemType::IronOre, Quality::Standard, 1);
+        let mut ore_inventory = Inventory::default();
+        ore_inventory.add(iron_ore).unwrap();
+        let mut full_inventory = Inventory::with_slots(1);
+        while full_inventory
+            .add(ItemStack::new(ItemType::IronGear, Quality::Standard, 1))
+            .is_ok()
+        {}
+        let halfway = || Machine {
+            action_progress_ticks: 10,
+            ..default()
+        };
+
+        // a miner with room for its ore
+        world.spawn((
+            Machine::default(),
+            MiningMachine::new(iron_ore),
+            OutputInventory::default(),
+            chunk_transform(0),
+        ));
+        // a crafter whose gears don't fit in its output
+        world.spawn((
+            Machine {
+                action_progress_ticks: Machine::default().action_time_ticks,
+                ..default()
+            },
+            CraftingMachine::new(RecipeId::IronPlateToIronGear),
+            InputInventory::default(),
+            OutputInventory(full_inventory),
+            chunk_transform(1),
+        ));
+        // a furnace out of fuel in the middle of a smelt
+        world.spawn((
+            halfway(),
+            FurnaceMachine {
+                current_recipe_id: Some(RecipeId::IronOreToIronPlate),
+            },
+            Burner::default(),
+            InputInventory(ore_inventory.clone()),
+            OutputInventory::default(),
+            chunk_transform(2),
+        ));
+        // a belt moving items
+        world.spawn((
+            halfway(),
+            BeltMachine,
+            InputInventory(ore_inventory),
+            OutputInventory::default(),
+            chunk_transform(3),
+        ));
+
+        for _ in 0..3 {
+            world
+                .run_system_once(process_crafting_machines_system)
+                .unwrap();
+            world.run_system_once(process_belt_machines_system).unwrap();
+            world
+                .run_system_once(process_mining_machines_system)
+                .unwrap();
+            world
+                .run_system_once(process_furnace_machines_system)
+                .unwrap();
+            world.run_system_once(emit_pollution_system).unwrap();
+        }
+
+        let map_manager = world.resource::<MapManager>();
+        assert_eq!(
+            map_manager.pollution(ChunkCoordinates { x: 0, y: 0 }),
+            3.0 * MACHINE_POLLUTION_PER_TICK
+        );
+        for x in 1..4 {
+            assert_eq!(map_manager.pollution(ChunkCoordinates { x, y: 0 }), 0.0);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    items::ItemType,
+    map::{Footprint, TileCoordinates},
+    units::Direction,
+};
+use bevy::prelude::*;
+
+/// which way items go through a port, Fuel ports are input ports that fill the FuelInventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    Input,
+    Fuel,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    pub name: &'static str,
+    pub kind: PortKind,
+    /// side of the footprint, relative to the machine facing North (North is the front)
+    pub side: Direction,
+    /// position along the edge, going clockwise around the footprint ; None is the middle of the edge
+    pub offset: Option<u32>,
+    /// item types allowed through the port, None allows everything
+    pub filter: Option<Vec<ItemType>>,
+}
+impl Port {
+    pub fn new(name: &'static str, kind: PortKind, side: Direction) -> Self {
+        Self {
+            name,
+            kind,
+            side,
+            offset: None,
+            filter: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Vec<ItemType>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn accepts(&self, item_type: ItemType) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.contains(&item_type))
+    }
+
+    pub fn is_input(&self) -> bool {
+        matches!(self.kind, PortKind::Input | PortKind::Fuel)
+    }
+
+    /// local footprint coordinates of the tile covered by the machine where the port is
+    fn local_tile(&self, footprint: &Footprint) -> IVec2 {
+        let (w, h) = (footprint.width as i32, footprint.height as i32);
+        let edge_length = match self.side {
+            Direction::North | Direction::South => w,
+            Direction::East | Direction::West => h,
+        };
+        let offset = self
+            .offset
+            .map_or(edge_length / 2, |offset| offset as i32)
+            .clamp(0, edge_length - 1);
+        match self.side {
+            Direction::North => IVec2::new(offset, 0),
+            Direction::East => IVec2::new(w - 1, offset),
+            Direction::South => IVec2::new(w - 1 - offset, h - 1),
+            Direction::West => IVec2::new(0, h - 1 - offset),
+        }
+    }
+
+    /// tile covered by the machine where the port is
+    pub fn inside_tile(
+        &self,
+        footprint: &Footprint,
+        anchor: TileCoordinates,
+        direction: Direction,
+    ) -> TileCoordinates {
+        footprint.local_to_tile_coord(anchor, direction, self.local_tile(footprint))
+    }
+
+    /// tile just outside the machine that the port faces
+    pub fn outside_tile(
+        &self,
+        footprint: &Footprint,
+        anchor: TileCoordinates,
+        direction: Direction,
+    ) -> TileCoordinates {
+        let inside_tile = self.inside_tile(footprint, anchor, direction);
+        let delta = self.side.rotate_by(direction).direction_to_vec2();
+        TileCoordinates {
+            x: inside_tile.x + delta.x,
+            y: inside_tile.y + delta.y,
+        }
+    }
+}
+
+/// ports of a machine, machines without declared ports output in front of them and take items from every side
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Ports(pub Vec<Port>);
+impl Ports {
+    pub fn get(&self, name: &str) -> Option<&Port> {
+        self.0.iter().find(|port| port.name == name)
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &Port> {
+        self.0.iter().filter(|port| port.is_input())
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &Port> {
+        self.0.iter().filter(|port| port.kind == PortKind::Output)
+    }
+
+    /// furnace layout : ore from the back, fuel from the sides, plates out of the front
+    pub fn furnace() -> Self {
+        Self(vec![
+            Port::new("output", PortKind::Output, Direction::North),
+            Port::new("ore", PortKind::Input, Direction::South)
+                .with_filter(vec![ItemType::IronOre, ItemType::CopperOre]),
+            Port::new("fuel_left", PortKind::Fuel, Direction::West)
+                .with_filter(vec![ItemType::Coal, ItemType::Wood]),
+            Port::new("fuel_right", PortKind::Fuel, Direction::East)
+                .with_filter(vec![ItemType::Coal, ItemType::Wood]),
+        ])
+    }
+
+    /// one item type taken from every side, nothing goes out
+    fn inputs_only(item_type: ItemType) -> Self {
+        Self(
+            [
+                ("input_front", Direction::North),
+                ("input_right", Direction::East),
+                ("input_back", Direction::South),
+                ("input_left", Direction::West),
+            ]
+            .into_iter()
+            .map(|(name, side)| Port::new(name, PortKind::Input, side).with_filter(vec![item_type]))
+            .collect(),
+        )
+    }
+
+    /// turret layout : ammo from every side
+    pub fn turret() -> Self {
+        Self::inputs_only(ItemType::Ammo)
+    }
+
+    /// lab layout : science packs from every side
+    pub fn lab() -> Self {
+        Self::inputs_only(ItemType::SciencePack)
+    }
+}
+impl Default for Ports {
+    fn default() -> Self {
+        Self(vec![
+            Port::new("output", PortKind::Output, Direction::North),
+            Port::new("input_front", PortKind::Input, Direction::North),
+            Port::new("input_right", PortKind::Input, Direction::East),
+            Port::new("input_back", PortKind::Input, Direction::South),
+            Port::new("input_left", PortKind::Input, Direction::West),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_output_port_matches_front_tile() {
+        let anchor = TileCoordinates { x: 3, y: -2 };
+        let ports = Ports::default();
+        let output = ports.get("output").unwrap();
+        for footprint in [Footprint::default(), Footprint::new(3, 2)] {
+            for direction in [
+                Direction::North,
+                Direction::East,
+                Direction::South,
+                Direction::West,
+            ] {
+                assert_eq!(
+                    output.outside_tile(&footprint, anchor, direction),
+                    footprint.front_tile(anchor, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ports_on_each_side() {
+        let footprint = Footprint::new(2, 2);
+        let anchor = TileCoordinates { x: 0, y: 0 };
+
+        let back = Port::new("back", PortKind::Input, Direction::South).with_offset(0);
+        // facing North the back edge is the bottom row, going clockwise it starts on the right
+        assert_eq!(
+            back.inside_tile(&footprint, anchor, Direction::North),
+            TileCoordinates { x: 1, y: 1 }
+        );
+        assert_eq!(
+            back.outside_tile(&footprint, anchor, Direction::North),
+            TileCoordinates { x: 1, y: 2 }
+        );
+        // facing East the back edge is the left column
+        assert_eq!(
+            back.outside_tile(&footprint, anchor, Direction::East),
+            TileCoordinates { x: -1, y: 1 }
+        );
+
+        let left = Port::new("left", PortKind::Input, Direction::West).with_offset(1);
+        assert_eq!(
+            left.outside_tile(&footprint, anchor, Direction::North),
+            TileCoordinates { x: -1, y: 0 }
+        );
+    }
+
+    #[test]
+    fn test_port_filter() {
+        let port =
+            Port::new("fuel", PortKind::Fuel, Direction::West).with_filter(vec![ItemType::Coal]);
+        assert!(port.accepts(ItemType::Coal));
+        assert!(!port.accepts(ItemType::IronOre));
+        assert!(port.is_input());
+        assert!(Port::new("any", PortKind::Output, Direction::North).accepts(ItemType::IronOre));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    environment::render::AmbientTint,
+    map::{CHUNK_SIZE, Chunk, Footprint, SpritePath, TILE_SIZE},
+};
+use bevy::{
+    prelude::*,
+    sprite_render::{TileData, TilemapChunk, TilemapChunkTileData},
+};
+use rand::Rng;
+
+/// everything the map needs to be drawn, left out of headless simulations
+pub struct MapRenderPlugin;
+
+impl Plugin for MapRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                attach_chunk_tilemap_system,
+                load_sprites_system,
+                update_tileset_image,
+            ),
+        );
+    }
+}
+
+pub fn attach_chunk_tilemap_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    chunk_query: Query<Entity, Added<Chunk>>,
+) {
+    let mut rng = rand::rng();
+    for chunk_entity in chunk_query.iter() {
+        let tile_data: Vec<Option<TileData>> = (0..CHUNK_SIZE.element_product())
+            // .map(|_| rng.random_range(0..5))
+            .map(|_| rng.random_range(1..2))
+            .map(|i| {
+                if i == 0 {
+                    None
+                } else {
+                    Some(TileData::from_tileset_index(i - 1))
+                }
+            })
+            .collect();
+        commands.entity(chunk_entity).insert((
+            TilemapChunk {
+                chunk_size: CHUNK_SIZE,
+                tile_display_size: UVec2::splat(TILE_SIZE.x as u32),
+                tileset: asset_server.load("textures/array_texture.png"),
+                ..default()
+            },
+            TilemapChunkTileData(tile_data),
+        ));
+    }
+}
+
+pub fn load_sprites_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    query: Query<(Entity, &SpritePath, Option<&Footprint>), Added<SpritePath>>,
+) {
+    for (entity, sprite_path, footprint) in query.iter() {
+        let mut sprite = Sprite::from_image(asset_server.load(sprite_path.0.clone()));
+        // multi-tile structures stretch their sprite over the whole footprint
+        if let Some(footprint) = footprint
+            && *footprint != Footprint::default()
+        {
+            sprite.custom_size = Some(Vec2::new(
+                footprint.width as f32 * TILE_SIZE.x,
+                footprint.height as f32 * TILE_SIZE.y,
+            ));
+        }
+        commands
+            .entity(entity)
+            .insert((sprite, AmbientTint::default()));
+    }
+}
+
+fn update_tileset_image(
+    chunk_query: Single<&TilemapChunk>,
+    mut events: MessageReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let chunk = *chunk_query;
+    for event in events.read() {
+        if event.is_loaded_with_dependencies(chunk.tileset.id()) {
+            let image = images.get_mut(&chunk.tileset).unwrap();
+            image.reinterpret_stacked_2d_as_array(4);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::map::{
+    CHUNK_SIZE, ChunkCoordinates, LocalTileCoordinates, TileCoordinates,
+    local_tile_coord_to_tile_coord, tile_coord_to_chunk_coord, tile_coord_to_local_tile_coord,
+};
+use std::collections::{HashMap, HashSet};
+
+const CHUNK_AREA: usize = (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize;
+
+/// walkable tiles of the spawned chunks, one dense array per chunk so the pathfinding does not look up the structure layers
+/// tiles of chunks that are not spawned yet are walkable
+#[derive(Debug, Default)]
+pub struct WalkabilityGrid {
+    chunks: HashMap<ChunkCoordinates, Vec<bool>>,
+    /// tiles whose walkability changed since the last take_changes
+    changes: Vec<TileCoordinates>,
+    /// chunks spawned or whose walkability changed since the last take_changed_chunks
+    changed_chunks: HashSet<ChunkCoordinates>,
+}
+impl WalkabilityGrid {
+    fn index(local_tile: LocalTileCoordinates) -> usize {
+        (local_tile.y as u32 * CHUNK_SIZE.x + local_tile.x as u32) as usize
+    }
+
+    pub fn contains_chunk(&self, chunk_coord: ChunkCoordinates) -> bool {
+        self.chunks.contains_key(&chunk_coord)
+    }
+
+    pub fn is_walkable(&self, tile: TileCoordinates) -> bool {
+        let chunk_coord = tile_coord_to_chunk_coord(tile);
+        self.chunks.get(&chunk_coord).is_none_or(|walkable| {
+            walkable[Self::index(tile_coord_to_local_tile_coord(tile, chunk_coord))]
+        })
+    }
+
+    /// adds a spawned chunk, the blocked tiles are recorded as changes
+    pub fn insert_chunk(
+        &mut self,
+        chunk_coord: ChunkCoordinates,
+        blocked_tiles: impl IntoIterator<Item = LocalTileCoordinates>,
+    ) {
+        let mut walkable = vec![true; CHUNK_AREA];
+        for local_tile in blocked_tiles {
+            walkable[Self::index(local_tile)] = false;
+            self.changes
+                .push(local_tile_coord_to_tile_coord(local_tile, chunk_coord));
+        }
+        self.chunks.insert(chunk_coord, walkable);
+        self.changed_chunks.insert(chunk_coord);
+    }
+
+    /// does nothing for chunks that are not spawned, their tiles are set by insert_chunk
+    pub fn set_walkable(&mut self, tile: TileCoordinates, is_walkable: bool) {
+        let chunk_coord = tile_coord_to_chunk_coord(tile);
+        let Some(walkable) = self.chunks.get_mut(&chunk_coord) else {
+            return;
+        };
+        let index = Self::index(tile_coord_to_local_tile_coord(tile, chunk_coord));
+        if walkable[index] != is_walkable {
+            walkable[index] = is_walkable;
+            self.changes.push(tile);
+            self.changed_chunks.insert(chunk_coord);
+        }
+    }
+
+    pub fn take_changes(&mut self) -> Vec<TileCoordinates> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn take_changed_chunks(&mut self) -> HashSet<ChunkCoordinates> {
+        std::mem::take(&mut self.changed_chunks)
+    }
+}
@@ -0,0 +1,341 @@
+use crate::{
+    map::{Chunk, Source, Structure, ghost::Ghost, machine::Machine},
+    simulation::SimulationTick,
+    units::{Unit, combat::Hostile, worker::Worker},
+};
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::query::QueryData,
+    log::{
+        BoxedLayer,
+        tracing::{
+            Subscriber,
+            field::{Field, Visit},
+            span::{Attributes, Id},
+        },
+        tracing_subscriber::{Layer, layer::Context, registry::LookupSpan},
+    },
+    prelude::*,
+};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// opens or closes the profiler overlay
+pub const PROFILER_KEY: KeyCode = KeyCode::F3;
+/// seconds of UPS kept for the graph
+const UPS_HISTORY_SECONDS: usize = 60;
+const UPS_GRAPH_SIZE: egui::Vec2 = egui::vec2(300.0, 60.0);
+
+/// overlay with the time spent in each FixedUpdate system, the entity counts and the UPS of the last minute
+/// the system times come from the spans of the bevy `trace` feature, turned on by the `profiler` feature, recorded by the layer of `profiler_layer`
+pub struct ProfilerPlugin;
+
+impl Plugin for ProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Profiler>()
+            .init_resource::<SystemTimes>()
+            .add_systems(Update, (toggle_profiler_system, sample_profiler_system))
+            .add_systems(EguiPrimaryContextPass, profiler_overlay_system);
+    }
+}
+
+/// total time spent in each system since the start, by system name, filled by SystemTimeLayer
+#[derive(Resource, Clone, Default)]
+pub struct SystemTimes(pub Arc<Mutex<HashMap<String, Duration>>>);
+
+/// to give to `LogPlugin::custom_layer`, shares its SystemTimes with the app
+pub fn profiler_layer(app: &mut App) -> Option<BoxedLayer> {
+    let system_times = SystemTimes::default();
+    app.insert_resource(system_times.clone());
+    Some(Box::new(SystemTimeLayer(system_times)))
+}
+
+/// name of the system of a span
+struct SystemName(String);
+/// when the system span was entered
+struct EnteredAt(Instant);
+
+struct NameVisitor(Option<String>);
+impl Visit for NameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// measures the time between the enter and the exit of the "system" spans
+pub struct SystemTimeLayer(SystemTimes);
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SystemTimeLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "system" {
+            return;
+        }
+        let mut visitor = NameVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(name), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SystemName(name));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.extensions().get::<SystemName>().is_some() {
+            span.extensions_mut().replace(EnteredAt(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let (Some(SystemName(name)), Some(EnteredAt(entered_at))) = (
+            extensions.get::<SystemName>(),
+            extensions.get::<EnteredAt>(),
+        ) else {
+            return;
+        };
+        if let Ok(mut system_times) = self.0.0.lock() {
+            *system_times.entry(name.clone()).or_default() += entered_at.elapsed();
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Profiler {
+    pub is_open: bool,
+    last_sample_secs: f64,
+    last_tick: u64,
+    last_system_times: HashMap<String, Duration>,
+    /// mean time per tick of each FixedUpdate system during the last second, slowest first
+    pub system_times: Vec<(String, Duration)>,
+    /// ticks run during each of the last seconds, oldest first
+    pub ups_history: VecDeque<u64>,
+    pub entity_counts: Vec<(&'static str, usize)>,
+}
+
+/// mean time per tick of the given systems between two readings of SystemTimes, slowest first
+fn time_per_tick(
+    previous: &HashMap<String, Duration>,
+    current: &HashMap<String, Duration>,
+    systems: &[String],
+    ticks: u64,
+) -> Vec<(String, Duration)> {
+    let mut times: Vec<(String, Duration)> = systems
+        .iter()
+        .map(|system| {
+            let total = current.get(system).copied().unwrap_or_default();
+            let spent = total.saturating_sub(previous.get(system).copied().unwrap_or_default());
+            (system.clone(), spent / ticks.max(1) as u32)
+        })
+        .collect();
+    times.sort_by(|(a_name, a_time), (b_name, b_time)| b_time.cmp(a_time).then(a_name.cmp(b_name)));
+    times
+}
+
+pub fn toggle_profiler_system(input: Res<ButtonInput<KeyCode>>, mut profiler: ResMut<Profiler>) {
+    if input.just_pressed(PROFILER_KEY) {
+        profiler.is_open = !profiler.is_open;
+    }
+}
+
+#[derive(QueryData)]
+pub struct EntityKind {
+    is_unit: Has<Unit>,
+    is_worker: Has<Worker>,
+    is_hostile: Has<Hostile>,
+    is_structure: Has<Structure>,
+    is_machine: Has<Machine>,
+    is_ghost: Has<Ghost>,
+    is_source: Has<Source>,
+    is_chunk: Has<Chunk>,
+}
+
+/// once per real second, reads the system times, counts the entities and the ticks run
+pub fn sample_profiler_system(
+    mut profiler: ResMut<Profiler>,
+    time: Res<Time<Real>>,
+    simulation_tick: Res<SimulationTick>,
+    system_times: Res<SystemTimes>,
+    schedules: Res<Schedules>,
+    entity_query: Query<EntityKind>,
+) {
+    let now = time.elapsed_secs_f64();
+    if now - profiler.last_sample_secs < 1.0 {
+        return;
+    }
+    profiler.last_sample_secs = now;
+    let ticks = simulation_tick.0 - profiler.last_tick;
+    profiler.last_tick = simulation_tick.0;
+    profiler.ups_history.push_back(ticks);
+    if profiler.ups_history.len() > UPS_HISTORY_SECONDS {
+        profiler.ups_history.pop_front();
+    }
+
+    let fixed_systems: Vec<String> = schedules
+        .get(FixedUpdate)
+        .and_then(|schedule| schedule.systems().ok())
+        .map(|systems| {
+            systems
+                .map(|(_, system)| system.name().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let current = system_times
+        .0
+        .lock()
+        .map(|system_times| system_times.clone())
+        .unwrap_or_default();
+    profiler.system_times =
+        time_per_tick(&profiler.last_system_times, &current, &fixed_systems, ticks);
+    profiler.last_system_times = current;
+
+    let mut counts = [0; 9];
+    for entity in entity_query.iter() {
+        counts[0] += 1;
+        for (index, is_kind) in [
+            entity.is_unit,
+            entity.is_worker,
+            entity.is_hostile,
+            entity.is_structure,
+            entity.is_machine,
+            entity.is_ghost,
+            entity.is_source,
+            entity.is_chunk,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            counts[index + 1] += is_kind as usize;
+        }
+    }
+    profiler.entity_counts = [
+        "Entities",
+        "Units",
+        "Workers",
+        "Hostiles",
+        "Structures",
+        "Machines",
+        "Ghosts",
+        "Sources",
+        "Chunks",
+    ]
+    .into_iter()
+    .zip(counts)
+    .collect();
+}
+
+pub fn profiler_overlay_system(
+    mut contexts: EguiContexts,
+    profiler: Res<Profiler>,
+    diagnostics: Res<DiagnosticsStore>,
+) -> Result {
+    if !profiler.is_open {
+        return Ok(());
+    }
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or_default();
+
+    egui::Window::new("Profiler")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+        .resizable(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            let ups = profiler.ups_history.back().copied().unwrap_or_default();
+            ui.label(format!("FPS {fps:.0} | UPS {ups}"));
+
+            // UPS of the last minute, the newest second on the right
+            let (response, painter) = ui.allocate_painter(UPS_GRAPH_SIZE, egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(
+                rect,
+                0.0,
+                ui.visuals().widgets.noninteractive.bg_stroke,
+                egui::StrokeKind::Inside,
+            );
+            let max_ups = profiler
+                .ups_history
+                .iter()
+                .copied()
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            let step = rect.width() / (UPS_HISTORY_SECONDS - 1) as f32;
+            let offset = UPS_HISTORY_SECONDS - profiler.ups_history.len();
+            let points: Vec<egui::Pos2> = profiler
+                .ups_history
+                .iter()
+                .enumerate()
+                .map(|(second, ups)| {
+                    egui::pos2(
+                        rect.left() + (offset + second) as f32 * step,
+                        rect.bottom() - *ups as f32 / max_ups as f32 * rect.height(),
+                    )
+                })
+                .collect();
+            painter.add(egui::Shape::line(
+                points,
+                egui::Stroke::new(1.5, egui::Color32::LIGHT_GREEN),
+            ));
+
+            ui.separator();
+            egui::Grid::new("profiler_systems").show(ui, |ui| {
+                for (name, time) in &profiler.system_times {
+                    // the module path is too long for the overlay
+                    ui.label(name.rsplit("::").next().unwrap_or(name));
+                    ui.label(format!("{:.3} ms", time.as_secs_f64() * 1000.0));
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            egui::Grid::new("profiler_entities").show(ui, |ui| {
+                for (kind, count) in &profiler.entity_counts {
+                    ui.label(*kind);
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_times_are_per_tick_and_sorted() {
+        let ms = Duration::from_millis;
+        let previous = HashMap::from([("a".to_owned(), ms(10)), ("b".to_owned(), ms(10))]);
+        let current = HashMap::from([
+            ("a".to_owned(), ms(20)),
+            ("b".to_owned(), ms(70)),
+            ("render".to_owned(), ms(500)),
+        ]);
+        let systems = ["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        assert_eq!(
+            time_per_tick(&previous, &current, &systems, 10),
+            vec![
+                ("b".to_owned(), ms(6)),
+                ("a".to_owned(), ms(1)),
+                ("c".to_owned(), Duration::ZERO),
+            ]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    UPS_TARGET,
+    items::{
+        ItemType,
+        inventory::{InputInventory, ItemStack},
+        statistics::ProductionStatistics,
+    },
+    map::{
+        Footprint, STRUCTURE_LAYER, SpritePath, Structure, TileCoordinates,
+        machine::{Machine, MachineBaseBundle, transfert_items_to_next_machine_system},
+        modules::update_machine_modifiers_system,
+        ports::Ports,
+    },
+    research::technology::{Research, TechnologyId},
+    units::Direction,
+};
+use bevy::prelude::*;
+
+/// ticks a lab spends on one science pack, before the speed of the machine
+const LAB_TIME_PER_PACK_TICKS: u64 = UPS_TARGET as u64 * 2;
+
+pub struct ResearchPlugin;
+
+impl Plugin for ResearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Research>()
+            .add_message::<ResearchCompleted>()
+            .add_systems(
+                FixedUpdate,
+                process_labs_system
+                    .after(update_machine_modifiers_system)
+                    .before(transfert_items_to_next_machine_system),
+            );
+    }
+}
+
+/// written when the labs complete a technology, the tech tree shows it to the player
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResearchCompleted(pub TechnologyId);
+
+/// machine consuming the science packs of its InputInventory to research the technologies
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+#[require(InputInventory)]
+pub struct Lab {
+    /// technology of the science pack being studied
+    pub technology: Option<TechnologyId>,
+}
+
+pub fn spawn_lab(
+    commands: &mut Commands,
+    tile_coord: TileCoordinates,
+    input_inventory: InputInventory,
+) -> Entity {
+    let footprint = Footprint::default();
+    let target_coord = footprint.anchor_to_absolute_coord(tile_coord, Direction::North);
+    commands
+        .spawn((
+            MachineBaseBundle {
+                name: Name::new("Lab"),
+                structure: Structure,
+                direction: Direction::North,
+                transform: Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+                machine: Machine::default(),
+            },
+            Lab::default(),
+            input_inventory,
+            Ports::lab(),
+            // until the lab has its own sprite
+            SpritePath::structure("default_machine.png"),
+        ))
+        .id()
+}
+
+/// each lab studies one science pack at a time for the technology the research targets
+pub fn process_labs_system(
+    mut lab_query: Query<(&mut Machine, &mut Lab, &mut InputInventory)>,
+    mut research: ResMut<Research>,
+    mut statistics: ResMut<ProductionStatistics>,
+    mut message_research_completed: MessageWriter<ResearchCompleted>,
+) {
+    for (mut machine, mut lab, mut input_inventory) in lab_query.iter_mut() {
+        machine.worked_this_tick = false;
+        if machine.action_progress_ticks >= machine.action_time_ticks {
+            // another lab may have completed the technology in the meantime, the pack then goes to the next target
+            if let Some(studied) = lab.technology.take()
+                && let Some(technology_id) = Some(studied)
+                    .filter(|studied| research.is_available(*studied))
+                    .or_else(|| research.target())
+                && research.add_progress(technology_id)
+            {
+                message_research_completed.write(ResearchCompleted(technology_id));
+            }
+            machine.action_progress_ticks = 0;
+        }
+
+        if machine.action_progress_ticks == 0 {
+            let Some(technology_id) = research.target() else {
+                continue;
+            };
+            let Some(science_pack) = input_inventory
+                .0
+                .slots
+                .iter()
+                .find(|slot| slot.item_type == ItemType::SciencePack)
+                .copied()
+            else {
+                continue;
+            };
+            let science_pack = ItemStack {
+                quantity: 1,
+                ..science_pack
+            };
+            input_inventory.0.remove_quantity(science_pack);
+            statistics.record_consumed(&[science_pack]);
+
+            lab.technology = Some(technology_id);
+            machine.action_time_ticks =
+                (LAB_TIME_PER_PACK_TICKS as f32 / machine.action_speed) as u64;
+            machine.action_progress_ticks = 1;
+        } else {
+            machine.action_progress_ticks += 1;
+        }
+        machine.worked_this_tick = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::machine::DEFAULT_ACTION_TIME_TICKS;
+    use bevy::ecs::{message::Messages, system::RunSystemOnce};
+
+    #[test]
+    fn test_pack_of_a_technology_completed_by_another_lab_goes_to_the_next_one() {
+        let mut world = World::new();
+        world.init_resource::<ProductionStatistics>();
+        world.init_resource::<Messages<ResearchCompleted>>();
+        let mut research = Research::default();
+        let ammunition_cost = TechnologyId::Ammunition.technology().cost;
+        research
+            .progress
+            .insert(TechnologyId::Ammunition, ammunition_cost - 1);
+        world.insert_resource(research);
+        // both labs finish a pack of the last missing one
+        for _ in 0..2 {
+            world.spawn((
+                Machine {
+                    action_progress_ticks: DEFAULT_ACTION_TIME_TICKS,
+                    ..default()
+                },
+                Lab {
+                    technology: Some(TechnologyId::Ammunition),
+                },
+                InputInventory::default(),
+            ));
+        }
+
+        world.run_system_once(process_labs_system).unwrap();
+        let research = world.resource::<Research>();
+        assert!(research.is_researched(TechnologyId::Ammunition));
+        let next = research.target().unwrap();
+        assert_eq!(research.progress(next), 1);
+        let completed: Vec<ResearchCompleted> = world
+            .resource_mut::<Messages<ResearchCompleted>>()
+            .drain()
+            .collect();
+        assert_eq!(completed, vec![ResearchCompleted(TechnologyId::Ammunition)]);
+
+        // without science packs the labs wait
+        assert!(
+            world
+                .query::<&Lab>()
+                .iter(&world)
+                .all(|lab| lab.technology.is_none())
+        );
+    }
+}
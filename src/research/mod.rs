@@ -0,0 +1,3 @@
+pub mod lab;
+pub mod technology;
+pub mod ui;
@@ -0,0 +1,229 @@
+use crate::{
+    items::{ItemType, recipe::RecipeId},
+    map::modules::MachineTier,
+    save::{SavedStructure, SavedStructureKind},
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TechnologyId {
+    Ammunition,
+    Turrets,
+    Logistics,
+    AdvancedMachines,
+    Modules,
+    AdvancedModules,
+}
+impl TechnologyId {
+    /// in the order the labs pick them when no research is chosen
+    pub const ALL: [TechnologyId; 6] = [
+        TechnologyId::Ammunition,
+        TechnologyId::Turrets,
+        TechnologyId::Logistics,
+        TechnologyId::AdvancedMachines,
+        TechnologyId::Modules,
+        TechnologyId::AdvancedModules,
+    ];
+
+    pub fn technology(&self) -> Technology {
+        match self {
+            TechnologyId::Ammunition => Technology {
+                name: "Ammunition",
+                cost: 10,
+                prerequisites: &[],
+                unlocks: &[Unlock::Recipe(RecipeId::IronPlateToAmmo)],
+            },
+            TechnologyId::Turrets => Technology {
+                name: "Turrets",
+                cost: 20,
+                prerequisites: &[TechnologyId::Ammunition],
+                unlocks: &[Unlock::Structure(StructureType::Turret)],
+            },
+            TechnologyId::Logistics => Technology {
+                name: "Logistics",
+                cost: 30,
+                prerequisites: &[],
+                unlocks: &[Unlock::Structure(StructureType::LogisticsChest)],
+            },
+            TechnologyId::AdvancedMachines => Technology {
+                name: "Advanced machines",
+                cost: 40,
+                prerequisites: &[TechnologyId::Logistics],
+                unlocks: &[Unlock::Structure(StructureType::AdvancedMachine)],
+            },
+            TechnologyId::Modules => Technology {
+                name: "Modules",
+                cost: 40,
+                prerequisites: &[TechnologyId::AdvancedMachines],
+                unlocks: &[
+                    Unlock::Recipe(RecipeId::IronGearAndCopperPlateToSpeedModule),
+                    Unlock::Recipe(RecipeId::IronPlateAndCopperPlateToEfficiencyModule),
+                    Unlock::Module(ItemType::SpeedModule),
+                    Unlock::Module(ItemType::EfficiencyModule),
+                ],
+            },
+            TechnologyId::AdvancedModules => Technology {
+                name: "Advanced modules",
+                cost: 80,
+                prerequisites: &[TechnologyId::Modules],
+                unlocks: &[
+                    Unlock::Recipe(RecipeId::SpeedModuleAndSciencePackToProductivityModule),
+                    Unlock::Recipe(RecipeId::EfficiencyModuleAndSciencePackToQualityModule),
+                    Unlock::Module(ItemType::ProductivityModule),
+                    Unlock::Module(ItemType::QualityModule),
+                ],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Technology {
+    pub name: &'static str,
+    /// science packs consumed by the labs
+    pub cost: u32,
+    pub prerequisites: &'static [TechnologyId],
+    pub unlocks: &'static [Unlock],
+}
+
+/// structures the workers only build once their technology is researched, the others are always available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StructureType {
+    Turret,
+    /// provider, requester and storage chests
+    LogisticsChest,
+    /// machines of MachineTier Mk2 and Mk3
+    AdvancedMachine,
+}
+impl StructureType {
+    pub fn of(blueprint: &SavedStructure) -> Option<Self> {
+        if matches!(blueprint.kind, SavedStructureKind::Turret { .. }) {
+            Some(StructureType::Turret)
+        } else if blueprint.logistics_chest.is_some() {
+            Some(StructureType::LogisticsChest)
+        } else if blueprint
+            .machine
+            .as_ref()
+            .is_some_and(|machine| machine.tier != MachineTier::Mk1)
+        {
+            Some(StructureType::AdvancedMachine)
+        } else {
+            None
+        }
+    }
+}
+
+/// what a technology gives access to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unlock {
+    /// the crafting machines start this recipe
+    Recipe(RecipeId),
+    /// the ghosts of this structure are built
+    Structure(StructureType),
+    /// this module counts in the MachineModifiers of the machines holding it
+    Module(ItemType),
+}
+
+/// progress of the tech tree, saved with the world
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Research {
+    pub researched: BTreeSet<TechnologyId>,
+    /// chosen in the tech tree, the labs work on the first available technology when None
+    pub current: Option<TechnologyId>,
+    /// science packs already consumed by the unfinished technologies
+    pub progress: BTreeMap<TechnologyId, u32>,
+}
+impl Research {
+    pub fn is_researched(&self, technology_id: TechnologyId) -> bool {
+        self.researched.contains(&technology_id)
+    }
+
+    /// not researched yet, and every prerequisite is
+    pub fn is_available(&self, technology_id: TechnologyId) -> bool {
+        !self.is_researched(technology_id)
+            && technology_id
+                .technology()
+                .prerequisites
+                .iter()
+                .all(|prerequisite| self.is_researched(*prerequisite))
+    }
+
+    /// true when no technology gates it, or one of them is researched
+    pub fn is_unlocked(&self, unlock: Unlock) -> bool {
+        let mut gates = TechnologyId::ALL
+            .into_iter()
+            .filter(|technology_id| technology_id.technology().unlocks.contains(&unlock))
+            .peekable();
+        gates.peek().is_none() || gates.any(|technology_id| self.is_researched(technology_id))
+    }
+
+    pub fn progress(&self, technology_id: TechnologyId) -> u32 {
+        self.progress
+            .get(&technology_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// the technology the labs work on
+    pub fn target(&self) -> Option<TechnologyId> {
+        self.current
+            .filter(|current| self.is_available(*current))
+            .or_else(|| {
+                TechnologyId::ALL
+                    .into_iter()
+                    .find(|technology_id| self.is_available(*technology_id))
+            })
+    }
+
+    /// adds the science pack of a lab to a technology, returns true when it completes it
+    pub fn add_progress(&mut self, technology_id: TechnologyId) -> bool {
+        if !self.is_available(technology_id) {
+            return false;
+        }
+        let progress = self.progress.entry(technology_id).or_default();
+        *progress += 1;
+        if *progress < technology_id.technology().cost {
+            return false;
+        }
+        self.progress.remove(&technology_id);
+        self.researched.insert(technology_id);
+        if self.current == Some(technology_id) {
+            self.current = None;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_technologies_need_their_prerequisites_and_unlock_when_researched() {
+        let mut research = Research::default();
+        let ammo = Unlock::Recipe(RecipeId::IronPlateToAmmo);
+        assert!(!research.is_unlocked(ammo));
+        assert!(research.is_unlocked(Unlock::Recipe(RecipeId::IronPlateToIronGear)));
+        assert!(!research.is_available(TechnologyId::Turrets));
+        assert_eq!(research.target(), Some(TechnologyId::Ammunition));
+
+        research.current = Some(TechnologyId::Logistics);
+        assert_eq!(research.target(), Some(TechnologyId::Logistics));
+        // a technology without its prerequisites is not researched even when chosen
+        research.current = Some(TechnologyId::Turrets);
+        assert_eq!(research.target(), Some(TechnologyId::Ammunition));
+
+        let cost = TechnologyId::Ammunition.technology().cost;
+        for _ in 1..cost {
+            assert!(!research.add_progress(TechnologyId::Ammunition));
+        }
+        assert!(research.add_progress(TechnologyId::Ammunition));
+        assert!(research.is_unlocked(ammo));
+        assert!(research.progress.is_empty());
+        // once researched the packs are not counted anymore
+        assert!(!research.add_progress(TechnologyId::Ammunition));
+        assert_eq!(research.target(), Some(TechnologyId::Turrets));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::research::{
+    lab::ResearchCompleted,
+    technology::{Research, TechnologyId, Unlock},
+};
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// opens or closes the tech tree
+pub const TECH_TREE_KEY: KeyCode = KeyCode::KeyT;
+/// real seconds the notice of the last completed technologies stays on screen
+const RESEARCH_NOTICE_SECONDS: f64 = 5.0;
+
+/// egui view of the technologies, where the player chooses what the labs research
+pub struct ResearchUiPlugin;
+
+impl Plugin for ResearchUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TechTreeOpen>()
+            .init_resource::<ResearchNotice>()
+            .add_systems(Update, toggle_tech_tree_system)
+            .add_systems(
+                EguiPrimaryContextPass,
+                (tech_tree_window_system, research_notice_system),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct TechTreeOpen(pub bool);
+
+/// technologies completed during the last RESEARCH_NOTICE_SECONDS
+#[derive(Resource, Debug, Default)]
+pub struct ResearchNotice {
+    pub completed: Vec<TechnologyId>,
+    shown_until_secs: f64,
+}
+
+pub fn toggle_tech_tree_system(
+    input: Res<ButtonInput<KeyCode>>,
+    mut tech_tree_open: ResMut<TechTreeOpen>,
+) {
+    if input.just_pressed(TECH_TREE_KEY) {
+        tech_tree_open.0 = !tech_tree_open.0;
+    }
+}
+
+/// number of technologies between a technology and the root of the tree, its column in the view
+fn depth(technology_id: TechnologyId) -> usize {
+    technology_id
+        .technology()
+        .prerequisites
+        .iter()
+        .map(|prerequisite| depth(*prerequisite) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+fn unlock_label(unlock: &Unlock) -> String {
+    match unlock {
+        Unlock::Recipe(recipe_id) => format!("recipe {recipe_id:?}"),
+        Unlock::Structure(structure_type) => format!("structure {structure_type:?}"),
+        Unlock::Module(item_type) => format!("module {item_type:?}"),
+    }
+}
+
+pub fn tech_tree_window_system(
+    mut contexts: EguiContexts,
+    mut tech_tree_open: ResMut<TechTreeOpen>,
+    mut research: ResMut<Research>,
+) -> Result {
+    if !tech_tree_open.0 {
+        return Ok(());
+    }
+    let target = research.target();
+    let columns = TechnologyId::ALL.into_iter().map(depth).max().unwrap_or(0) + 1;
+
+    let mut picked = None;
+    egui::Window::new("Tech tree")
+        .open(&mut tech_tree_open.0)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.columns(columns, |columns| {
+                for technology_id in TechnologyId::ALL {
+                    let technology = technology_id.technology();
+                    let ui = &mut columns[depth(technology_id)];
+                    ui.group(|ui| {
+                        ui.strong(technology.name);
+                        if research.is_researched(technology_id) {
+                            ui.label("Researched");
+                        } else {
+                            let progress = research.progress(technology_id);
+                            ui.add(
+                                egui::ProgressBar::new(progress as f32 / technology.cost as f32)
+                                    .text(format!(
+                                        "{progress} / {} science packs",
+                                        technology.cost
+                                    )),
+                            );
+                        }
+                        for prerequisite in technology.prerequisites {
+                            ui.small(format!("needs {}", prerequisite.technology().name));
+                        }
+                        for unlock in technology.unlocks {
+                            ui.small(unlock_label(unlock));
+                        }
+                        if target == Some(technology_id) {
+                            ui.label("In progress");
+                        } else if research.is_available(technology_id)
+                            && ui.button("Research").clicked()
+                        {
+                            picked = Some(technology_id);
+                        }
+                    });
+                }
+            });
+        });
+
+    if picked.is_some() {
+        research.current = picked;
+    }
+    Ok(())
+}
+
+pub fn research_notice_system(
+    mut contexts: EguiContexts,
+    mut research_notice: ResMut<ResearchNotice>,
+    mut message_research_completed: MessageReader<ResearchCompleted>,
+    time: Res<Time<Real>>,
+) -> Result {
+    let now = time.elapsed_secs_f64();
+    for ResearchCompleted(technology_id) in message_research_completed.read() {
+        research_notice.completed.push(*technology_id);
+        research_notice.shown_until_secs = now + RESEARCH_NOTICE_SECONDS;
+    }
+    if now >= research_notice.shown_until_secs {
+        research_notice.completed.clear();
+        return Ok(());
+    }
+
+    egui::Area::new(egui::Id::new("research_notice"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 16.0))
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for technology_id in &research_notice.completed {
+                    ui.strong(format!(
+                        "Research completed: {}",
+                        technology_id.technology().name
+                    ));
+                }
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,859 @@
+use crate::{
+    dialogue::ui::Dialogue,
+    items::{
+        inventory::{
+            ChestInventory, FuelInventory, InputInventory, Inventory, ItemStack, ModuleInventory,
+            OutputInventory,
+        },
+        recipe::RecipeId,
+    },
+    logistics::network::LogisticsChest,
+    map::{
+        Chest, ChunkCoordinates, Coordinates, Footprint, LocalTileCoordinates, MapManager, MapSeed,
+        STRUCTURE_LAYER, Source, SourceLayerManager, SpritePath, Structure, StructureLayerManager,
+        TileCoordinates, Wall, absolute_coord_to_coord, absolute_coord_to_tile_coord,
+        ghost::{Ghost, spawn_ghost},
+        machine::{
+            BeltMachine, Burner, CraftingMachine, FurnaceMachine, Machine, MachineBaseBundle,
+            MiningMachine,
+        },
+        modules::MachineTier,
+        ports::Ports,
+        spawn_chest, spawn_chunk_entity, spawn_source, spawn_wall, tile_coord_to_chunk_coord,
+        tile_coord_to_local_tile_coord,
+    },
+    research::{
+        lab::Lab,
+        technology::{Research, TechnologyId},
+    },
+    simulation::SimulationTick,
+    units::{
+        Direction, Player, Speed, Unit,
+        combat::{Attack, Health, Hostile},
+        emotions::Emotions,
+        nests::{Nest, spawn_nest},
+        pathfinding::{Goal, GoalTile},
+        spawn_unit,
+        turrets::Turret,
+        worker::{Flying, Worker},
+    },
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+/// bumped each time the save format changes, older saves are refused
+pub const SAVE_VERSION: u32 = 1;
+pub const QUICK_SAVE_PATH: &str = "saves/quicksave.ron";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    Version { found: u32 },
+}
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "can't access the save file: {error}"),
+            SaveError::Serialize(error) => write!(f, "can't write the save: {error}"),
+            SaveError::Deserialize(error) => write!(f, "can't read the save: {error}"),
+            SaveError::Version { found } => write!(
+                f,
+                "save version {found} is not supported (expected {SAVE_VERSION})"
+            ),
+        }
+    }
+}
+impl std::error::Error for SaveError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SavedStructureKind {
+    Wall,
+    Belt,
+    Crafting { recipe_id: Option<RecipeId> },
+    Mining { mined_item: Option<ItemStack> },
+    Furnace { current_recipe_id: Option<RecipeId> },
+    Chest,
+    Nest { wave_progress: f32 },
+    Turret { turret: Turret },
+    Lab { technology: Option<TechnologyId> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMachine {
+    pub name: String,
+    pub tier: MachineTier,
+    pub action_time_ticks: u64,
+    pub action_progress_ticks: u64,
+    pub productivity_progress: f32,
+    pub quality_progress: f32,
+    pub remaining_burn_ticks: Option<f32>,
+    pub input_inventory: Option<Inventory>,
+    pub output_inventory: Option<Inventory>,
+    pub fuel_inventory: Option<Inventory>,
+    pub module_inventory: Inventory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedStructure {
+    pub kind: SavedStructureKind,
+    pub anchor: TileCoordinates,
+    pub direction: Direction,
+    pub footprint: Footprint,
+    pub sprite: Option<String>,
+    pub machine: Option<SavedMachine>,
+    /// items of a chest
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+    #[serde(default)]
+    pub logistics_chest: Option<LogisticsChest>,
+    #[serde(default)]
+    pub health: Option<Health>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGhost {
+    pub blueprint: SavedStructure,
+    pub missing: Vec<ItemStack>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSource {
+    pub tile: TileCoordinates,
+    pub item_stack: ItemStack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedUnit {
+    pub name: String,
+    pub coordinates: Coordinates,
+    pub direction: Direction,
+    pub speed: f32,
+    pub is_player: bool,
+    /// first template of the dialogue of the unit
+    #[serde(default)]
+    pub dialogue: Option<String>,
+    #[serde(default)]
+    pub emotions: Option<Emotions>,
+    /// entity goals are saved as the tile they lead to
+    #[serde(default)]
+    pub goal: Goal,
+    #[serde(default)]
+    pub is_worker: bool,
+    #[serde(default)]
+    pub is_flying: bool,
+    #[serde(default)]
+    pub health: Option<Health>,
+    /// hostile units lose their target with the save and look for it again
+    #[serde(default)]
+    pub hostile: Option<Hostile>,
+    #[serde(default)]
+    pub attack: Option<Attack>,
+    /// items carried by a worker, its job is not saved and it stores them in a chest after the load
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+}
+
+/// everything needed to rebuild the simulation, chunks that are not saved are generated again from the seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    pub tick: u64,
+    pub chunks: Vec<ChunkCoordinates>,
+    pub structures: Vec<SavedStructure>,
+    pub sources: Vec<SavedSource>,
+    pub units: Vec<SavedUnit>,
+    #[serde(default)]
+    pub ghosts: Vec<SavedGhost>,
+    /// polluted chunks, sorted by chunk
+    #[serde(default)]
+    pub pollution: Vec<(ChunkCoordinates, f32)>,
+    #[serde(default)]
+    pub research: Research,
+}
+
+pub fn read_save(path: &Path) -> Result<SaveGame, SaveError> {
+    let content = fs::read_to_string(path).map_err(SaveError::Io)?;
+    let save: SaveGame = ron::from_str(&content).map_err(SaveError::Deserialize)?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version {
+            found: save.version,
+        });
+    }
+    Ok(save)
+}
+
+pub fn write_save(path: &Path, save: &SaveGame) -> Result<(), SaveError> {
+    let content = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Serialize)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(SaveError::Io)?;
+    }
+    fs::write(path, content).map_err(SaveError::Io)
+}
+
+/// snapshots the world, entries are sorted by position so the same world always gives the same file
+pub fn create_save(world: &mut World) -> SaveGame {
+    let mut structures = Vec::new();
+    let mut structure_query = world.query_filtered::<EntityRef, With<Structure>>();
+    for entity in structure_query.iter(world) {
+        if let Some(structure) = save_structure(entity) {
+            structures.push(structure);
+        }
+    }
+    structures.sort_by_key(|structure| (structure.anchor.y, structure.anchor.x));
+
+    let mut sources: Vec<SavedSource> = world
+        .query::<(&Source, &Transform)>()
+        .iter(world)
+        .map(|(source, transform)| SavedSource {
+            tile: absolute_coord_to_tile_coord((*transform).into()),
+            item_stack: source.0,
+        })
+        .collect();
+    sources.sort_by_key(|source| (source.tile.y, source.tile.x));
+
+    let mut units: Vec<SavedUnit> = world
+        .query_filtered::<(
+            &Name,
+            &Transform,
+            &Direction,
+            &Speed,
+            Has<Player>,
+            Option<&Dialogue>,
+            Option<&Emotions>,
+            &Goal,
+            &GoalTile,
+            Has<Worker>,
+            Has<Flying>,
+            Option<&Inventory>,
+            Option<&Health>,
+            Option<&Hostile>,
+            Option<&Attack>,
+        ), With<Unit>>()
+        .iter(world)
+        .map(
+            |(
+                name,
+                transform,
+                direction,
+                speed,
+                is_player,
+                dialogue,
+                emotions,
+                goal,
+                goal_tile,
+                is_worker,
+                is_flying,
+                inventory,
+                health,
+                hostile,
+                attack,
+            )| SavedUnit {
+                name: name.to_string(),
+                coordinates: absolute_coord_to_coord((*transform).into()),
+                direction: *direction,
+                speed: speed.0,
+                is_player,
+                dialogue: dialogue.map(|dialogue| dialogue.0.clone()),
+                emotions: emotions.copied(),
+                goal: match goal {
+                    Goal::Entity(_) => goal_tile.0.map_or(Goal::Idle, Goal::Tile),
+                    goal => *goal,
+                },
+                is_worker,
+                is_flying,
+                inventory: inventory.cloned(),
+                health: health.copied(),
+                hostile: hostile.copied(),
+                attack: attack.copied(),
+            },
+        )
+        .collect();
+    units.sort_by(|a, b| {
+        (!a.is_player, &a.name)
+            .cmp(&(!b.is_player, &b.name))
+            .then(a.coordinates.y.total_cmp(&b.coordinates.y))
+            .then(a.coordinates.x.total_cmp(&b.coordinates.x))
+    });
+
+    let mut chunks: Vec<ChunkCoordinates> = world
+        .resource::<MapManager>()
+        .chunks
+        .keys()
+        .copied()
+        .collect();
+    chunks.sort_by_key(|chunk_coord| (chunk_coord.y, chunk_coord.x));
+
+    let mut ghosts: Vec<SavedGhost> = world
+        .query::<&Ghost>()
+        .iter(world)
+        .map(|ghost| SavedGhost {
+            blueprint: ghost.blueprint.clone(),
+            missing: ghost.missing.clone(),
+        })
+        .collect();
+    ghosts.sort_by_key(|ghost| (ghost.blueprint.anchor.y, ghost.blueprint.anchor.x));
+
+    let mut pollution: Vec<(ChunkCoordinates, f32)> = world
+        .resource::<MapManager>()
+        .pollution
+        .iter()
+        .map(|(chunk_coord, pollution)| (*chunk_coord, *pollution))
+        .collect();
+    pollution.sort_by_key(|(chunk_coord, _)| (chunk_coord.y, chunk_coord.x));
+
+    SaveGame {
+        version: SAVE_VERSION,
+        seed: world.resource::<MapSeed>().0,
+        tick: world.resource::<SimulationTick>().0,
+        chunks,
+        structures,
+        sources,
+        units,
+        ghosts,
+        pollution,
+        research: world.resource::<Research>().clone(),
+    }
+}
+
+fn save_structure(entity: EntityRef) -> Option<SavedStructure> {
+    let transform = entity.get::<Transform>()?;
+    let footprint = *entity.get::<Footprint>()?;
+    let direction = entity
+        .get::<Direction>()
+        .copied()
+        .unwrap_or(Direction::North);
+    let kind = if entity.contains::<Wall>() {
+        SavedStructureKind::Wall
+    } else if entity.contains::<BeltMachine>() {
+        SavedStructureKind::Belt
+    } else if let Some(crafting_machine) = entity.get::<CraftingMachine>() {
+        SavedStructureKind::Crafting {
+            recipe_id: crafting_machine.recipe_id,
+        }
+    } else if let Some(mining_machine) = entity.get::<MiningMachine>() {
+        SavedStructureKind::Mining {
+            mined_item: mining_machine.mined_item,
+        }
+    } else if let Some(furnace_machine) = entity.get::<FurnaceMachine>() {
+        SavedStructureKind::Furnace {
+            current_recipe_id: furnace_machine.current_recipe_id,
+        }
+    } else if entity.contains::<Chest>() {
+        SavedStructureKind::Chest
+    } else if let Some(lab) = entity.get::<Lab>() {
+        SavedStructureKind::Lab {
+            technology: lab.technology,
+        }
+    } else if let Some(turret) = entity.get::<Turret>() {
+        SavedStructureKind::Turret { turret: *turret }
+    } else if let Some(nest) = entity.get::<Nest>() {
+        SavedStructureKind::Nest {
+            wave_progress: nest.wave_progress,
+        }
+    } else {
+        return None;
+    };
+
+    let machine = entity.get::<Machine>().map(|machine| SavedMachine {
+        name: entity
+            .get::<Name>()
+            .map_or_else(String::new, |name| name.to_string()),
+        tier: entity.get::<MachineTier>().copied().unwrap_or_default(),
+        action_time_ticks: machine.action_time_ticks,
+        action_progress_ticks: machine.action_progress_ticks,
+        productivity_progress: machine.productivity_progress,
+        quality_progress: machine.quality_progress,
+        remaining_burn_ticks: entity
+            .get::<Burner>()
+            .map(|burner| burner.remaining_burn_ticks),
+        input_inventory: entity
+            .get::<InputInventory>()
+            .map(|inventory| inventory.0.clone()),
+        output_inventory: entity
+            .get::<OutputInventory>()
+            .map(|inventory| inventory.0.clone()),
+        fuel_inventory: entity
+            .get::<FuelInventory>()
+            .map(|inventory| inventory.0.clone()),
+        module_inventory: entity
+            .get::<ModuleInventory>()
+            .map(|inventory| inventory.0.clone())
+            .unwrap_or_default(),
+    });
+
+    Some(SavedStructure {
+        kind,
+        anchor: footprint.absolute_coord_to_anchor((*transform).into(), direction),
+        direction,
+        footprint,
+        sprite: entity.get::<SpritePath>().map(|sprite| sprite.0.clone()),
+        machine,
+        inventory: entity
+            .get::<ChestInventory>()
+            .map(|inventory| inventory.0.clone()),
+        logistics_chest: entity.get::<LogisticsChest>().cloned(),
+        health: entity.get::<Health>().copied(),
+    })
+}
+
+/// spawns the saved chunks with their structures, sources and units ; must run before any chunk is generated
+pub fn load_save(commands: &mut Commands, map_manager: &mut MapManager, save: &SaveGame) {
+    let mut layer_managers: HashMap<ChunkCoordinates, (StructureLayerManager, SourceLayerManager)> =
+        save.chunks
+            .iter()
+            .map(|chunk_coord| (*chunk_coord, default()))
+            .collect();
+
+    for saved_structure in &save.structures {
+        let structure_entity = spawn_saved_structure(commands, saved_structure);
+        if let Some(health) = saved_structure.health {
+            commands.entity(structure_entity).insert(health);
+        }
+        for tile in saved_structure
+            .footprint
+            .tiles(saved_structure.anchor, saved_structure.direction)
+        {
+            let chunk_coord = tile_coord_to_chunk_coord(tile);
+            let local_tile = tile_coord_to_local_tile_coord(tile, chunk_coord);
+            match layer_managers.get_mut(&chunk_coord) {
+                Some((structure_layer_manager, _)) => {
+                    structure_layer_manager
+                        .structures
+                        .insert(local_tile, structure_entity);
+                }
+                None => map_manager
+                    .pending_structures
+                    .entry(chunk_coord)
+                    .or_default()
+                    .push((local_tile, structure_entity)),
+            }
+        }
+    }
+
+    for saved_source in &save.sources {
+        let chunk_coord = tile_coord_to_chunk_coord(saved_source.tile);
+        let Some((_, source_layer_manager)) = layer_managers.get_mut(&chunk_coord) else {
+            continue;
+        };
+        let source_entity = spawn_source(commands, saved_source.tile, saved_source.item_stack);
+        source_layer_manager.sources.insert(
+            tile_coord_to_local_tile_coord(saved_source.tile, chunk_coord),
+            source_entity,
+        );
+    }
+
+    for chunk_coord in &save.chunks {
+        let Some((structure_layer_manager, source_layer_manager)) =
+            layer_managers.remove(chunk_coord)
+        else {
+            continue;
+        };
+        let structure_tiles: Vec<LocalTileCoordinates> =
+            structure_layer_manager.structures.keys().copied().collect();
+        let chunk_entity = spawn_chunk_entity(
+            commands,
+            *chunk_coord,
+            structure_layer_manager,
+            source_layer_manager,
+        );
+        map_manager.insert_chunk(*chunk_coord, chunk_entity, structure_tiles);
+    }
+
+    for saved_unit in &save.units {
+        let mut unit = spawn_unit(
+            commands,
+            &saved_unit.name,
+            saved_unit.coordinates,
+            Speed(saved_unit.speed),
+        );
+        unit.insert(saved_unit.direction);
+        if saved_unit.is_player {
+            unit.insert(Player);
+        }
+        if let Some(dialogue) = &saved_unit.dialogue {
+            unit.insert(Dialogue(dialogue.clone()));
+        }
+        if let Some(emotions) = saved_unit.emotions {
+            unit.insert(emotions);
+        }
+        unit.insert(saved_unit.goal);
+        if saved_unit.is_worker {
+            unit.insert(Worker);
+        }
+        if saved_unit.is_flying {
+            unit.insert(Flying);
+        }
+        if let Some(health) = saved_unit.health {
+            unit.insert(health);
+        }
+        if let Some(hostile) = saved_unit.hostile {
+            unit.insert(hostile);
+        }
+        if let Some(attack) = saved_unit.attack {
+            unit.insert(attack);
+        }
+        if let Some(inventory) = &saved_unit.inventory {
+            unit.insert(inventory.clone());
+        }
+    }
+
+    map_manager.pollution = save.pollution.iter().copied().collect();
+    commands.insert_resource(save.research.clone());
+
+    for saved_ghost in &save.ghosts {
+        spawn_ghost(
+            commands,
+            saved_ghost.blueprint.clone(),
+            saved_ghost.missing.clone(),
+        );
+    }
+}
+
+pub fn spawn_saved_structure(commands: &mut Commands, saved_structure: &SavedStructure) -> Entity {
+    let Some(saved_machine) = &saved_structure.machine else {
+        return match saved_structure.kind {
+            SavedStructureKind::Chest => {
+                let chest_entity = spawn_chest(
+                    commands,
+                    saved_structure.anchor,
+                    ChestInventory(saved_structure.inventory.clone().unwrap_or_default()),
+                );
+                if let Some(logistics_chest) = &saved_structure.logistics_chest {
+                    commands
+                        .entity(chest_entity)
+                        .insert(logistics_chest.clone());
+                }
+                chest_entity
+            }
+            SavedStructureKind::Nest { wave_progress } => {
+                spawn_nest(commands, saved_structure.anchor, Nest { wave_progress })
+            }
+            _ => spawn_wall(commands, saved_structure.anchor),
+        };
+    };
+
+    let footprint = saved_structure.footprint;
+    let target_coord =
+        footprint.anchor_to_absolute_coord(saved_structure.anchor, saved_structure.direction);
+    let mut entity_commands = commands.spawn((
+        MachineBaseBundle {
+            name: Name::new(saved_machine.name.clone()),
+            structure: Structure,
+            direction: saved_structure.direction,
+            transform: Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+            machine: Machine {
+                action_time_ticks: saved_machine.action_time_ticks,
+                action_speed: 1.0,
+                action_progress_ticks: saved_machine.action_progress_ticks,
+                productivity_progress: saved_machine.productivity_progress,
+                quality_progress: saved_machine.quality_progress,
+                worked_this_tick: false,
+            },
+        },
+        footprint,
+        footprint.collider(),
+        saved_machine.tier,
+        ModuleInventory(saved_machine.module_inventory.clone()),
+    ));
+
+    match saved_structure.kind {
+        SavedStructureKind::Wall | SavedStructureKind::Chest | SavedStructureKind::Nest { .. } => {}
+        SavedStructureKind::Belt => {
+            entity_commands.insert(BeltMachine);
+        }
+        SavedStructureKind::Crafting { recipe_id } => {
+            entity_commands.insert(CraftingMachine { recipe_id });
+        }
+        SavedStructureKind::Mining { mined_item } => {
+            entity_commands.insert(MiningMachine { mined_item });
+        }
+        SavedStructureKind::Furnace { current_recipe_id } => {
+            entity_commands.insert((FurnaceMachine { current_recipe_id }, Ports::furnace()));
+        }
+        SavedStructureKind::Turret { turret } => {
+            entity_commands.insert((turret, Ports::turret()));
+        }
+        SavedStructureKind::Lab { technology } => {
+            entity_commands.insert((Lab { technology }, Ports::lab()));
+        }
+    }
+    if let Some(remaining_burn_ticks) = saved_machine.remaining_burn_ticks {
+        entity_commands.insert(Burner {
+            remaining_burn_ticks,
+        });
+    }
+    if let Some(inventory) = &saved_machine.input_inventory {
+        entity_commands.insert(InputInventory(inventory.clone()));
+    }
+    if let Some(inventory) = &saved_machine.output_inventory {
+        entity_commands.insert(OutputInventory(inventory.clone()));
+    }
+    if let Some(inventory) = &saved_machine.fuel_inventory {
+        entity_commands.insert(FuelInventory(inventory.clone()));
+    }
+    if let Some(sprite) = &saved_structure.sprite {
+        entity_commands.insert(SpritePath(sprite.clone()));
+    }
+    entity_commands.id()
+}
+
+/// F5 writes the world to QUICK_SAVE_PATH, it can be loaded back with `--load`
+pub fn quick_save_system(world: &mut World) {
+    if !world
+        .resource::<ButtonInput<KeyCode>>()
+        .just_pressed(KeyCode::F5)
+    {
+        return;
+    }
+    let save = create_save(world);
+    match write_save(Path::new(QUICK_SAVE_PATH), &save) {
+        Ok(()) => println!("Saved to {QUICK_SAVE_PATH}"),
+        Err(error) => eprintln!("Quick save failed: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{ItemType, Quality},
+        map::machine::{CraftingMachineBundle, MachineBaseBundle},
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    const MACHINE_ANCHOR: TileCoordinates = TileCoordinates { x: 3, y: 3 };
+    const CHEST_TILE: TileCoordinates = TileCoordinates { x: 1, y: 0 };
+
+    /// a chunk with a crafting machine in the middle of an action, a chest and a worker carrying items
+    fn build_world(mut commands: Commands, mut map_manager: ResMut<MapManager>) {
+        let footprint = Footprint::new(2, 2);
+        let target_coord = footprint.anchor_to_absolute_coord(MACHINE_ANCHOR, Direction::East);
+        let mut input_inventory = InputInventory::default();
+        input_inventory
+            .0
+            .add(ItemStack::new(ItemType::IronPlate, Quality::Standard, 3))
+            .unwrap();
+        let mut output_inventory = OutputInventory::default();
+        output_inventory
+            .0
+            .add(ItemStack::new(ItemType::IronGear, Quality::Perfect, 2))
+            .unwrap();
+        let machine_entity = commands
+            .spawn((
+                CraftingMachineBundle {
+                    base: MachineBaseBundle {
+                        name: Name::new("Assembler"),
+                        structure: Structure,
+                        direction: Direction::East,
+                        transform: Transform::from_xyz(
+                            target_coord.x,
+                            target_coord.y,
+                            STRUCTURE_LAYER,
+                        ),
+                        machine: Machine {
+                            action_progress_ticks: 12,
+                            quality_progress: 0.5,
+                            ..default()
+                        },
+                    },
+                    input_inventory,
+                    output_inventory,
+                    crafting_machine: CraftingMachine::new(RecipeId::IronPlateToIronGear),
+                },
+                footprint,
+                MachineTier::Mk2,
+            ))
+            .id();
+
+        let mut chest_inventory = ChestInventory::default();
+        chest_inventory
+            .0
+            .add(ItemStack::new(ItemType::Wood, Quality::Standard, 7))
+            .unwrap();
+        let chest_entity = spawn_chest(&mut commands, CHEST_TILE, chest_inventory);
+
+        let mut carried = Inventory::with_slots(1);
+        carried
+            .add(ItemStack::new(ItemType::Coal, Quality::Standard, 4))
+            .unwrap();
+        spawn_unit(
+            &mut commands,
+            "Robot",
+            Coordinates { x: 6.0, y: 1.0 },
+            Speed(2.0),
+        )
+        .insert((Worker, carried));
+
+        let chunk_coord = ChunkCoordinates { x: 0, y: 0 };
+        let mut structure_layer_manager = StructureLayerManager::default();
+        for (tile, entity) in footprint
+            .tiles(MACHINE_ANCHOR, Direction::East)
+            .into_iter()
+            .map(|tile| (tile, machine_entity))
+            .chain([(CHEST_TILE, chest_entity)])
+        {
+            structure_layer_manager
+                .structures
+                .insert(tile_coord_to_local_tile_coord(tile, chunk_coord), entity);
+        }
+        let structure_tiles: Vec<LocalTileCoordinates> =
+            structure_layer_manager.structures.keys().copied().collect();
+        let chunk_entity = spawn_chunk_entity(
+            &mut commands,
+            chunk_coord,
+            structure_layer_manager,
+            SourceLayerManager::default(),
+        );
+        map_manager.insert_chunk(chunk_coord, chunk_entity, structure_tiles);
+        map_manager.pollution.insert(chunk_coord, 2.5);
+    }
+
+    fn new_app(seed: u64, tick: u64) -> App {
+        let mut app = App::new();
+        app.init_resource::<MapManager>()
+            .init_resource::<Research>()
+            .insert_resource(MapSeed(seed))
+            .insert_resource(SimulationTick(tick));
+        app
+    }
+
+    fn to_ron(save: &SaveGame) -> String {
+        ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let mut app = new_app(7, 42);
+        app.world_mut().run_system_once(build_world).unwrap();
+        app.world_mut()
+            .resource_mut::<Research>()
+            .add_progress(TechnologyId::Ammunition);
+        let save = create_save(app.world_mut());
+        assert_eq!(save.structures.len(), 2);
+        assert_eq!(save.units.len(), 1);
+
+        let loaded_save: SaveGame = ron::from_str(&to_ron(&save)).unwrap();
+        let mut loaded_app = new_app(loaded_save.seed, loaded_save.tick);
+        loaded_app
+            .world_mut()
+            .run_system_once(
+                move |mut commands: Commands, mut map_manager: ResMut<MapManager>| {
+                    load_save(&mut commands, &mut map_manager, &loaded_save)
+                },
+            )
+            .unwrap();
+        let world = loaded_app.world_mut();
+
+        // the loaded world gives back the same save
+        assert_eq!(to_ron(&create_save(world)), to_ron(&save));
+
+        let (machine, crafting_machine, input_inventory, output_inventory, tier) = world
+            .query::<(
+                &Machine,
+                &CraftingMachine,
+                &InputInventory,
+                &OutputInventory,
+                &MachineTier,
+            )>()
+            .single(world)
+            .unwrap();
+        assert_eq!(machine.action_progress_ticks, 12);
+        assert_eq!(machine.quality_progress, 0.5);
+        assert_eq!(
+            crafting_machine.recipe_id,
+            Some(RecipeId::IronPlateToIronGear)
+        );
+        assert_eq!(input_inventory.0.quantity(ItemType::IronPlate), 3);
+        assert_eq!(
+            output_inventory.0.slots,
+            vec![ItemStack::new(ItemType::IronGear, Quality::Perfect, 2)]
+        );
+        assert_eq!(*tier, MachineTier::Mk2);
+
+        let chest_inventory = world
+            .query_filtered::<&ChestInventory, With<Chest>>()
+            .single(world)
+            .unwrap();
+        assert_eq!(chest_inventory.0.quantity(ItemType::Wood), 7);
+
+        let (name, carried, speed) = world
+            .query_filtered::<(&Name, &Inventory, &Speed), With<Worker>>()
+            .single(world)
+            .unwrap();
+        assert_eq!(name.as_str(), "Robot");
+        assert_eq!(carried.quantity(ItemType::Coal), 4);
+        assert_eq!(speed.0, 2.0);
+
+        let map_manager = world.resource::<MapManager>();
+        let chunk_coord = ChunkCoordinates { x: 0, y: 0 };
+        assert_eq!(map_manager.chunks.len(), 1);
+        assert_eq!(map_manager.pollution(chunk_coord), 2.5);
+        assert!(!map_manager.is_tile_walkable(CHEST_TILE));
+        assert!(!map_manager.is_tile_walkable(MACHINE_ANCHOR));
+        assert!(map_manager.is_tile_walkable(TileCoordinates { x: 6, y: 1 }));
+        let chunk_entity = map_manager.chunks[&chunk_coord];
+        assert_eq!(
+            world
+                .get::<StructureLayerManager>(chunk_entity)
+                .unwrap()
+                .structures
+                .len(),
+            5
+        );
+        assert_eq!(
+            world
+                .resource::<Research>()
+                .progress(TechnologyId::Ammunition),
+            1
+        );
+    }
+
+    #[test]
+    fn test_version_1_save_without_the_later_fields_still_loads() {
+        // a save written before the ghosts, pollution, research, combat and logistics fields
+        let content = r#"(
+            version: 1,
+            seed: 7,
+            tick: 42,
+            chunks: [(x: 0, y: 0)],
+            structures: [(
+                kind: Chest,
+                anchor: (x: 1, y: 0),
+                direction: North,
+                footprint: (width: 1, height: 1),
+                sprite: None,
+                machine: None,
+            )],
+            sources: [],
+            units: [(
+                name: "Player",
+                coordinates: (x: 0.0, y: 0.0),
+                direction: South,
+                speed: 1.0,
+                is_player: true,
+            )],
+        )"#;
+        let path = std::env::temp_dir().join("stellar_routine_save_version_1.ron");
+        fs::write(&path, content).unwrap();
+        let save = read_save(&path).unwrap();
+        assert!(save.ghosts.is_empty() && save.pollution.is_empty());
+        assert_eq!(save.research, Research::default());
+        let structure = &save.structures[0];
+        assert!(structure.inventory.is_none() && structure.logistics_chest.is_none());
+        let unit = &save.units[0];
+        assert_eq!(unit.goal, Goal::default());
+        assert!(!unit.is_worker && unit.health.is_none() && unit.inventory.is_none());
+
+        fs::write(&path, content.replace("version: 1", "version: 2")).unwrap();
+        assert!(matches!(
+            read_save(&path),
+            Err(SaveError::Version { found: 2 })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
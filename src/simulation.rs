@@ -0,0 +1,330 @@
+use crate::{
+    DAY_DURATION, UPS_TARGET,
+    dialogue::ui::Dialogue,
+    environment::{
+        day_night::{DayNightPlugin, WorldClock},
+        weather::WeatherPlugin,
+    },
+    items::{recipe::RecipeBook, statistics::ProductionStatistics},
+    logistics::jobs::JobsPlugin,
+    map::{
+        Coordinates, MapManager, MapPlugin, MapSeed, coord_to_tile_coord, machine::MachinePlugin,
+        pollution::PollutionPlugin,
+    },
+    research::lab::ResearchPlugin,
+    save::{SaveError, SaveGame, create_save, load_save, write_save},
+    units::{
+        Player, Speed, UNIT_DEFAULT_MOVEMENT_SPEED, UnitsPlugin,
+        combat::{Attack, CombatPlugin, Hostile},
+        emotions::EmotionsPlugin,
+        nests::NestsPlugin,
+        pathfinding::{Goal, PathfindingPlugin},
+        spawn_unit,
+        turrets::TurretsPlugin,
+        worker::{Flying, Worker},
+    },
+};
+use avian2d::prelude::*;
+use bevy::{
+    input::InputPlugin, mesh::MeshPlugin, prelude::*, scene::ScenePlugin, time::TimeUpdateStrategy,
+};
+use std::{path::PathBuf, time::Instant};
+
+pub const LENGTH_UNIT: f32 = 16.0;
+/// distance in tiles at which the monsters of the spawn notice a target
+const MONSTER_AGGRO_RADIUS: f32 = 8.0;
+pub const DEFAULT_HEADLESS_TICKS: u64 = DAY_DURATION as u64;
+pub const USAGE: &str =
+    "usage: stellar-routine-rust [--headless] [--ticks N] [--seed S] [--load PATH] [--save PATH] [--csv PATH]
+  --headless    run the simulation without window nor rendering, as fast as possible
+  --ticks N     number of ticks simulated in headless mode (default: one day)
+  --seed S      seed of the map generation (default: random, ignored when loading a save)
+  --load PATH   starts from a save file
+  --save PATH   writes a save file at the end of a headless run
+  --csv PATH    writes the production statistics as CSV at the end of a headless run";
+
+/// the whole factory logic, shared by the windowed game and the headless simulation
+pub struct SimulationPlugin {
+    pub seed: u64,
+    pub save: Option<SaveGame>,
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = self.save.as_ref().map_or(self.seed, |save| save.seed);
+        app.add_plugins(PhysicsPlugins::default().with_length_unit(LENGTH_UNIT))
+            .add_plugins(UnitsPlugin)
+            .add_plugins(EmotionsPlugin)
+            .add_plugins(MapPlugin)
+            .add_plugins(PathfindingPlugin)
+            .add_plugins(MachinePlugin)
+            .add_plugins(JobsPlugin)
+            .add_plugins(CombatPlugin)
+            .add_plugins(NestsPlugin)
+            .add_plugins(TurretsPlugin)
+            .add_plugins(PollutionPlugin)
+            .add_plugins(ResearchPlugin)
+            .add_plugins(DayNightPlugin)
+            .add_plugins(WeatherPlugin)
+            .insert_resource(Gravity(Vec2::ZERO))
+            .insert_resource(RecipeBook::default())
+            .insert_resource(Time::<Fixed>::from_hz(UPS_TARGET as f64))
+            .insert_resource(MapSeed(seed))
+            .init_resource::<SimulationTick>()
+            .add_systems(Startup, setup_world_system)
+            .add_systems(FixedFirst, advance_simulation_tick_system);
+        if let Some(save) = &self.save {
+            app.insert_resource(PendingSave(save.clone()));
+        }
+    }
+}
+
+/// number of fixed updates since the world was created, saved with the world
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct SimulationTick(pub u64);
+
+/// present when the simulation runs without rendering
+#[derive(Resource)]
+pub struct Headless;
+
+/// save given on the command line, loaded by setup_world_system
+#[derive(Resource)]
+struct PendingSave(SaveGame);
+
+fn setup_world_system(
+    mut commands: Commands,
+    mut map_manager: ResMut<MapManager>,
+    mut simulation_tick: ResMut<SimulationTick>,
+    pending_save: Option<Res<PendingSave>>,
+) {
+    if let Some(pending_save) = pending_save {
+        load_save(&mut commands, &mut map_manager, &pending_save.0);
+        simulation_tick.0 = pending_save.0.tick;
+        // else the first update of the clock would see the phase of the save as a change
+        commands.insert_resource(WorldClock::from_tick(pending_save.0.tick));
+        commands.remove_resource::<PendingSave>();
+        return;
+    }
+
+    spawn_unit(
+        &mut commands,
+        "Player",
+        Coordinates { x: 0.0, y: 0.0 },
+        Speed(UNIT_DEFAULT_MOVEMENT_SPEED),
+    )
+    .insert(Player);
+    spawn_unit(
+        &mut commands,
+        "Monstre",
+        Coordinates { x: 5.0, y: 5.0 },
+        Speed(UNIT_DEFAULT_MOVEMENT_SPEED * 20.0),
+    )
+    .insert((
+        Dialogue("dialogues/npc_greetings.tera".to_owned()),
+        Goal::FollowPlayer,
+        Hostile::new(MONSTER_AGGRO_RADIUS),
+        Attack::melee(10.0, UPS_TARGET as u64),
+    ));
+    spawn_unit(
+        &mut commands,
+        "Cracheur",
+        Coordinates { x: -8.0, y: 8.0 },
+        Speed(UNIT_DEFAULT_MOVEMENT_SPEED),
+    )
+    .insert((
+        Goal::Wander {
+            center: coord_to_tile_coord(Coordinates { x: -8.0, y: 8.0 }),
+            radius: 4,
+            point: None,
+        },
+        Hostile::new(MONSTER_AGGRO_RADIUS),
+        Attack::ranged(5.0, 5.0, 2 * UPS_TARGET as u64),
+    ));
+    for y in [1.0, 2.0] {
+        spawn_unit(
+            &mut commands,
+            "Robot",
+            Coordinates { x: 2.0, y },
+            Speed(UNIT_DEFAULT_MOVEMENT_SPEED),
+        )
+        .insert(Worker);
+    }
+    spawn_unit(
+        &mut commands,
+        "Drone",
+        Coordinates { x: 3.0, y: 1.0 },
+        Speed(UNIT_DEFAULT_MOVEMENT_SPEED),
+    )
+    .insert((Worker, Flying));
+}
+
+fn advance_simulation_tick_system(mut simulation_tick: ResMut<SimulationTick>) {
+    simulation_tick.0 += 1;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationOptions {
+    pub help: bool,
+    pub headless: bool,
+    pub ticks: u64,
+    pub seed: Option<u64>,
+    pub load: Option<PathBuf>,
+    pub save: Option<PathBuf>,
+    pub csv: Option<PathBuf>,
+}
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            help: false,
+            headless: false,
+            ticks: DEFAULT_HEADLESS_TICKS,
+            seed: None,
+            load: None,
+            save: None,
+            csv: None,
+        }
+    }
+}
+impl SimulationOptions {
+    /// parses the command line arguments, without the name of the program
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value after {arg}"));
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--headless" => options.headless = true,
+                "--ticks" => {
+                    options.ticks = value()?
+                        .parse()
+                        .map_err(|_| "--ticks expects a number".to_string())?
+                }
+                "--seed" => {
+                    options.seed = Some(
+                        value()?
+                            .parse()
+                            .map_err(|_| "--seed expects a number".to_string())?,
+                    )
+                }
+                "--load" => options.load = Some(value()?.into()),
+                "--save" => options.save = Some(value()?.into()),
+                "--csv" => options.csv = Some(value()?.into()),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// runs `options.ticks` fixed updates as fast as possible, then prints the production statistics
+pub fn run_headless(
+    options: &SimulationOptions,
+    seed: u64,
+    save: Option<SaveGame>,
+) -> Result<(), SaveError> {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        // no rendering, but the input, transforms and assets resources are still read by the logic and physics
+        .add_plugins((
+            TransformPlugin,
+            InputPlugin,
+            AssetPlugin::default(),
+            MeshPlugin,
+            ScenePlugin,
+        ))
+        .insert_resource(Headless)
+        .add_plugins(SimulationPlugin { seed, save });
+    // each update advances the time by exactly one tick instead of following the clock
+    let timestep = Time::<Fixed>::from_hz(UPS_TARGET as f64).timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+    app.finish();
+    app.cleanup();
+
+    // the first update only runs the startup schedules
+    app.update();
+    let start_tick = app.world().resource::<SimulationTick>().0;
+    let end_tick = start_tick + options.ticks;
+    println!(
+        "Headless simulation: seed {} | ticks {} to {}",
+        app.world().resource::<MapSeed>().0,
+        start_tick,
+        end_tick
+    );
+
+    let started = Instant::now();
+    while app.world().resource::<SimulationTick>().0 < end_tick {
+        app.update();
+    }
+    let elapsed_secs = started.elapsed().as_secs_f64();
+
+    println!(
+        "{} ticks in {:.2}s | {:.0} UPS (target {})",
+        options.ticks,
+        elapsed_secs,
+        options.ticks as f64 / elapsed_secs.max(f64::EPSILON),
+        UPS_TARGET
+    );
+    print!(
+        "{}",
+        app.world()
+            .resource::<ProductionStatistics>()
+            .report(options.ticks)
+    );
+
+    if let Some(path) = &options.save {
+        write_save(path, &create_save(app.world_mut()))?;
+        println!("Saved to {}", path.display());
+    }
+    if let Some(path) = &options.csv {
+        match app
+            .world()
+            .resource::<ProductionStatistics>()
+            .write_csv(path)
+        {
+            Ok(()) => println!("Statistics exported to {}", path.display()),
+            Err(error) => eprintln!("Statistics export failed: {error}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_headless_options() {
+        let options = SimulationOptions::from_args(args(&[
+            "--headless",
+            "--ticks",
+            "300",
+            "--seed",
+            "42",
+            "--load",
+            "saves/a.ron",
+            "--csv",
+            "stats.csv",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options,
+            SimulationOptions {
+                headless: true,
+                ticks: 300,
+                seed: Some(42),
+                load: Some("saves/a.ron".into()),
+                csv: Some("stats.csv".into()),
+                ..default()
+            }
+        );
+
+        assert!(SimulationOptions::from_args(args(&["--ticks"])).is_err());
+        assert!(SimulationOptions::from_args(args(&["--ticks", "many"])).is_err());
+        assert!(SimulationOptions::from_args(args(&["--fast"])).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{
+    UPS_TARGET,
+    camera::UpsCounter,
+    environment::{day_night::WorldClock, weather::Weather},
+};
+use bevy::{
+    app::{FixedMain, RunFixedMainLoopSystems},
+    prelude::*,
+};
+use std::time::Duration;
+
+/// speed multipliers available with the faster / slower keys
+pub const SIMULATION_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED_INDEX: usize = 2;
+/// maximum number of ticks simulated in one frame, when a frame takes too long the simulation slows down instead of trying to catch up
+pub const MAX_CATCH_UP_TICKS: u32 = 16;
+
+/// pause, single tick steps and fast-forward of the simulation
+/// the speed is applied to Time<Virtual>, which feeds Time<Fixed> : the timestep itself never changes so every tick simulates the same duration
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeState>()
+            .add_systems(Startup, spawn_time_indicator_system)
+            .add_systems(PreUpdate, control_time_system)
+            .add_systems(
+                RunFixedMainLoop,
+                step_simulation_system.in_set(RunFixedMainLoopSystems::AfterFixedMainLoop),
+            )
+            .add_systems(Update, update_time_indicator_system);
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TimeState {
+    pub is_paused: bool,
+    pub speed_index: usize,
+    /// ticks to run while paused, one per frame
+    pub pending_steps: u32,
+}
+impl TimeState {
+    pub fn speed(&self) -> f64 {
+        SIMULATION_SPEEDS[self.speed_index]
+    }
+
+    /// number of ticks per second the simulation should reach
+    pub fn target_ups(&self) -> f64 {
+        if self.is_paused {
+            0.0
+        } else {
+            UPS_TARGET as f64 * self.speed()
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(SIMULATION_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    /// virtual time allowed in one frame so that at most MAX_CATCH_UP_TICKS ticks are run
+    pub fn max_delta(&self, timestep: Duration) -> Duration {
+        timestep.mul_f64(MAX_CATCH_UP_TICKS as f64 / self.speed())
+    }
+}
+impl Default for TimeState {
+    fn default() -> Self {
+        Self {
+            is_paused: false,
+            speed_index: NORMAL_SPEED_INDEX,
+            pending_steps: 0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TimeIndicator;
+
+pub fn control_time_system(
+    input: Res<ButtonInput<KeyCode>>,
+    mut time_state: ResMut<TimeState>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    fixed_time: Res<Time<Fixed>>,
+    mut counter: ResMut<UpsCounter>,
+) {
+    // Space pour alterner entre pause et reprise
+    if input.just_pressed(KeyCode::Space) {
+        time_state.is_paused = !time_state.is_paused;
+    }
+    // O pour avancer d'un seul tick pendant la pause
+    if input.just_pressed(KeyCode::KeyO) && time_state.is_paused {
+        time_state.pending_steps += 1;
+    }
+    // Y accélère, U ralentit, I revient à la vitesse normale
+    if input.just_pressed(KeyCode::KeyY) {
+        time_state.faster();
+    }
+    if input.just_pressed(KeyCode::KeyU) {
+        time_state.slower();
+    }
+    if input.just_pressed(KeyCode::KeyI) {
+        time_state.speed_index = NORMAL_SPEED_INDEX;
+    }
+
+    if !time_state.is_changed() {
+        return;
+    }
+    if time_state.is_paused {
+        virtual_time.pause();
+    } else {
+        virtual_time.unpause();
+    }
+    virtual_time.set_relative_speed_f64(time_state.speed());
+    virtual_time.set_max_delta(time_state.max_delta(fixed_time.timestep()));
+    counter.target_ups = time_state.target_ups();
+}
+
+/// runs FixedMain once for a requested step, the virtual clock stays paused
+pub fn step_simulation_system(world: &mut World) {
+    let mut time_state = world.resource_mut::<TimeState>();
+    if !time_state.is_paused || time_state.pending_steps == 0 {
+        return;
+    }
+    time_state.pending_steps -= 1;
+
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+fn spawn_time_indicator_system(mut commands: Commands) {
+    commands.spawn((
+        TimeIndicator,
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            ..default()
+        },
+    ));
+}
+
+pub fn update_time_indicator_system(
+    time_state: Res<TimeState>,
+    counter: Res<UpsCounter>,
+    world_clock: Res<WorldClock>,
+    weather: Res<Weather>,
+    mut indicator_query: Query<&mut Text, With<TimeIndicator>>,
+) {
+    let Ok(mut text) = indicator_query.single_mut() else {
+        return;
+    };
+    let speed = if time_state.is_paused {
+        "PAUSE".to_string()
+    } else {
+        format!("x{}", time_state.speed())
+    };
+    let (hours, minutes) = world_clock.hours_minutes();
+    let indicator = format!(
+        "Day {} {:02}:{:02} {} | {} | UPS {}/{:.0}",
+        world_clock.day,
+        hours,
+        minutes,
+        weather.kind.dialogue_name(),
+        speed,
+        counter.ups,
+        counter.target_ups
+    );
+    if text.0 != indicator {
+        text.0 = indicator;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Default)]
+    struct TickCount(u32);
+
+    #[test]
+    fn test_speed_limits() {
+        let mut time_state = TimeState::default();
+        assert_eq!(time_state.target_ups(), UPS_TARGET as f64);
+        for _ in 0..10 {
+            time_state.faster();
+        }
+        assert_eq!(time_state.speed(), 8.0);
+        for _ in 0..10 {
+            time_state.slower();
+        }
+        assert_eq!(time_state.speed(), 0.25);
+        time_state.is_paused = true;
+        assert_eq!(time_state.target_ups(), 0.0);
+    }
+
+    #[test]
+    fn test_max_delta_keeps_the_tick_budget() {
+        let timestep = Time::<Fixed>::from_hz(UPS_TARGET as f64).timestep();
+        let mut time_state = TimeState::default();
+        for _ in 0..SIMULATION_SPEEDS.len() {
+            let ticks = time_state.max_delta(timestep).as_secs_f64() * time_state.speed()
+                / timestep.as_secs_f64();
+            assert!((ticks - MAX_CATCH_UP_TICKS as f64).abs() < 1e-6);
+            time_state.faster();
+        }
+    }
+
+    #[test]
+    fn test_step_runs_one_tick_while_paused() {
+        let mut world = World::new();
+        world.insert_resource(Time::<Fixed>::from_hz(UPS_TARGET as f64));
+        world.insert_resource(Time::<Virtual>::default());
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<TickCount>();
+        let mut fixed_main = Schedule::new(FixedMain);
+        fixed_main.add_systems(|mut count: ResMut<TickCount>| count.0 += 1);
+        world.add_schedule(fixed_main);
+        world.insert_resource(TimeState {
+            is_paused: true,
+            pending_steps: 1,
+            ..default()
+        });
+
+        world.run_system_once(step_simulation_system).unwrap();
+        world.run_system_once(step_simulation_system).unwrap();
+        assert_eq!(world.resource::<TickCount>().0, 1);
+        assert_eq!(world.resource::<TimeState>().pending_steps, 0);
+        assert_eq!(
+            world.resource::<Time<Fixed>>().elapsed(),
+            world.resource::<Time<Fixed>>().timestep()
+        );
+    }
+}
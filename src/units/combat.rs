@@ -0,0 +1,465 @@
+use crate::{
+    map::{
+        AbsoluteCoordinates, Chunk, Coordinates, Footprint, MapManager, Structure,
+        StructureLayerManager, TILE_SIZE, absolute_coord_to_tile_coord, coord_to_absolute_coord,
+    },
+    simulation::SimulationTick,
+    units::{
+        Direction, Player, Unit,
+        emotions::{Emotion, EmotionStimulus},
+        nests::{Nest, Raider},
+        pathfinding::{Goal, has_line_of_sight, resolve_goal_tiles_system},
+    },
+};
+use avian2d::prelude::LinearVelocity;
+use bevy::{ecs::query::QueryData, prelude::*};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_HEALTH: f32 = 100.0;
+/// where the player comes back after its death
+const PLAYER_RESPAWN_COORDINATES: Coordinates = Coordinates { x: 0.0, y: 0.0 };
+/// fear felt by a unit for each point of damage it takes
+const FEAR_PER_DAMAGE: f32 = 0.01;
+/// a hostile unit without a target wanders this far around the place it lost it, in tiles
+const WANDER_RADIUS: i32 = 5;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<Damage>().add_systems(
+            FixedUpdate,
+            (aggro_system, attack_system, apply_damage_system)
+                .chain()
+                // the hostile units head to their new target in the same tick
+                .before(resolve_goal_tiles_system),
+        );
+    }
+}
+
+/// hit points of a unit or a structure, it dies at 0
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// returns true when the damage kills
+    pub fn take(&mut self, damage: f32) -> bool {
+        self.current = (self.current - damage).max(0.0);
+        self.is_dead()
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEALTH)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttackKind {
+    /// hits what it touches
+    Melee,
+    /// hits from afar, but only the units it sees
+    Ranged,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Attack {
+    pub kind: AttackKind,
+    pub damage: f32,
+    /// from the edge of the target, in pixels
+    pub range: f32,
+    pub cooldown_ticks: u64,
+    pub last_attack_tick: Option<u64>,
+}
+impl Attack {
+    pub fn melee(damage: f32, cooldown_ticks: u64) -> Self {
+        Self {
+            kind: AttackKind::Melee,
+            damage,
+            range: TILE_SIZE.x * 0.5,
+            cooldown_ticks,
+            last_attack_tick: None,
+        }
+    }
+
+    pub fn ranged(damage: f32, range_tiles: f32, cooldown_ticks: u64) -> Self {
+        Self {
+            kind: AttackKind::Ranged,
+            damage,
+            range: TILE_SIZE.x * range_tiles,
+            cooldown_ticks,
+            last_attack_tick: None,
+        }
+    }
+
+    pub fn is_ready(&self, tick: u64) -> bool {
+        self.last_attack_tick
+            .is_none_or(|last_attack_tick| tick >= last_attack_tick + self.cooldown_ticks)
+    }
+}
+
+/// unit attacking the units, and the structures when no unit is around
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[require(Health)]
+pub struct Hostile {
+    /// a target closer than this is chased, in tiles
+    pub aggro_radius: f32,
+    /// the target being chased, not saved
+    #[serde(skip)]
+    pub target: Option<Entity>,
+}
+impl Hostile {
+    pub fn new(aggro_radius: f32) -> Self {
+        Self {
+            aggro_radius,
+            target: None,
+        }
+    }
+}
+
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// distance between a point and the edge of a unit or a structure
+fn distance_to_edge(position: Vec2, target: &Transform, footprint: Option<&Footprint>) -> f32 {
+    let half_size = footprint.map_or(TILE_SIZE.x * 0.4, |footprint| {
+        footprint.width.max(footprint.height) as f32 * TILE_SIZE.x / 2.0
+    });
+    (position.distance(target.translation.xy()) - half_size).max(0.0)
+}
+
+/// what the hostile units attack, the nests are on their side
+type Prey = (Without<Hostile>, Without<Nest>);
+
+/// each hostile unit chases the closest unit in its aggro radius, or else the closest structure
+pub fn aggro_system(
+    mut hostile_query: Query<(Entity, &Transform, &mut Hostile, &mut Goal, Option<&Raider>)>,
+    target_query: Query<(Entity, &Transform, &Health, Has<Unit>), Prey>,
+) {
+    for (entity, transform, mut hostile, mut goal, raider) in hostile_query.iter_mut() {
+        let position = transform.translation.xy();
+        let aggro_distance = hostile.aggro_radius * TILE_SIZE.x;
+        let target = target_query
+            .iter()
+            .filter(|(target, ..)| *target != entity)
+            .map(|(target, target_transform, _, is_unit)| {
+                let distance = position.distance(target_transform.translation.xy());
+                (target, distance, is_unit)
+            })
+            .filter(|(_, distance, _)| *distance <= aggro_distance)
+            // the units first, then the closest, then the oldest entity so the choice is stable
+            .min_by(|(a, a_distance, a_is_unit), (b, b_distance, b_is_unit)| {
+                b_is_unit
+                    .cmp(a_is_unit)
+                    .then(a_distance.total_cmp(b_distance))
+                    .then(a.cmp(b))
+            })
+            .map(|(target, ..)| target);
+
+        if target == hostile.target {
+            continue;
+        }
+        hostile.target = target;
+        // a raider goes back to the target of its wave
+        let raid_target = raider
+            .and_then(|raider| raider.target)
+            .filter(|raid_target| target_query.contains(*raid_target));
+        *goal = match target.or(raid_target) {
+            Some(target) => Goal::Entity(target),
+            None => Goal::Wander {
+                center: absolute_coord_to_tile_coord((*transform).into()),
+                radius: WANDER_RADIUS,
+                point: None,
+            },
+        };
+    }
+}
+
+/// the hostile units hit their target when it is in range and their attack is ready
+pub fn attack_system(
+    mut attacker_query: Query<(&Transform, &Hostile, &mut Attack)>,
+    target_query: Query<(&Transform, &Health, Option<&Footprint>, Has<Unit>)>,
+    map_manager: Res<MapManager>,
+    simulation_tick: Res<SimulationTick>,
+    mut message_damage: MessageWriter<Damage>,
+) {
+    for (transform, hostile, mut attack) in attacker_query.iter_mut() {
+        let Some(target) = hostile.target else {
+            continue;
+        };
+        let Ok((target_transform, _, footprint, is_unit)) = target_query.get(target) else {
+            continue;
+        };
+        if !attack.is_ready(simulation_tick.0) {
+            continue;
+        }
+        let position = transform.translation.xy();
+        if distance_to_edge(position, target_transform, footprint) > attack.range {
+            continue;
+        }
+        // a structure is its own obstacle, only the units must be in sight
+        if attack.kind == AttackKind::Ranged
+            && is_unit
+            && !has_line_of_sight(
+                AbsoluteCoordinates {
+                    x: position.x,
+                    y: position.y,
+                },
+                absolute_coord_to_tile_coord((*target_transform).into()),
+                &map_manager.walkability,
+            )
+        {
+            continue;
+        }
+        attack.last_attack_tick = Some(simulation_tick.0);
+        message_damage.write(Damage {
+            target,
+            amount: attack.damage,
+        });
+    }
+}
+
+/// unit or structure hit by an attack
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub struct Damaged {
+    health: &'static mut Health,
+    transform: &'static mut Transform,
+    velocity: Option<&'static mut LinearVelocity>,
+    footprint: Option<&'static Footprint>,
+    direction: Option<&'static Direction>,
+    is_player: Has<Player>,
+    is_structure: Has<Structure>,
+}
+
+/// removes the health of the damaged entities, respawns the player and despawns the other dead
+pub fn apply_damage_system(
+    mut commands: Commands,
+    mut message_damage: MessageReader<Damage>,
+    mut message_stimulus: MessageWriter<EmotionStimulus>,
+    mut damaged_query: Query<Damaged>,
+    mut map_manager: ResMut<MapManager>,
+    mut chunk_query: Query<&mut StructureLayerManager, With<Chunk>>,
+) {
+    for damage in message_damage.read() {
+        let Ok(mut damaged) = damaged_query.get_mut(damage.target) else {
+            continue;
+        };
+        // already dead this tick
+        if damaged.health.is_dead() {
+            continue;
+        }
+        message_stimulus.write(EmotionStimulus {
+            target: Some(damage.target),
+            emotion: Emotion::Fear,
+            amount: damage.amount * FEAR_PER_DAMAGE,
+        });
+        if !damaged.health.take(damage.amount) {
+            continue;
+        }
+
+        if damaged.is_player {
+            let respawn = coord_to_absolute_coord(PLAYER_RESPAWN_COORDINATES);
+            damaged.transform.translation.x = respawn.x;
+            damaged.transform.translation.y = respawn.y;
+            if let Some(velocity) = damaged.velocity.as_deref_mut() {
+                velocity.0 = Vec2::ZERO;
+            }
+            *damaged.health = Health::new(damaged.health.max);
+            continue;
+        }
+        if damaged.is_structure
+            && let Some(footprint) = damaged.footprint
+        {
+            // the units walk through its tiles again, the flow fields are repaired with them
+            let direction = damaged.direction.copied().unwrap_or(Direction::North);
+            let anchor = footprint.absolute_coord_to_anchor((*damaged.transform).into(), direction);
+            map_manager.unregister_structure(
+                footprint.tiles(anchor, direction),
+                damage.target,
+                &mut chunk_query,
+            );
+        }
+        commands.entity(damage.target).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{
+        ChunkCoordinates, LocalTileCoordinates, TileCoordinates, tile_coord_to_absolute_coord,
+    };
+    use bevy::ecs::{message::Messages, system::RunSystemOnce};
+
+    fn transform_at(tile: TileCoordinates) -> Transform {
+        let absolute_coord = tile_coord_to_absolute_coord(tile);
+        Transform::from_xyz(absolute_coord.x, absolute_coord.y, 0.0)
+    }
+
+    fn damage_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Messages<Damage>>();
+        world.init_resource::<Messages<EmotionStimulus>>();
+        world.init_resource::<MapManager>();
+        world
+    }
+
+    #[test]
+    fn test_attacks_wait_for_their_cooldown_and_health_dies_at_zero() {
+        let mut attack = Attack::melee(10.0, 30);
+        assert!(attack.is_ready(0));
+        attack.last_attack_tick = Some(100);
+        assert!(!attack.is_ready(129));
+        assert!(attack.is_ready(130));
+
+        let mut health = Health::new(25.0);
+        assert!(!health.take(10.0));
+        assert!(!health.take(10.0));
+        assert!(health.take(10.0));
+        assert_eq!(health.current, 0.0);
+    }
+
+    #[test]
+    fn test_destroyed_structure_frees_its_tiles_across_chunks() {
+        let mut world = damage_world();
+        let chunks = [
+            ChunkCoordinates { x: 0, y: 0 },
+            ChunkCoordinates { x: 1, y: 0 },
+        ];
+        for chunk_coord in chunks {
+            let chunk_entity = world.spawn(Chunk { coord: chunk_coord }).id();
+            world.resource_mut::<MapManager>().insert_chunk(
+                chunk_coord,
+                chunk_entity,
+                Vec::<LocalTileCoordinates>::new(),
+            );
+        }
+        // a 2x2 structure on the border of the two chunks
+        let footprint = Footprint::new(2, 2);
+        let anchor = TileCoordinates { x: 31, y: 3 };
+        let target_coord = footprint.anchor_to_absolute_coord(anchor, Direction::North);
+        let structure = world
+            .spawn((
+                Structure,
+                Health::new(10.0),
+                footprint,
+                Direction::North,
+                Transform::from_xyz(target_coord.x, target_coord.y, 0.0),
+            ))
+            .id();
+        let tiles = footprint.tiles(anchor, Direction::North);
+        let registered_tiles = tiles.clone();
+        world
+            .run_system_once(
+                move |mut map_manager: ResMut<MapManager>,
+                      mut chunk_query: Query<&mut StructureLayerManager, With<Chunk>>| {
+                    map_manager.register_structure(
+                        registered_tiles.clone(),
+                        structure,
+                        &mut chunk_query,
+                    );
+                },
+            )
+            .unwrap();
+        assert!(
+            tiles
+                .iter()
+                .all(|tile| !world.resource::<MapManager>().is_tile_walkable(*tile))
+        );
+
+        world.write_message(Damage {
+            target: structure,
+            amount: 10.0,
+        });
+        world.run_system_once(apply_damage_system).unwrap();
+
+        assert!(world.get_entity(structure).is_err());
+        let map_manager = world.resource::<MapManager>();
+        assert!(tiles.iter().all(|tile| map_manager.is_tile_walkable(*tile)));
+        assert!(
+            world
+                .query::<&StructureLayerManager>()
+                .iter(&world)
+                .all(|structure_manager| structure_manager.structures.is_empty())
+        );
+    }
+
+    #[test]
+    fn test_dead_player_respawns_with_full_health() {
+        let mut world = damage_world();
+        let player = world
+            .spawn((
+                Player,
+                Health::new(50.0),
+                transform_at(TileCoordinates { x: 20, y: 7 }),
+            ))
+            .id();
+        world.write_message(Damage {
+            target: player,
+            amount: 80.0,
+        });
+        world.run_system_once(apply_damage_system).unwrap();
+
+        let respawn = coord_to_absolute_coord(PLAYER_RESPAWN_COORDINATES);
+        let translation = world.get::<Transform>(player).unwrap().translation;
+        assert_eq!(translation.xy(), Vec2::new(respawn.x, respawn.y));
+        assert_eq!(*world.get::<Health>(player).unwrap(), Health::new(50.0));
+    }
+
+    #[test]
+    fn test_hostiles_chase_the_units_before_the_structures() {
+        let mut world = World::new();
+        let hostile = world
+            .spawn((
+                Hostile::new(5.0),
+                Goal::Idle,
+                transform_at(TileCoordinates { x: 0, y: 0 }),
+            ))
+            .id();
+        let structure = world
+            .spawn((
+                Structure,
+                Health::default(),
+                transform_at(TileCoordinates { x: 1, y: 0 }),
+            ))
+            .id();
+        let unit = world
+            .spawn((
+                Unit,
+                Health::default(),
+                transform_at(TileCoordinates { x: 0, y: 4 }),
+            ))
+            .id();
+        // out of the aggro radius
+        world.spawn((
+            Unit,
+            Health::default(),
+            transform_at(TileCoordinates { x: 0, y: 8 }),
+        ));
+
+        world.run_system_once(aggro_system).unwrap();
+        assert_eq!(world.get::<Hostile>(hostile).unwrap().target, Some(unit));
+        assert_eq!(*world.get::<Goal>(hostile).unwrap(), Goal::Entity(unit));
+
+        world.despawn(unit);
+        world.run_system_once(aggro_system).unwrap();
+        assert_eq!(
+            world.get::<Hostile>(hostile).unwrap().target,
+            Some(structure)
+        );
+    }
+}
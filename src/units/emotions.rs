@@ -0,0 +1,248 @@
+use crate::{
+    environment::weather::{WeatherChanged, WeatherKind},
+    map::{AbsoluteCoordinates, TILE_SIZE, absolute_coord_to_tile_coord},
+    units::{Player, Unit, hierarchical::Waypoint, pathfinding::FlowFields},
+};
+use avian2d::prelude::LinearVelocity;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// part of each emotion lost per second
+pub const EMOTION_DECAY_PER_SECOND: f32 = 0.02;
+/// an emotion above this threshold drives the behavior of the unit
+pub const EMOTION_THRESHOLD: f32 = 0.6;
+/// a unit slower than this while it has somewhere to go is blocked, in pixels per second
+const BLOCKED_SPEED: f32 = TILE_SIZE.x * 0.1;
+const BLOCKED_ANGER_PER_SECOND: f32 = 0.05;
+pub const TALK_JOY: f32 = 0.15;
+
+pub struct EmotionsPlugin;
+
+impl Plugin for EmotionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<EmotionStimulus>().add_systems(
+            FixedUpdate,
+            (
+                add_emotions_system,
+                react_to_weather_system,
+                react_to_blocking_system,
+                apply_emotion_stimuli_system,
+                decay_emotions_system,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emotion {
+    Anger,
+    Joy,
+    Fear,
+    Sadness,
+}
+
+/// emotional state of a non-player unit, each emotion goes from 0.0 to 1.0 and decays toward 0.0
+/// the dialogue templates read it with the french names
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Emotions {
+    #[serde(rename = "colere")]
+    pub anger: f32,
+    #[serde(rename = "joie")]
+    pub joy: f32,
+    #[serde(rename = "peur")]
+    pub fear: f32,
+    #[serde(rename = "tristesse")]
+    pub sadness: f32,
+}
+impl Emotions {
+    pub fn get(&self, emotion: Emotion) -> f32 {
+        match emotion {
+            Emotion::Anger => self.anger,
+            Emotion::Joy => self.joy,
+            Emotion::Fear => self.fear,
+            Emotion::Sadness => self.sadness,
+        }
+    }
+
+    fn get_mut(&mut self, emotion: Emotion) -> &mut f32 {
+        match emotion {
+            Emotion::Anger => &mut self.anger,
+            Emotion::Joy => &mut self.joy,
+            Emotion::Fear => &mut self.fear,
+            Emotion::Sadness => &mut self.sadness,
+        }
+    }
+
+    /// amount can be negative to calm an emotion down
+    pub fn add(&mut self, emotion: Emotion, amount: f32) {
+        let value = self.get_mut(emotion);
+        *value = (*value + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn decay(&mut self, delta_secs: f32) {
+        let factor = (1.0 - EMOTION_DECAY_PER_SECOND * delta_secs).max(0.0);
+        for emotion in [
+            Emotion::Anger,
+            Emotion::Joy,
+            Emotion::Fear,
+            Emotion::Sadness,
+        ] {
+            *self.get_mut(emotion) *= factor;
+        }
+    }
+
+    /// strongest emotion, when it is above EMOTION_THRESHOLD
+    pub fn dominant(&self) -> Option<Emotion> {
+        [
+            Emotion::Fear,
+            Emotion::Anger,
+            Emotion::Sadness,
+            Emotion::Joy,
+        ]
+        .into_iter()
+        .filter(|emotion| self.get(*emotion) > EMOTION_THRESHOLD)
+        .max_by(|a, b| self.get(*a).total_cmp(&self.get(*b)))
+    }
+
+    pub fn behavior(&self) -> Behavior {
+        match self.dominant() {
+            Some(Emotion::Fear) => Behavior::Flee,
+            Some(Emotion::Anger) => Behavior::Aggressive,
+            Some(Emotion::Sadness) => Behavior::Sulk,
+            Some(Emotion::Joy) | None => Behavior::Calm,
+        }
+    }
+}
+
+/// how the AI of a unit acts, chosen from its emotions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    Calm,
+    /// moves away from its target
+    Flee,
+    /// rushes toward its target
+    Aggressive,
+    /// drags its feet
+    Sulk,
+}
+impl Behavior {
+    /// multiplier of the force a unit uses to reach its target, negative to move away
+    pub fn force_multiplier(&self) -> f32 {
+        match self {
+            Behavior::Calm => 1.0,
+            Behavior::Flee => -1.0,
+            Behavior::Aggressive => 1.3,
+            Behavior::Sulk => 0.5,
+        }
+    }
+}
+
+/// something that happened to a unit, or to every unit when target is None
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct EmotionStimulus {
+    pub target: Option<Entity>,
+    pub emotion: Emotion,
+    pub amount: f32,
+}
+
+fn add_emotions_system(
+    mut commands: Commands,
+    unit_query: Query<(Entity, Has<Player>, Has<Emotions>), Added<Unit>>,
+) {
+    // units loaded from a save already have their emotions
+    for (entity, is_player, has_emotions) in unit_query.iter() {
+        if !is_player && !has_emotions {
+            commands.entity(entity).insert(Emotions::default());
+        }
+    }
+}
+
+pub fn react_to_weather_system(
+    mut message_weather_changed: MessageReader<WeatherChanged>,
+    mut message_stimulus: MessageWriter<EmotionStimulus>,
+) {
+    for weather_changed in message_weather_changed.read() {
+        let stimuli: &[(Emotion, f32)] = match weather_changed.kind {
+            WeatherKind::Clear => &[(Emotion::Joy, 0.3), (Emotion::Sadness, -0.2)],
+            WeatherKind::Rain => &[(Emotion::Sadness, 0.2), (Emotion::Anger, 0.1)],
+            WeatherKind::Storm => &[(Emotion::Fear, 0.4), (Emotion::Anger, 0.2)],
+        };
+        for (emotion, amount) in stimuli {
+            message_stimulus.write(EmotionStimulus {
+                target: None,
+                emotion: *emotion,
+                amount: *amount,
+            });
+        }
+    }
+}
+
+/// units that have a path but do not move get angry
+pub fn react_to_blocking_system(
+    mut unit_query: Query<(&Transform, &LinearVelocity, &Waypoint, &mut Emotions)>,
+    flow_fields: Res<FlowFields>,
+    time: Res<Time<Fixed>>,
+) {
+    for (transform, velocity, waypoint, mut emotions) in unit_query.iter_mut() {
+        let current_tile = absolute_coord_to_tile_coord(AbsoluteCoordinates {
+            x: transform.translation.x,
+            y: transform.translation.y,
+        });
+        let has_path = waypoint
+            .0
+            .is_some_and(|goal| flow_fields.next_tile(goal, current_tile).is_some());
+        if has_path && velocity.length() < BLOCKED_SPEED {
+            emotions.add(Emotion::Anger, BLOCKED_ANGER_PER_SECOND * time.delta_secs());
+        }
+    }
+}
+
+pub fn apply_emotion_stimuli_system(
+    mut message_stimulus: MessageReader<EmotionStimulus>,
+    mut emotions_query: Query<&mut Emotions>,
+) {
+    for stimulus in message_stimulus.read() {
+        match stimulus.target {
+            Some(target) => {
+                if let Ok(mut emotions) = emotions_query.get_mut(target) {
+                    emotions.add(stimulus.emotion, stimulus.amount);
+                }
+            }
+            None => {
+                for mut emotions in emotions_query.iter_mut() {
+                    emotions.add(stimulus.emotion, stimulus.amount);
+                }
+            }
+        }
+    }
+}
+
+pub fn decay_emotions_system(mut emotions_query: Query<&mut Emotions>, time: Res<Time<Fixed>>) {
+    for mut emotions in emotions_query.iter_mut() {
+        emotions.decay(time.delta_secs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emotions_drive_behavior_and_decay() {
+        let mut emotions = Emotions::default();
+        assert_eq!(emotions.behavior(), Behavior::Calm);
+
+        emotions.add(Emotion::Anger, 0.7);
+        emotions.add(Emotion::Fear, 2.0);
+        assert_eq!(emotions.fear, 1.0);
+        assert_eq!(emotions.behavior(), Behavior::Flee);
+
+        // one minute of decay calms both emotions down
+        for _ in 0..60 {
+            emotions.decay(1.0);
+        }
+        assert!(emotions.fear < EMOTION_THRESHOLD);
+        assert_eq!(emotions.behavior(), Behavior::Calm);
+    }
+}
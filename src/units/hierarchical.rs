@@ -0,0 +1,419 @@
+use crate::{
+    map::{
+        CHUNK_SIZE, ChunkCoordinates, MapManager, TileCoordinates, absolute_coord_to_tile_coord,
+        chunk_coord_to_tile_coord, tile_coord_to_chunk_coord, tile_coord_to_local_tile_coord,
+        walkability::WalkabilityGrid,
+    },
+    units::pathfinding::{
+        CostQueue, GoalTile, STRAIGHT_COST, is_in_flow_field_range, neighbors, step_cost,
+        straight_line_cost,
+    },
+};
+use bevy::prelude::*;
+use pathfinding::prelude::astar;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+};
+
+/// chunks are square
+const CHUNK_WIDTH: i32 = CHUNK_SIZE.x as i32;
+const CHUNK_NEIGHBORS: [IVec2; 4] = [IVec2::NEG_Y, IVec2::NEG_X, IVec2::X, IVec2::Y];
+
+/// abstract graph of the spawned chunks used to plan the paths longer than a flow field
+/// each run of walkable tiles shared by two neighbor chunks gets one portal on each side
+/// the portals of a chunk are linked by their walking cost inside the chunk, and to the facing portal of the neighbor chunk
+#[derive(Resource, Default)]
+pub struct PortalGraph {
+    portals: HashMap<ChunkCoordinates, Vec<TileCoordinates>>,
+    edges: HashMap<TileCoordinates, Vec<(TileCoordinates, u32)>>,
+    /// incremented each time the graph changes so the units plan their path again
+    pub version: u64,
+}
+
+/// tile whose flow field the unit follows, its GoalTile when it is close enough, else the next portal of its LongPath
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Waypoint(pub Option<TileCoordinates>);
+
+/// portals to go through to reach a goal out of the range of the flow fields, ending with the goal
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct LongPath {
+    goal: Option<TileCoordinates>,
+    graph_version: u64,
+    tiles: VecDeque<TileCoordinates>,
+}
+impl LongPath {
+    /// furthest tile of the path the flow fields can lead to without leaving their range
+    /// the tiles before it are dropped, None when the unit drifted away from its path
+    fn next_waypoint(&mut self, current_tile: TileCoordinates) -> Option<TileCoordinates> {
+        let in_range = self
+            .tiles
+            .iter()
+            .take_while(|tile| is_in_flow_field_range(current_tile, **tile))
+            .count();
+        if in_range == 0 {
+            return None;
+        }
+        self.tiles.drain(..in_range - 1);
+        self.tiles.front().copied()
+    }
+}
+
+fn local_index(tile: TileCoordinates, chunk_coord: ChunkCoordinates) -> usize {
+    let local_tile = tile_coord_to_local_tile_coord(tile, chunk_coord);
+    (local_tile.y * CHUNK_WIDTH + local_tile.x) as usize
+}
+
+/// walking cost from a tile to every tile of its chunk without leaving it, u32::MAX for the tiles it cannot reach
+/// same steps as the flow fields, so the costs can be compared with theirs
+fn local_costs(from: TileCoordinates, walkability: &WalkabilityGrid) -> Vec<u32> {
+    let chunk_coord = tile_coord_to_chunk_coord(from);
+    let mut costs = vec![u32::MAX; (CHUNK_WIDTH * CHUNK_WIDTH) as usize];
+    if !walkability.is_walkable(from) {
+        return costs;
+    }
+    costs[local_index(from, chunk_coord)] = 0;
+    let mut queue = CostQueue::from([Reverse((0, from.y, from.x))]);
+    while let Some(Reverse((cost, y, x))) = queue.pop() {
+        let tile = TileCoordinates { x, y };
+        if costs[local_index(tile, chunk_coord)] != cost {
+            // already reached with a lower cost
+            continue;
+        }
+        // the corners of a diagonal between two tiles of the chunk are in the chunk too
+        for neighbor in neighbors(tile) {
+            if tile_coord_to_chunk_coord(neighbor) != chunk_coord {
+                continue;
+            }
+            let Some(step_cost) = step_cost(tile, neighbor, walkability) else {
+                continue;
+            };
+            let index = local_index(neighbor, chunk_coord);
+            if cost + step_cost < costs[index] {
+                costs[index] = cost + step_cost;
+                queue.push(Reverse((cost + step_cost, neighbor.y, neighbor.x)));
+            }
+        }
+    }
+    costs
+}
+
+/// (portal inside the chunk, facing portal in the neighbor chunk) for each walkable run of the border in that direction
+/// both chunks find the same pairs, so the graph of a chunk can be rebuilt without its neighbors
+fn border_portals(
+    chunk_coord: ChunkCoordinates,
+    direction: IVec2,
+    walkability: &WalkabilityGrid,
+) -> Vec<(TileCoordinates, TileCoordinates)> {
+    let origin = chunk_coord_to_tile_coord(chunk_coord);
+    let (start, along) = match (direction.x, direction.y) {
+        (1, 0) => (IVec2::new(CHUNK_WIDTH - 1, 0), IVec2::Y),
+        (-1, 0) => (IVec2::ZERO, IVec2::Y),
+        (0, 1) => (IVec2::new(0, CHUNK_WIDTH - 1), IVec2::X),
+        _ => (IVec2::ZERO, IVec2::X),
+    };
+    let border_tiles = |i: i32| {
+        let inside = IVec2::new(origin.x, origin.y) + start + along * i;
+        let outside = inside + direction;
+        (
+            TileCoordinates {
+                x: inside.x,
+                y: inside.y,
+            },
+            TileCoordinates {
+                x: outside.x,
+                y: outside.y,
+            },
+        )
+    };
+
+    let mut portals = Vec::new();
+    let mut run_start = None;
+    for i in 0..=CHUNK_WIDTH {
+        let is_open = i < CHUNK_WIDTH && {
+            let (inside, outside) = border_tiles(i);
+            walkability.is_walkable(inside) && walkability.is_walkable(outside)
+        };
+        match (is_open, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                portals.push(border_tiles(start + (i - start) / 2));
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    portals
+}
+
+impl PortalGraph {
+    /// rebuilds the portals of the changed chunks and of their neighbors, whose shared borders may have changed too
+    pub fn update_chunks(
+        &mut self,
+        changed_chunks: &HashSet<ChunkCoordinates>,
+        walkability: &WalkabilityGrid,
+    ) {
+        let mut rebuilt: Vec<ChunkCoordinates> = changed_chunks
+            .iter()
+            .flat_map(|chunk_coord| {
+                std::iter::once(IVec2::ZERO)
+                    .chain(CHUNK_NEIGHBORS)
+                    .map(move |delta| ChunkCoordinates {
+                        x: chunk_coord.x + delta.x,
+                        y: chunk_coord.y + delta.y,
+                    })
+            })
+            .filter(|chunk_coord| walkability.contains_chunk(*chunk_coord))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if rebuilt.is_empty() {
+            return;
+        }
+        // same order every run so the paths are the same
+        rebuilt.sort_by_key(|chunk_coord| (chunk_coord.y, chunk_coord.x));
+
+        for chunk_coord in &rebuilt {
+            for portal in self.portals.remove(chunk_coord).unwrap_or_default() {
+                self.edges.remove(&portal);
+            }
+        }
+        for chunk_coord in rebuilt {
+            self.build_chunk(chunk_coord, walkability);
+        }
+        self.version += 1;
+    }
+
+    fn build_chunk(&mut self, chunk_coord: ChunkCoordinates, walkability: &WalkabilityGrid) {
+        let pairs: Vec<(TileCoordinates, TileCoordinates)> = CHUNK_NEIGHBORS
+            .into_iter()
+            .filter(|direction| {
+                walkability.contains_chunk(ChunkCoordinates {
+                    x: chunk_coord.x + direction.x,
+                    y: chunk_coord.y + direction.y,
+                })
+            })
+            .flat_map(|direction| border_portals(chunk_coord, direction, walkability))
+            .collect();
+        let mut portals: Vec<TileCoordinates> = Vec::new();
+        for (inside, _) in &pairs {
+            // a corner tile can be a portal toward two neighbors
+            if !portals.contains(inside) {
+                portals.push(*inside);
+            }
+        }
+
+        for portal in &portals {
+            let costs = local_costs(*portal, walkability);
+            let mut edges: Vec<(TileCoordinates, u32)> = portals
+                .iter()
+                .filter(|other| *other != portal)
+                .map(|other| (*other, costs[local_index(*other, chunk_coord)]))
+                .filter(|(_, cost)| *cost != u32::MAX)
+                .collect();
+            edges.extend(
+                pairs
+                    .iter()
+                    .filter(|(inside, _)| inside == portal)
+                    .map(|(_, outside)| (*outside, STRAIGHT_COST)),
+            );
+            self.edges.insert(*portal, edges);
+        }
+        self.portals.insert(chunk_coord, portals);
+    }
+
+    /// A* over the portals, from the portals reachable from the start inside its chunk to the goal reachable inside its chunk
+    /// returns the portals to go through followed by the goal, None when the goal cannot be reached through the spawned chunks
+    pub fn find_path(
+        &self,
+        start: TileCoordinates,
+        goal: TileCoordinates,
+        walkability: &WalkabilityGrid,
+    ) -> Option<Vec<TileCoordinates>> {
+        self.search(start, goal, walkability)
+            .map(|(path, _)| path.into_iter().skip(1).collect())
+    }
+
+    /// walking cost from the start to the goal through the portals, in the unit of the flow fields
+    pub fn path_cost(
+        &self,
+        start: TileCoordinates,
+        goal: TileCoordinates,
+        walkability: &WalkabilityGrid,
+    ) -> Option<u32> {
+        self.search(start, goal, walkability).map(|(_, cost)| cost)
+    }
+
+    fn search(
+        &self,
+        start: TileCoordinates,
+        goal: TileCoordinates,
+        walkability: &WalkabilityGrid,
+    ) -> Option<(Vec<TileCoordinates>, u32)> {
+        let start_chunk = tile_coord_to_chunk_coord(start);
+        let goal_chunk = tile_coord_to_chunk_coord(goal);
+        if !walkability.contains_chunk(start_chunk) || !walkability.contains_chunk(goal_chunk) {
+            return None;
+        }
+        let start_costs = local_costs(start, walkability);
+        let start_edges: Vec<(TileCoordinates, u32)> = self
+            .portals
+            .get(&start_chunk)
+            .into_iter()
+            .flatten()
+            .map(|portal| (*portal, start_costs[local_index(*portal, start_chunk)]))
+            .filter(|(_, cost)| *cost != u32::MAX)
+            .collect();
+        // the costs inside the chunk of the goal are the same in both directions
+        let goal_costs = local_costs(goal, walkability);
+
+        let successors = |tile: &TileCoordinates| {
+            let mut successors = self.edges.get(tile).cloned().unwrap_or_default();
+            if *tile == start {
+                successors.extend(start_edges.iter().copied());
+            }
+            if tile_coord_to_chunk_coord(*tile) == goal_chunk {
+                let cost = goal_costs[local_index(*tile, goal_chunk)];
+                if cost != u32::MAX {
+                    successors.push((goal, cost));
+                }
+            }
+            successors
+        };
+        let heuristic = |tile: &TileCoordinates| straight_line_cost(*tile, goal);
+        astar(&start, successors, heuristic, |tile| *tile == goal)
+    }
+}
+
+/// rebuilds the graph of the chunks that were spawned or whose structures changed
+pub fn update_portal_graph_system(
+    mut portal_graph: ResMut<PortalGraph>,
+    mut map_manager: ResMut<MapManager>,
+) {
+    let changed_chunks = map_manager.walkability.take_changed_chunks();
+    if !changed_chunks.is_empty() {
+        portal_graph.update_chunks(&changed_chunks, &map_manager.walkability);
+    }
+}
+
+/// picks the tile each unit follows the flow field of, planning a path over the portals when its goal is too far
+pub fn follow_long_paths_system(
+    mut unit_query: Query<(&GoalTile, &Transform, &mut LongPath, &mut Waypoint)>,
+    portal_graph: Res<PortalGraph>,
+    map_manager: Res<MapManager>,
+) {
+    for (goal_tile, transform, mut long_path, mut waypoint) in unit_query.iter_mut() {
+        let current_tile = absolute_coord_to_tile_coord((*transform).into());
+        let next_waypoint = match goal_tile.0 {
+            Some(goal) if !is_in_flow_field_range(current_tile, goal) => {
+                // unreachable goals are only planned again when the graph changes
+                let drifted =
+                    !long_path.tiles.is_empty() && long_path.next_waypoint(current_tile).is_none();
+                if long_path.goal != Some(goal)
+                    || long_path.graph_version != portal_graph.version
+                    || drifted
+                {
+                    *long_path = LongPath {
+                        goal: Some(goal),
+                        graph_version: portal_graph.version,
+                        tiles: portal_graph
+                            .find_path(current_tile, goal, &map_manager.walkability)
+                            .unwrap_or_default()
+                            .into(),
+                    };
+                }
+                long_path.next_waypoint(current_tile)
+            }
+            goal => {
+                if long_path.goal.is_some() {
+                    *long_path = LongPath::default();
+                }
+                goal
+            }
+        };
+        if waypoint.0 != next_waypoint {
+            waypoint.0 = next_waypoint;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::LocalTileCoordinates;
+
+    #[test]
+    fn test_path_goes_around_walls_and_follows_structure_changes() {
+        let mut walkability = WalkabilityGrid::default();
+        // three chunks in a row, the border between the last two is a wall except at y = 5
+        for x in 0..3 {
+            let wall = (x == 1)
+                .then(|| {
+                    (0..CHUNK_WIDTH)
+                        .filter(|y| *y != 5)
+                        .map(|y| LocalTileCoordinates {
+                            x: CHUNK_WIDTH - 1,
+                            y,
+                        })
+                })
+                .into_iter()
+                .flatten();
+            walkability.insert_chunk(ChunkCoordinates { x, y: 0 }, wall);
+        }
+        let mut graph = PortalGraph::default();
+        graph.update_chunks(&walkability.take_changed_chunks(), &walkability);
+
+        let start = TileCoordinates { x: 0, y: 30 };
+        let goal = TileCoordinates { x: 90, y: 30 };
+        let path = graph.find_path(start, goal, &walkability).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.contains(&TileCoordinates {
+            x: 2 * CHUNK_WIDTH - 1,
+            y: 5
+        }));
+        // outside the spawned chunks
+        assert!(
+            graph
+                .find_path(start, TileCoordinates { x: 200, y: 30 }, &walkability)
+                .is_none()
+        );
+
+        walkability.set_walkable(
+            TileCoordinates {
+                x: 2 * CHUNK_WIDTH - 1,
+                y: 5,
+            },
+            false,
+        );
+        graph.update_chunks(&walkability.take_changed_chunks(), &walkability);
+        assert!(graph.find_path(start, goal, &walkability).is_none());
+    }
+
+    #[test]
+    fn test_walking_costs_take_diagonals_without_cutting_corners() {
+        let mut walkability = WalkabilityGrid::default();
+        // a single blocked tile between (4, 4) and (5, 5)
+        walkability.insert_chunk(
+            ChunkCoordinates { x: 0, y: 0 },
+            [LocalTileCoordinates { x: 5, y: 4 }],
+        );
+        let mut graph = PortalGraph::default();
+        graph.update_chunks(&walkability.take_changed_chunks(), &walkability);
+
+        let start = TileCoordinates { x: 0, y: 0 };
+        let goal = TileCoordinates { x: 3, y: 3 };
+        // as cheap as flying there, like the flow fields count it
+        assert_eq!(
+            graph.path_cost(start, goal, &walkability),
+            Some(straight_line_cost(start, goal))
+        );
+        assert_eq!(
+            graph.path_cost(
+                TileCoordinates { x: 4, y: 4 },
+                TileCoordinates { x: 5, y: 5 },
+                &walkability
+            ),
+            Some(2 * STRAIGHT_COST)
+        );
+    }
+}
@@ -1,4 +1,11 @@
+pub mod combat;
+pub mod emotions;
+pub mod hierarchical;
+pub mod nests;
 pub mod pathfinding;
+pub mod steering;
+pub mod turrets;
 mod unit;
+pub mod worker;
 
 pub use unit::*;
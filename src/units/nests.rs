@@ -0,0 +1,229 @@
+use crate::{
+    UPS_TARGET,
+    environment::day_night::{DayPhase, DayPhaseChanged, update_world_clock_system},
+    map::{
+        ChunkCoordinates, MapManager, STRUCTURE_LAYER, SpritePath, Structure, TileCoordinates,
+        absolute_coord_to_chunk_coord, absolute_coord_to_tile_coord, machine::Machine,
+        tile_coord_to_absolute_coord, tile_coord_to_coord,
+    },
+    units::{
+        Speed, UNIT_DEFAULT_MOVEMENT_SPEED,
+        combat::{Attack, Health, Hostile, aggro_system},
+        pathfinding::{Goal, closest_walkable_tile},
+        spawn_unit,
+    },
+};
+use bevy::prelude::*;
+
+/// nests are only generated in the chunks at least this far from the origin chunk
+pub const NEST_MIN_CHUNK_DISTANCE: i32 = 2;
+/// chance for one of these chunks to hold a nest
+pub const NEST_CHANCE: f64 = 0.5;
+const NEST_HEALTH: f32 = 500.0;
+/// ticks between two waves of a nest in a clean chunk
+const WAVE_INTERVAL_TICKS: f32 = (UPS_TARGET * 60 * 2) as f32;
+/// each point of pollution in the chunk of a nest makes its timer go this much faster
+const WAVE_SPEEDUP_PER_POLLUTION: f32 = 0.1;
+/// part of WAVE_INTERVAL_TICKS the timer of the nests jumps at dusk, the raids come with the night
+const DUSK_WAVE_HEADSTART: f32 = 0.5;
+const WAVE_SIZE: usize = 3;
+/// a nest stops sending waves while this many of its raiders are alive
+const MAX_RAIDERS_PER_NEST: usize = 6;
+const RAIDER_AGGRO_RADIUS: f32 = 4.0;
+
+pub struct NestsPlugin;
+
+impl Plugin for NestsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            nest_waves_system
+                .after(update_world_clock_system)
+                .before(aggro_system),
+        );
+    }
+}
+
+/// structure sending waves of raiders to the factory
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+#[require(Structure, Health = Health::new(NEST_HEALTH))]
+pub struct Nest {
+    /// grows each tick, faster with the pollution and at dusk, a wave leaves when it reaches WAVE_INTERVAL_TICKS
+    pub wave_progress: f32,
+}
+
+/// hostile unit sent by a nest, it goes for its target when nothing is in its aggro radius
+/// a saved raider is loaded as a plain hostile unit
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Raider {
+    pub nest: Entity,
+    /// the most polluting structure when the wave left
+    pub target: Option<Entity>,
+}
+
+pub fn is_nest_chunk(chunk_coord: ChunkCoordinates) -> bool {
+    chunk_coord.x.abs().max(chunk_coord.y.abs()) >= NEST_MIN_CHUNK_DISTANCE
+}
+
+pub fn spawn_nest(commands: &mut Commands, tile_coord: TileCoordinates, nest: Nest) -> Entity {
+    let target_coord = tile_coord_to_absolute_coord(tile_coord);
+    commands
+        .spawn((
+            Name::new("Nest"),
+            nest,
+            // until the nest has its own sprite
+            SpritePath::structure("default_machine.png"),
+            Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+        ))
+        .id()
+}
+
+/// a machine of the most polluted chunk, the closest to the nest when several are
+fn most_polluting_machine(
+    nest_tile: TileCoordinates,
+    machines: &[(Entity, TileCoordinates, ChunkCoordinates)],
+    map_manager: &MapManager,
+) -> Option<Entity> {
+    machines
+        .iter()
+        .filter(|(_, _, chunk_coord)| map_manager.pollution(*chunk_coord) > 0.0)
+        .max_by(|(a, a_tile, a_chunk), (b, b_tile, b_chunk)| {
+            let a_distance = (a_tile.x - nest_tile.x).abs() + (a_tile.y - nest_tile.y).abs();
+            let b_distance = (b_tile.x - nest_tile.x).abs() + (b_tile.y - nest_tile.y).abs();
+            map_manager
+                .pollution(*a_chunk)
+                .total_cmp(&map_manager.pollution(*b_chunk))
+                .then(b_distance.cmp(&a_distance))
+                .then(b.cmp(a))
+        })
+        .map(|(entity, ..)| *entity)
+}
+
+/// advances the timer of the nests and sends a wave of raiders to the most polluting structure when it is over
+pub fn nest_waves_system(
+    mut commands: Commands,
+    mut nest_query: Query<(Entity, &Transform, &mut Nest)>,
+    raider_query: Query<&Raider>,
+    machine_query: Query<(Entity, &Transform), With<Machine>>,
+    map_manager: Res<MapManager>,
+    mut message_phase_changed: MessageReader<DayPhaseChanged>,
+) {
+    let dusk_headstart = if message_phase_changed
+        .read()
+        .any(|phase_changed| phase_changed.phase == DayPhase::Dusk)
+    {
+        WAVE_INTERVAL_TICKS * DUSK_WAVE_HEADSTART
+    } else {
+        0.0
+    };
+    let mut machines: Vec<(Entity, TileCoordinates, ChunkCoordinates)> = machine_query
+        .iter()
+        .map(|(entity, transform)| {
+            (
+                entity,
+                absolute_coord_to_tile_coord((*transform).into()),
+                absolute_coord_to_chunk_coord((*transform).into()),
+            )
+        })
+        .collect();
+    machines.sort_by_key(|(entity, ..)| *entity);
+
+    for (nest_entity, transform, mut nest) in nest_query.iter_mut() {
+        let chunk_coord = absolute_coord_to_chunk_coord((*transform).into());
+        nest.wave_progress +=
+            1.0 + map_manager.pollution(chunk_coord) * WAVE_SPEEDUP_PER_POLLUTION + dusk_headstart;
+        if nest.wave_progress < WAVE_INTERVAL_TICKS {
+            continue;
+        }
+        nest.wave_progress = 0.0;
+
+        let raiders = raider_query
+            .iter()
+            .filter(|raider| raider.nest == nest_entity)
+            .count();
+        let nest_tile = absolute_coord_to_tile_coord((*transform).into());
+        let Some(spawn_tile) = closest_walkable_tile(nest_tile, nest_tile, &map_manager) else {
+            continue;
+        };
+        let target = most_polluting_machine(nest_tile, &machines, &map_manager);
+        for _ in raiders..MAX_RAIDERS_PER_NEST.min(raiders + WAVE_SIZE) {
+            spawn_unit(
+                &mut commands,
+                "Raider",
+                tile_coord_to_coord(spawn_tile),
+                Speed(UNIT_DEFAULT_MOVEMENT_SPEED),
+            )
+            .insert((
+                Raider {
+                    nest: nest_entity,
+                    target,
+                },
+                Hostile::new(RAIDER_AGGRO_RADIUS),
+                Attack::melee(5.0, UPS_TARGET as u64),
+                // without pollution the raiders roam around their nest
+                target.map_or(
+                    Goal::Wander {
+                        center: spawn_tile,
+                        radius: RAIDER_AGGRO_RADIUS as i32,
+                        point: None,
+                    },
+                    Goal::Entity,
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{message::Messages, system::RunSystemOnce};
+
+    #[test]
+    fn test_waves_target_the_machines_of_the_most_polluted_chunk() {
+        let mut map_manager = MapManager::default();
+        let clean = ChunkCoordinates { x: 0, y: 0 };
+        let polluted = ChunkCoordinates { x: 1, y: 0 };
+        map_manager.add_pollution(polluted, 3.0);
+        let entity = |index| Entity::from_raw_u32(index).unwrap();
+        let machines = [
+            (entity(1), TileCoordinates { x: 2, y: 2 }, clean),
+            (entity(2), TileCoordinates { x: 60, y: 2 }, polluted),
+            (entity(3), TileCoordinates { x: 40, y: 2 }, polluted),
+        ];
+        let nest_tile = TileCoordinates { x: 100, y: 2 };
+        assert_eq!(
+            most_polluting_machine(nest_tile, &machines, &map_manager),
+            Some(entity(2))
+        );
+        assert_eq!(
+            most_polluting_machine(nest_tile, &machines[..1], &map_manager),
+            None
+        );
+
+        assert!(!is_nest_chunk(ChunkCoordinates { x: 1, y: -1 }));
+        assert!(is_nest_chunk(ChunkCoordinates { x: -2, y: 0 }));
+    }
+
+    #[test]
+    fn test_nests_get_closer_to_their_wave_at_dusk() {
+        let mut world = World::new();
+        world.init_resource::<MapManager>();
+        world.init_resource::<Messages<DayPhaseChanged>>();
+        let nest = world.spawn((Nest::default(), Transform::default())).id();
+
+        world.run_system_once(nest_waves_system).unwrap();
+        assert_eq!(world.get::<Nest>(nest).unwrap().wave_progress, 1.0);
+
+        world.write_message(DayPhaseChanged {
+            day: 1,
+            previous: DayPhase::Day,
+            phase: DayPhase::Dusk,
+        });
+        world.run_system_once(nest_waves_system).unwrap();
+        assert_eq!(
+            world.get::<Nest>(nest).unwrap().wave_progress,
+            2.0 + WAVE_INTERVAL_TICKS * DUSK_WAVE_HEADSTART
+        );
+    }
+}
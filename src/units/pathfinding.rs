@@ -1,151 +1,536 @@
 use crate::{
-    map::{MapManager, StructureLayerManager, TileCoordinates, absolute_coord_to_tile_coord},
-    units::Player,
+    map::{
+        AbsoluteCoordinates, MapManager, MapSeed, TILE_SIZE, TileCoordinates,
+        absolute_coord_to_tile_coord, tile_coord_to_absolute_coord, walkability::WalkabilityGrid,
+    },
+    simulation::SimulationTick,
+    units::{
+        Player, UNIT_DEFAULT_SIZE, Unit,
+        hierarchical::{
+            PortalGraph, Waypoint, follow_long_paths_system, update_portal_graph_system,
+        },
+    },
+};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
 };
-use bevy::{prelude::*, sprite_render::TilemapChunk};
-use pathfinding::prelude::dijkstra_all;
-use std::collections::HashMap;
 
 const FLOWFIELD_RADIUS: i32 = 50; // radius in tile
+const FLOWFIELD_SIZE: i32 = 2 * FLOWFIELD_RADIUS + 1;
+pub const STRAIGHT_COST: u32 = 10;
+pub const DIAGONAL_COST: u32 = 14;
+/// in the order the next tile is picked when several neighbors have the same cost, straight steps first
+const NEIGHBORS: [IVec2; 8] = [
+    IVec2::new(0, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(-1, -1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(1, 1),
+];
+/// tiles of the field looked ahead for the furthest one in line of sight
+const SMOOTHING_LOOKAHEAD: usize = 8;
+/// distance between the points checked along a line of sight, in pixels
+const LINE_OF_SIGHT_STEP: f32 = TILE_SIZE.x * 0.25;
+/// new flow fields computed in one tick, the other units wait for the next ticks
+const MAX_FLOW_FIELDS_PER_TICK: usize = 4;
+/// distance in tiles searched around an entity goal to find where to stand
+const GOAL_SEARCH_RADIUS: i32 = 3;
 
 pub struct PathfindingPlugin;
 
+/// cost to reach the goal from each tile of the square of FLOWFIELD_RADIUS around it
+/// the next tile to take is the neighbor with the lowest cost
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+    pub goal: TileCoordinates,
+    /// u32::MAX for the tiles that cannot reach the goal
+    costs: Vec<u32>,
+}
+
+/// flow fields cached per goal tile, shared by every unit going to the same tile
+/// fields no unit goes to anymore are dropped
 #[derive(Resource, Default)]
-// pub struct FlowField(pub HashMap<TileCoordinates, Vec2>);
-pub struct FlowField(pub HashMap<TileCoordinates, TileCoordinates>);
+pub struct FlowFields(pub HashMap<TileCoordinates, FlowField>);
+impl FlowFields {
+    pub fn next_tile(
+        &self,
+        goal: TileCoordinates,
+        tile: TileCoordinates,
+    ) -> Option<TileCoordinates> {
+        self.0.get(&goal)?.next_tile(tile)
+    }
+
+    /// furthest tile of the next SMOOTHING_LOOKAHEAD tiles of the field that the unit can walk to in a straight line
+    /// so the units cut across open ground instead of following the tiles one by one
+    pub fn farthest_visible_tile(
+        &self,
+        goal: TileCoordinates,
+        position: AbsoluteCoordinates,
+        walkability: &WalkabilityGrid,
+    ) -> Option<TileCoordinates> {
+        let flow_field = self.0.get(&goal)?;
+        let mut farthest = flow_field.next_tile(absolute_coord_to_tile_coord(position))?;
+        let mut next = farthest;
+        for _ in 1..SMOOTHING_LOOKAHEAD {
+            match flow_field.next_tile(next) {
+                Some(tile) if has_line_of_sight(position, tile, walkability) => {
+                    farthest = tile;
+                    next = tile;
+                }
+                _ => break,
+            }
+        }
+        Some(farthest)
+    }
+}
+
+/// whether a unit can walk in a straight line from a position to the center of a tile without touching an unwalkable tile
+pub fn has_line_of_sight(
+    from: AbsoluteCoordinates,
+    to: TileCoordinates,
+    walkability: &WalkabilityGrid,
+) -> bool {
+    let from: Vec2 = from.into();
+    let to: Vec2 = tile_coord_to_absolute_coord(to).into();
+    // the two sides of the unit follow the line too
+    let side = (to - from).normalize_or_zero().perp() * UNIT_DEFAULT_SIZE / 2.0;
+    let steps = ((to - from).length() / LINE_OF_SIGHT_STEP).ceil().max(1.0) as u32;
+    (0..=steps).all(|step| {
+        let point = from.lerp(to, step as f32 / steps as f32);
+        [point, point + side, point - side]
+            .into_iter()
+            .all(|point| {
+                walkability.is_walkable(absolute_coord_to_tile_coord(AbsoluteCoordinates {
+                    x: point.x,
+                    y: point.y,
+                }))
+            })
+    })
+}
 
-#[derive(Message, Default)]
-pub struct RecalculateFlowField;
+/// where a unit wants to go
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Goal {
+    #[default]
+    Idle,
+    FollowPlayer,
+    Tile(TileCoordinates),
+    /// an entity of the map (machine, ore, chest...), reached from the closest walkable tile next to it
+    #[serde(skip)]
+    Entity(Entity),
+    /// goes from random point to random point around the center
+    Wander {
+        center: TileCoordinates,
+        radius: i32,
+        point: Option<TileCoordinates>,
+    },
+}
+
+/// tile the unit is heading to this tick, resolved from its Goal, None when the goal cannot be reached
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct GoalTile(pub Option<TileCoordinates>);
 
 impl Plugin for PathfindingPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(FlowField::default())
-            .add_message::<RecalculateFlowField>()
-            .add_systems(FixedUpdate, calculate_flow_field_system);
+        app.init_resource::<FlowFields>()
+            .init_resource::<PortalGraph>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    resolve_goal_tiles_system,
+                    update_portal_graph_system,
+                    follow_long_paths_system,
+                    update_flow_fields_system,
+                )
+                    .chain(),
+            );
     }
 }
 
-pub fn calculate_flow_field_system(
-    mut message_recalculate: MessageReader<RecalculateFlowField>,
-    mut flow_field: ResMut<FlowField>,
-    map_manager: Res<MapManager>,
+pub fn resolve_goal_tiles_system(
+    mut unit_query: Query<(&mut Goal, &mut GoalTile, &Transform), With<Unit>>,
     player_query: Query<&Transform, With<Player>>,
-    chunk_query: Query<&StructureLayerManager, With<TilemapChunk>>,
+    target_query: Query<&Transform>,
+    map_manager: Res<MapManager>,
+    map_seed: Res<MapSeed>,
+    simulation_tick: Res<SimulationTick>,
 ) {
-    if message_recalculate.is_empty() {
-        return;
-    }
-    message_recalculate.clear();
-
-    let Ok(transform) = player_query.single() else {
-        return;
-    };
-    let goal = absolute_coord_to_tile_coord((*transform).into());
-
-    // TODO: regarder si on devrait utiliser dijkstra_partial ou dijkstra_reach
-    let cost_map = dijkstra_all(&goal, |&tile| {
-        let mut neighbors = Vec::with_capacity(8);
-        for y in -1..=1 {
-            for x in -1..=1 {
-                if x == 0 && y == 0 {
-                    continue;
-                }
+    for (mut goal, mut goal_tile, transform) in unit_query.iter_mut() {
+        let current_tile = absolute_coord_to_tile_coord((*transform).into());
+        let tile = match *goal {
+            Goal::Idle => None,
+            Goal::FollowPlayer => player_query
+                .single()
+                .ok()
+                .map(|player_transform| absolute_coord_to_tile_coord((*player_transform).into())),
+            Goal::Tile(tile) => Some(tile),
+            Goal::Entity(target) => target_query.get(target).ok().and_then(|target_transform| {
+                closest_walkable_tile(
+                    absolute_coord_to_tile_coord((*target_transform).into()),
+                    current_tile,
+                    &map_manager,
+                )
+            }),
+            Goal::Wander {
+                center,
+                radius,
+                point,
+            } => {
+                let point = match point {
+                    Some(point) if point != current_tile => Some(point),
+                    // a new point each time one is reached, unwalkable points are drawn again next tick
+                    _ => {
+                        let mut rng = wander_rng(map_seed.0, simulation_tick.0, current_tile);
+                        let point = TileCoordinates {
+                            x: center.x + rng.random_range(-radius..=radius),
+                            y: center.y + rng.random_range(-radius..=radius),
+                        };
+                        map_manager.is_tile_walkable(point).then_some(point)
+                    }
+                };
+                *goal = Goal::Wander {
+                    center,
+                    radius,
+                    point,
+                };
+                point
+            }
+        };
+        if goal_tile.0 != tile {
+            goal_tile.0 = tile;
+        }
+    }
+}
 
-                if x != 0 && y != 0 {
-                    continue;
-                }
+/// whether a flow field computed for the goal covers the tile
+pub fn is_in_flow_field_range(tile: TileCoordinates, goal: TileCoordinates) -> bool {
+    (tile.x - goal.x).abs() <= FLOWFIELD_RADIUS && (tile.y - goal.y).abs() <= FLOWFIELD_RADIUS
+}
 
-                let neighbor_tile = TileCoordinates {
-                    x: tile.x + x,
-                    y: tile.y + y,
-                };
+/// walkable tile closest to `from` around a target, the target itself when it is walkable
+pub fn closest_walkable_tile(
+    target: TileCoordinates,
+    from: TileCoordinates,
+    map_manager: &MapManager,
+) -> Option<TileCoordinates> {
+    (-GOAL_SEARCH_RADIUS..=GOAL_SEARCH_RADIUS)
+        .flat_map(|y| {
+            (-GOAL_SEARCH_RADIUS..=GOAL_SEARCH_RADIUS).map(move |x| TileCoordinates {
+                x: target.x + x,
+                y: target.y + y,
+            })
+        })
+        .filter(|tile| map_manager.is_tile_walkable(*tile))
+        .min_by_key(|tile| {
+            let to_target = (tile.x - target.x).abs() + (tile.y - target.y).abs();
+            let to_from = (tile.x - from.x).abs() + (tile.y - from.y).abs();
+            (to_target, to_from, tile.y, tile.x)
+        })
+}
 
-                // Vérifier que le voisin est dans le rayon ET praticable
-                let dx = (neighbor_tile.x - goal.x).abs();
-                let dy = (neighbor_tile.y - goal.y).abs();
+fn wander_rng(seed: u64, tick: u64, tile: TileCoordinates) -> StdRng {
+    StdRng::seed_from_u64(
+        seed ^ tick.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (tile.x as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (tile.y as i64 as u64).wrapping_mul(0x1656_67B1_9E37_79F9),
+    )
+}
 
-                if dx > FLOWFIELD_RADIUS || dy > FLOWFIELD_RADIUS {
-                    continue;
-                }
+/// repairs the cached flow fields where the walkable tiles changed and computes the fields of the new goals
+pub fn update_flow_fields_system(
+    mut flow_fields: ResMut<FlowFields>,
+    waypoint_query: Query<&Waypoint>,
+    mut map_manager: ResMut<MapManager>,
+) {
+    let changes = map_manager.walkability.take_changes();
+    if !changes.is_empty() {
+        for flow_field in flow_fields.0.values_mut() {
+            flow_field.repair(&changes, &map_manager.walkability);
+        }
+    }
 
-                // 1. Vérifier si la tuile de destination est marchable
-                if !map_manager.is_tile_walkable(neighbor_tile, &chunk_query) {
-                    continue;
-                }
+    let mut goals: Vec<TileCoordinates> = waypoint_query
+        .iter()
+        .filter_map(|waypoint| waypoint.0)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    // same order every run so the same fields are computed first
+    goals.sort_by_key(|goal| (goal.y, goal.x));
 
-                // 2. NOUVELLE VÉRIFICATION : Empêcher de couper les coins
-                if x != 0 && y != 0 {
-                    // C'est un mouvement diagonal
-                    let adjacent_1 = TileCoordinates {
-                        x: tile.x + x,
-                        y: tile.y,
-                    };
-                    let adjacent_2 = TileCoordinates {
-                        x: tile.x,
-                        y: tile.y + y,
-                    };
-
-                    if !map_manager.is_tile_walkable(adjacent_1, &chunk_query)
-                        || !map_manager.is_tile_walkable(adjacent_2, &chunk_query)
-                    {
-                        // L'un des coins est un mur, on ne peut pas passer
-                        continue;
-                    }
-                }
+    flow_fields.0.retain(|goal, _| goals.contains(goal));
+    goals.retain(|goal| !flow_fields.0.contains_key(goal));
+    for goal in goals.into_iter().take(MAX_FLOW_FIELDS_PER_TICK) {
+        let flow_field = FlowField::compute(goal, &map_manager.walkability);
+        flow_fields.0.insert(goal, flow_field);
+    }
+}
+
+pub fn neighbors(tile: TileCoordinates) -> impl Iterator<Item = TileCoordinates> {
+    NEIGHBORS.into_iter().map(move |delta| TileCoordinates {
+        x: tile.x + delta.x,
+        y: tile.y + delta.y,
+    })
+}
+
+/// cost of a step between two neighbor tiles, None when the tile stepped on is not walkable
+/// or when the step is a diagonal cutting the corner of an unwalkable tile
+pub fn step_cost(
+    from: TileCoordinates,
+    to: TileCoordinates,
+    walkability: &WalkabilityGrid,
+) -> Option<u32> {
+    if !walkability.is_walkable(to) {
+        return None;
+    }
+    if from.x == to.x || from.y == to.y {
+        return Some(STRAIGHT_COST);
+    }
+    (walkability.is_walkable(TileCoordinates { x: to.x, y: from.y })
+        && walkability.is_walkable(TileCoordinates { x: from.x, y: to.y }))
+    .then_some(DIAGONAL_COST)
+}
 
-                // Si on arrive ici, le mouvement est valide
-                let cost = if x == 0 || y == 0 { 10 } else { 14 };
-                neighbors.push((neighbor_tile, cost));
+/// cost of the straight line between two tiles, the lowest walking cost when nothing is in the way
+pub fn straight_line_cost(from: TileCoordinates, to: TileCoordinates) -> u32 {
+    let dx = from.x.abs_diff(to.x);
+    let dy = from.y.abs_diff(to.y);
+    dx.min(dy) * DIAGONAL_COST + dx.abs_diff(dy) * STRAIGHT_COST
+}
+
+/// tiles waiting to spread their cost, the lowest cost first
+pub type CostQueue = BinaryHeap<Reverse<(u32, i32, i32)>>;
+
+impl FlowField {
+    fn index(&self, tile: TileCoordinates) -> Option<usize> {
+        let x = tile.x - self.goal.x + FLOWFIELD_RADIUS;
+        let y = tile.y - self.goal.y + FLOWFIELD_RADIUS;
+        ((0..FLOWFIELD_SIZE).contains(&x) && (0..FLOWFIELD_SIZE).contains(&y))
+            .then_some((y * FLOWFIELD_SIZE + x) as usize)
+    }
+
+    /// None when the tile cannot reach the goal
+    pub fn cost(&self, tile: TileCoordinates) -> Option<u32> {
+        self.index(tile)
+            .map(|index| self.costs[index])
+            .filter(|cost| *cost != u32::MAX)
+    }
+
+    pub fn next_tile(&self, tile: TileCoordinates) -> Option<TileCoordinates> {
+        let cost = self.cost(tile)?;
+        neighbors(tile)
+            // the corners of a walkable diagonal are walkable tiles next to the tile, so they have a cost too
+            .filter(|neighbor| {
+                neighbor.x == tile.x
+                    || neighbor.y == tile.y
+                    || (self
+                        .cost(TileCoordinates {
+                            x: neighbor.x,
+                            y: tile.y,
+                        })
+                        .is_some()
+                        && self
+                            .cost(TileCoordinates {
+                                x: tile.x,
+                                y: neighbor.y,
+                            })
+                            .is_some())
+            })
+            .filter_map(|neighbor| Some((self.cost(neighbor)?, neighbor)))
+            .filter(|(neighbor_cost, _)| *neighbor_cost < cost)
+            .min_by_key(|(neighbor_cost, _)| *neighbor_cost)
+            .map(|(_, neighbor)| neighbor)
+    }
+
+    /// Dijkstra from the goal over the walkable tiles within FLOWFIELD_RADIUS
+    pub fn compute(goal: TileCoordinates, walkability: &WalkabilityGrid) -> Self {
+        let mut flow_field = Self {
+            goal,
+            costs: vec![u32::MAX; (FLOWFIELD_SIZE * FLOWFIELD_SIZE) as usize],
+        };
+        let goal_index = flow_field.index(goal).unwrap();
+        flow_field.costs[goal_index] = 0;
+        let mut queue = CostQueue::new();
+        queue.push(Reverse((0, goal.y, goal.x)));
+        flow_field.propagate(queue, walkability);
+        flow_field
+    }
+
+    fn propagate(&mut self, mut queue: CostQueue, walkability: &WalkabilityGrid) {
+        while let Some(Reverse((cost, y, x))) = queue.pop() {
+            let tile = TileCoordinates { x, y };
+            if self.cost(tile) != Some(cost) {
+                // already reached with a lower cost
+                continue;
+            }
+            for neighbor in neighbors(tile) {
+                let Some(index) = self.index(neighbor) else {
+                    continue;
+                };
+                let Some(step_cost) = step_cost(tile, neighbor, walkability) else {
+                    continue;
+                };
+                let neighbor_cost = cost + step_cost;
+                if neighbor_cost < self.costs[index] {
+                    self.costs[index] = neighbor_cost;
+                    queue.push(Reverse((neighbor_cost, neighbor.y, neighbor.x)));
+                }
             }
         }
+    }
 
-        neighbors
-    });
+    /// lowest cost the tile can get from its neighbors
+    fn cost_from_neighbors(
+        &self,
+        tile: TileCoordinates,
+        walkability: &WalkabilityGrid,
+    ) -> Option<u32> {
+        neighbors(tile)
+            .filter_map(|neighbor| {
+                Some(self.cost(neighbor)? + step_cost(neighbor, tile, walkability)?)
+            })
+            .min()
+    }
 
-    flow_field.0.clear();
-    for y in (goal.y - FLOWFIELD_RADIUS)..=(goal.y + FLOWFIELD_RADIUS) {
-        for x in (goal.x - FLOWFIELD_RADIUS)..=(goal.x + FLOWFIELD_RADIUS) {
+    /// updates the costs around the tiles whose walkability changed, instead of running Dijkstra on the whole field
+    pub fn repair(&mut self, changes: &[TileCoordinates], walkability: &WalkabilityGrid) {
+        // 1. the tiles whose cost came from a tile now blocked, or from a diagonal now cutting its corner, lose their cost
+        // lowest costs first, so the neighbors a tile can take its cost from are already checked
+        let mut candidates = CostQueue::new();
+        for tile in changes
+            .iter()
+            .filter(|tile| !walkability.is_walkable(**tile))
+        {
+            for candidate in std::iter::once(*tile).chain(neighbors(*tile)) {
+                if let Some(cost) = self.cost(candidate) {
+                    candidates.push(Reverse((cost, candidate.y, candidate.x)));
+                }
+            }
+        }
+        let mut invalidated = Vec::new();
+        while let Some(Reverse((cost, y, x))) = candidates.pop() {
             let tile = TileCoordinates { x, y };
-
-            if tile == goal {
+            if tile == self.goal
+                || self.cost(tile) != Some(cost)
+                || (walkability.is_walkable(tile)
+                    && self.cost_from_neighbors(tile, walkability) == Some(cost))
+            {
                 continue;
             }
+            let index = self.index(tile).unwrap();
+            self.costs[index] = u32::MAX;
+            invalidated.push(tile);
+            for neighbor in neighbors(tile) {
+                if let Some(neighbor_cost) = self.cost(neighbor)
+                    && neighbor_cost > cost
+                {
+                    candidates.push(Reverse((neighbor_cost, neighbor.y, neighbor.x)));
+                }
+            }
+        }
 
-            if !map_manager.is_tile_walkable(tile, &chunk_query) {
+        // 2. they take their cost back from their neighbors, like the tiles now walkable and the diagonals they open
+        let mut queue = CostQueue::new();
+        let reopened = changes
+            .iter()
+            .filter(|tile| walkability.is_walkable(**tile))
+            .flat_map(|tile| std::iter::once(*tile).chain(neighbors(*tile)))
+            .chain(invalidated);
+        for tile in reopened {
+            let Some(index) = self.index(tile) else {
+                continue;
+            };
+            if tile == self.goal || !walkability.is_walkable(tile) {
                 continue;
             }
+            let Some(cost) = self.cost_from_neighbors(tile, walkability) else {
+                continue;
+            };
+            if cost < self.costs[index] {
+                self.costs[index] = cost;
+                queue.push(Reverse((cost, tile.y, tile.x)));
+            }
+        }
+        self.propagate(queue, walkability);
+    }
+}
 
-            let mut best_neighbor = tile;
-            let mut min_cost = cost_map.get(&tile).map_or(u32::MAX, |&(_, cost)| cost);
-
-            for dy in -1..=1 {
-                for dx in -1..=1 {
-                    if dx == 0 && dy == 0 {
-                        continue;
-                    }
-
-                    if dx != 0 && dy != 0 {
-                        continue;
-                    }
-
-                    let neighbor_tile = TileCoordinates {
-                        x: tile.x + dx,
-                        y: tile.y + dy,
-                    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{ChunkCoordinates, LocalTileCoordinates};
+    use rand::seq::IteratorRandom;
 
-                    if let Some((_, neighbor_cost)) = cost_map.get(&neighbor_tile) {
-                        if *neighbor_cost < min_cost {
-                            min_cost = *neighbor_cost;
-                            best_neighbor = neighbor_tile;
-                        }
-                    }
-                }
+    #[test]
+    fn test_repair_matches_a_full_computation() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut walkability = WalkabilityGrid::default();
+        for y in -2..2 {
+            for x in -2..2 {
+                let blocked_tiles: Vec<LocalTileCoordinates> = (0..32)
+                    .flat_map(|y| (0..32).map(move |x| LocalTileCoordinates { x, y }))
+                    .filter(|_| rng.random_bool(0.25))
+                    .collect();
+                walkability.insert_chunk(ChunkCoordinates { x, y }, blocked_tiles);
             }
+        }
+        walkability.take_changes();
 
-            // si on a trouvé un chemin vers le player
-            if best_neighbor != tile {
-                flow_field.0.insert(tile, best_neighbor);
+        let goal = TileCoordinates { x: 3, y: 3 };
+        walkability.set_walkable(goal, true);
+        let mut flow_field = FlowField::compute(goal, &walkability);
+        for _ in 0..20 {
+            // blocks and opens a few tiles near the goal, then repairs the field
+            for tile in (-20..20)
+                .flat_map(|y| (-20..20).map(move |x| TileCoordinates { x, y }))
+                .choose_multiple(&mut rng, 30)
+            {
+                let is_walkable = walkability.is_walkable(tile);
+                walkability.set_walkable(tile, !is_walkable);
             }
+            flow_field.repair(&walkability.take_changes(), &walkability);
+            assert_eq!(flow_field, FlowField::compute(goal, &walkability));
         }
     }
+
+    #[test]
+    fn test_diagonals_do_not_cut_corners() {
+        let mut walkability = WalkabilityGrid::default();
+        walkability.insert_chunk(ChunkCoordinates { x: 0, y: 0 }, []);
+        let goal = TileCoordinates { x: 0, y: 0 };
+        let tile = TileCoordinates { x: 5, y: 5 };
+
+        let flow_field = FlowField::compute(goal, &walkability);
+        assert_eq!(flow_field.cost(tile), Some(5 * DIAGONAL_COST));
+        assert_eq!(
+            flow_field.next_tile(tile),
+            Some(TileCoordinates { x: 4, y: 4 })
+        );
+
+        walkability.set_walkable(TileCoordinates { x: 4, y: 5 }, false);
+        let flow_field = FlowField::compute(goal, &walkability);
+        assert_eq!(
+            flow_field.next_tile(tile),
+            Some(TileCoordinates { x: 5, y: 4 })
+        );
+
+        let position = tile_coord_to_absolute_coord(tile);
+        assert!(has_line_of_sight(
+            position,
+            TileCoordinates { x: 5, y: 0 },
+            &walkability
+        ));
+        assert!(!has_line_of_sight(
+            position,
+            TileCoordinates { x: 0, y: 5 },
+            &walkability
+        ));
+    }
 }
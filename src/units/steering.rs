@@ -0,0 +1,187 @@
+use crate::{
+    map::{
+        AbsoluteCoordinates, MapManager, TILE_SIZE, absolute_coord_to_tile_coord,
+        tile_coord_to_absolute_coord, walkability::WalkabilityGrid,
+    },
+    units::{Direction, Player, worker::Flying},
+};
+use avian2d::prelude::LinearVelocity;
+use bevy::prelude::*;
+
+/// units closer than this push each other away
+const SEPARATION_RADIUS: f32 = TILE_SIZE.x * 1.2;
+/// units closer than this move along with each other
+const ALIGNMENT_RADIUS: f32 = TILE_SIZE.x * 2.5;
+/// distance looked ahead of a unit for unwalkable tiles
+const OBSTACLE_LOOKAHEAD: f32 = TILE_SIZE.x * 0.9;
+/// angle between the forward probe and the side probes of the obstacle avoidance
+const OBSTACLE_PROBE_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+/// a unit slows down within this distance of its goal
+pub const ARRIVAL_DISTANCE: f32 = TILE_SIZE.x * 1.5;
+const SEPARATION_WEIGHT: f32 = 1.5;
+const ALIGNMENT_WEIGHT: f32 = 0.3;
+const OBSTACLE_WEIGHT: f32 = 1.0;
+/// fraction of its max speed a unit can gain or lose in one second
+const ACCELERATION: f32 = 8.0;
+/// below this speed the sprite keeps facing the same way, in pixels per second
+const FACING_SPEED: f32 = TILE_SIZE.x * 0.2;
+
+/// velocity a unit wants to move at, set by its AI, the steering adds the crowd and the walls to it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Steering {
+    pub desired_velocity: Vec2,
+    /// the velocity of the unit never goes above it, in pixels per second
+    pub max_speed: f32,
+}
+
+/// speed to reach a point without overshooting it, full speed until ARRIVAL_DISTANCE
+pub fn arrival_speed(max_speed: f32, distance: f32) -> f32 {
+    max_speed * (distance / ARRIVAL_DISTANCE).min(1.0)
+}
+
+/// pushes away from the units too close, stronger the closer they are
+fn separation(position: Vec2, neighbors: &[(Vec2, Vec2)]) -> Vec2 {
+    neighbors
+        .iter()
+        .map(|(neighbor_position, _)| position - *neighbor_position)
+        .filter(|offset| offset.length() < SEPARATION_RADIUS)
+        .map(|offset| {
+            // units on the same point split in an arbitrary but stable direction
+            let away = offset.try_normalize().unwrap_or(Vec2::X);
+            away * (1.0 - offset.length() / SEPARATION_RADIUS)
+        })
+        .sum()
+}
+
+/// difference between the average velocity of the units around and the velocity of the unit
+fn alignment(position: Vec2, velocity: Vec2, neighbors: &[(Vec2, Vec2)]) -> Vec2 {
+    let (sum, count) = neighbors
+        .iter()
+        .filter(|(neighbor_position, _)| neighbor_position.distance(position) < ALIGNMENT_RADIUS)
+        .fold((Vec2::ZERO, 0), |(sum, count), (_, neighbor_velocity)| {
+            (sum + *neighbor_velocity, count + 1)
+        });
+    if count == 0 {
+        return Vec2::ZERO;
+    }
+    sum / count as f32 - velocity
+}
+
+/// pushes away from the unwalkable tiles found ahead and on both sides of the heading
+fn obstacle_avoidance(position: Vec2, heading: Vec2, walkability: &WalkabilityGrid) -> Vec2 {
+    let Some(heading) = heading.try_normalize() else {
+        return Vec2::ZERO;
+    };
+    [0.0, OBSTACLE_PROBE_ANGLE, -OBSTACLE_PROBE_ANGLE]
+        .into_iter()
+        .map(|angle| position + Vec2::from_angle(angle).rotate(heading) * OBSTACLE_LOOKAHEAD)
+        .filter_map(|probe| {
+            let tile = absolute_coord_to_tile_coord(AbsoluteCoordinates {
+                x: probe.x,
+                y: probe.y,
+            });
+            (!walkability.is_walkable(tile)).then(|| {
+                let tile_center: Vec2 = tile_coord_to_absolute_coord(tile).into();
+                (position - tile_center).normalize_or_zero()
+            })
+        })
+        .sum()
+}
+
+/// velocity of a unit after one step of steering toward the target velocity, within its acceleration and max speed
+pub fn steer(velocity: Vec2, target_velocity: Vec2, max_speed: f32, delta_secs: f32) -> Vec2 {
+    let max_change = max_speed * ACCELERATION * delta_secs;
+    (velocity + (target_velocity - velocity).clamp_length_max(max_change))
+        .clamp_length_max(max_speed)
+}
+
+/// turns the desired velocity of each unit into its velocity, avoiding the other units and the walls
+pub fn steer_units_system(
+    mut unit_query: Query<(
+        Entity,
+        &Transform,
+        &Steering,
+        &mut LinearVelocity,
+        &mut Direction,
+    )>,
+    player_query: Query<(), With<Player>>,
+    flying_query: Query<(), With<Flying>>,
+    map_manager: Res<MapManager>,
+    time: Res<Time<Fixed>>,
+) {
+    let units: Vec<(Entity, Vec2, Vec2)> = unit_query
+        .iter()
+        .map(|(entity, transform, _, velocity, _)| (entity, transform.translation.xy(), velocity.0))
+        .collect();
+
+    for (entity, transform, steering, mut velocity, mut direction) in unit_query.iter_mut() {
+        // the player moves with the keyboard
+        if player_query.contains(entity) {
+            continue;
+        }
+        let position = transform.translation.xy();
+        let neighbors: Vec<(Vec2, Vec2)> = units
+            .iter()
+            .filter(|(other, other_position, _)| {
+                *other != entity && other_position.distance(position) < ALIGNMENT_RADIUS
+            })
+            .map(|(_, other_position, other_velocity)| (*other_position, *other_velocity))
+            .collect();
+
+        let mut target_velocity = steering.desired_velocity
+            + separation(position, &neighbors) * steering.max_speed * SEPARATION_WEIGHT;
+        // units waiting for a path stay where they are instead of drifting with the crowd
+        if steering.desired_velocity != Vec2::ZERO {
+            target_velocity += alignment(position, velocity.0, &neighbors) * ALIGNMENT_WEIGHT;
+            // the flying units pass over the walls
+            if !flying_query.contains(entity) {
+                target_velocity += obstacle_avoidance(
+                    position,
+                    steering.desired_velocity,
+                    &map_manager.walkability,
+                ) * steering.max_speed
+                    * OBSTACLE_WEIGHT;
+            }
+        }
+        velocity.0 = steer(
+            velocity.0,
+            target_velocity,
+            steering.max_speed,
+            time.delta_secs(),
+        );
+
+        if velocity.length() > FACING_SPEED {
+            *direction = Direction::from_movement(velocity.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steering_separates_slows_down_and_clamps() {
+        let position = Vec2::ZERO;
+        let neighbors = [(Vec2::new(TILE_SIZE.x * 0.5, 0.0), Vec2::ZERO)];
+        assert!(separation(position, &neighbors).x < 0.0);
+        assert_eq!(
+            separation(position, &[(Vec2::new(SEPARATION_RADIUS, 0.0), Vec2::ZERO)]),
+            Vec2::ZERO
+        );
+
+        assert_eq!(arrival_speed(100.0, ARRIVAL_DISTANCE * 2.0), 100.0);
+        assert_eq!(arrival_speed(100.0, ARRIVAL_DISTANCE / 2.0), 50.0);
+        assert_eq!(arrival_speed(100.0, 0.0), 0.0);
+
+        // a unit at rest needs a few steps to reach its max speed and never goes above it
+        let mut velocity = Vec2::ZERO;
+        let target = Vec2::new(1000.0, 0.0);
+        velocity = steer(velocity, target, 100.0, 1.0 / 30.0);
+        assert!(velocity.x > 0.0 && velocity.x < 100.0);
+        for _ in 0..30 {
+            velocity = steer(velocity, target, 100.0, 1.0 / 30.0);
+        }
+        assert_eq!(velocity, Vec2::new(100.0, 0.0));
+    }
+}
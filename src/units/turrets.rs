@@ -0,0 +1,233 @@
+use crate::{
+    UPS_TARGET,
+    items::{
+        ItemType,
+        inventory::{InputInventory, Inventory, ItemStack},
+        statistics::ProductionStatistics,
+    },
+    map::{
+        Footprint, STRUCTURE_LAYER, SpritePath, Structure, TILE_SIZE, TileCoordinates,
+        machine::{Machine, MachineBaseBundle},
+        ports::Ports,
+    },
+    simulation::SimulationTick,
+    units::{
+        Direction,
+        combat::{Damage, Health, Hostile, apply_damage_system, attack_system},
+    },
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// in tiles, from the center of the turret
+const TURRET_RANGE: f32 = 10.0;
+const TURRET_DAMAGE: f32 = 10.0;
+const TURRET_COOLDOWN_TICKS: u64 = UPS_TARGET as u64 / 2;
+/// rounds shot with one Ammo item
+pub const ROUNDS_PER_AMMO: u32 = 10;
+
+pub struct TurretsPlugin;
+
+impl Plugin for TurretsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            turret_system
+                .after(attack_system)
+                .before(apply_damage_system),
+        );
+    }
+}
+
+/// which hostile unit in range a turret shoots first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TurretTargeting {
+    #[default]
+    Nearest,
+    /// the lowest health, to kill as fast as possible
+    Weakest,
+}
+
+/// structure shooting the hostile units in range with the Ammo of its InputInventory
+/// it is a Machine so the ports and the workers feed it like the others
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[require(InputInventory)]
+pub struct Turret {
+    pub targeting: TurretTargeting,
+    pub last_shot_tick: Option<u64>,
+    /// rounds left in the loaded magazine
+    pub rounds: u32,
+    /// the unit being shot, not saved
+    #[serde(skip)]
+    pub target: Option<Entity>,
+}
+impl Turret {
+    pub fn new(targeting: TurretTargeting) -> Self {
+        Self {
+            targeting,
+            last_shot_tick: None,
+            rounds: 0,
+            target: None,
+        }
+    }
+
+    pub fn is_ready(&self, tick: u64) -> bool {
+        self.last_shot_tick
+            .is_none_or(|last_shot_tick| tick >= last_shot_tick + TURRET_COOLDOWN_TICKS)
+    }
+
+    /// the hostile unit to shoot among (entity, position, health), the oldest entity breaks the ties
+    pub fn choose_target(
+        &self,
+        position: Vec2,
+        hostiles: &[(Entity, Vec2, f32)],
+    ) -> Option<Entity> {
+        hostiles
+            .iter()
+            .map(|(entity, hostile_position, health)| {
+                (*entity, position.distance(*hostile_position), *health)
+            })
+            .filter(|(_, distance, _)| *distance <= TURRET_RANGE * TILE_SIZE.x)
+            .min_by(|(a, a_distance, a_health), (b, b_distance, b_health)| {
+                let order = match self.targeting {
+                    TurretTargeting::Nearest => a_distance.total_cmp(b_distance),
+                    TurretTargeting::Weakest => a_health
+                        .total_cmp(b_health)
+                        .then(a_distance.total_cmp(b_distance)),
+                };
+                order.then(a.cmp(b))
+            })
+            .map(|(entity, ..)| entity)
+    }
+
+    /// loads an Ammo item of the inventory when the magazine is empty, returns the item consumed
+    pub fn reload(&mut self, inventory: &mut Inventory) -> Option<ItemStack> {
+        if self.rounds > 0 {
+            return None;
+        }
+        let ammo = *inventory
+            .slots
+            .iter()
+            .find(|slot| slot.item_type == ItemType::Ammo)?;
+        let ammo = ItemStack {
+            quantity: 1,
+            ..ammo
+        };
+        inventory.remove_quantity(ammo);
+        self.rounds = ROUNDS_PER_AMMO;
+        Some(ammo)
+    }
+}
+
+pub fn spawn_turret(
+    commands: &mut Commands,
+    tile_coord: TileCoordinates,
+    turret: Turret,
+    input_inventory: InputInventory,
+) -> Entity {
+    let footprint = Footprint::default();
+    let target_coord = footprint.anchor_to_absolute_coord(tile_coord, Direction::North);
+    commands
+        .spawn((
+            MachineBaseBundle {
+                name: Name::new("Turret"),
+                structure: Structure,
+                direction: Direction::North,
+                transform: Transform::from_xyz(target_coord.x, target_coord.y, STRUCTURE_LAYER),
+                machine: Machine::default(),
+            },
+            turret,
+            input_inventory,
+            Ports::turret(),
+            // until the turret has its own sprite
+            SpritePath::structure("default_machine.png"),
+        ))
+        .id()
+}
+
+/// the hostile units that the turrets shoot at
+type TurretTarget = (With<Hostile>, Without<Turret>);
+
+/// each turret turns to its target and shoots it while it has rounds, reloading from its InputInventory
+pub fn turret_system(
+    mut turret_query: Query<(&mut Transform, &mut Turret, &mut InputInventory)>,
+    hostile_query: Query<(Entity, &Transform, &Health), TurretTarget>,
+    simulation_tick: Res<SimulationTick>,
+    mut statistics: ResMut<ProductionStatistics>,
+    mut message_damage: MessageWriter<Damage>,
+) {
+    let hostiles: Vec<(Entity, Vec2, f32)> = hostile_query
+        .iter()
+        .map(|(entity, transform, health)| (entity, transform.translation.xy(), health.current))
+        .collect();
+
+    for (mut transform, mut turret, mut input_inventory) in turret_query.iter_mut() {
+        let position = transform.translation.xy();
+        turret.target = turret.choose_target(position, &hostiles);
+        let Some(target) = turret.target else {
+            continue;
+        };
+        // the sprite faces North by default
+        if let Ok((_, target_transform, _)) = hostile_query.get(target) {
+            let angle = (target_transform.translation.xy() - position).to_angle() - FRAC_PI_2;
+            transform.rotation = Quat::from_rotation_z(angle);
+        }
+
+        if !turret.is_ready(simulation_tick.0) {
+            continue;
+        }
+        if let Some(ammo) = turret.reload(&mut input_inventory.0) {
+            statistics.record_consumed(&[ammo]);
+        }
+        if turret.rounds == 0 {
+            continue;
+        }
+        turret.rounds -= 1;
+        turret.last_shot_tick = Some(simulation_tick.0);
+        message_damage.write(Damage {
+            target,
+            amount: TURRET_DAMAGE,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Quality;
+
+    #[test]
+    fn test_turrets_pick_their_target_and_reload_from_their_ammo() {
+        let entity = |index| Entity::from_raw_u32(index).unwrap();
+        let hostiles = [
+            (entity(1), Vec2::new(TILE_SIZE.x * 2.0, 0.0), 80.0),
+            (entity(2), Vec2::new(TILE_SIZE.x * 6.0, 0.0), 20.0),
+            // weaker but out of range
+            (entity(3), Vec2::new(TILE_SIZE.x * 20.0, 0.0), 5.0),
+        ];
+        let nearest = Turret::new(TurretTargeting::Nearest);
+        let weakest = Turret::new(TurretTargeting::Weakest);
+        assert_eq!(
+            nearest.choose_target(Vec2::ZERO, &hostiles),
+            Some(entity(1))
+        );
+        assert_eq!(
+            weakest.choose_target(Vec2::ZERO, &hostiles),
+            Some(entity(2))
+        );
+        assert_eq!(weakest.choose_target(Vec2::ZERO, &hostiles[2..]), None);
+
+        let mut turret = nearest;
+        let mut inventory = Inventory::default();
+        assert_eq!(turret.reload(&mut inventory), None);
+        inventory
+            .add(ItemStack::new(ItemType::Ammo, Quality::Standard, 2))
+            .unwrap();
+        assert!(turret.reload(&mut inventory).is_some());
+        assert_eq!(turret.rounds, ROUNDS_PER_AMMO);
+        // the magazine is not empty yet
+        assert_eq!(turret.reload(&mut inventory), None);
+        assert_eq!(inventory.quantity(ItemType::Ammo), 1);
+    }
+}
@@ -1,14 +1,24 @@
 use crate::{
+    environment::weather::Weather,
     map::{
-        AbsoluteCoordinates, TILE_SIZE, absolute_coord_to_tile_coord, tile_coord_to_absolute_coord,
+        AbsoluteCoordinates, Coordinates, MapManager, SpritePath, TILE_SIZE,
+        coord_to_absolute_coord, tile_coord_to_absolute_coord,
+    },
+    units::{
+        combat::Health,
+        emotions::{Behavior, Emotions},
+        hierarchical::{LongPath, Waypoint},
+        pathfinding::{FlowFields, Goal, GoalTile},
+        steering::{Steering, arrival_speed, steer_units_system},
+        worker::Flying,
     },
-    units::pathfinding::{FlowField, RecalculateFlowField},
 };
 use avian2d::prelude::{
-    CoefficientCombine, Collider, Forces, Friction, LinearDamping, LinearVelocity, LockedAxes,
-    RigidBody, RigidBodyForces, TranslationInterpolation,
+    CoefficientCombine, Collider, Friction, LinearVelocity, LockedAxes, RigidBody,
+    TranslationInterpolation,
 };
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub const UNIT_REACH: f32 = 1.0;
 pub const UNIT_DEFAULT_SIZE: f32 = TILE_SIZE.x * 0.8;
@@ -24,7 +34,7 @@ impl Plugin for UnitsPlugin {
             FixedUpdate,
             (
                 player_control_system,
-                units_follow_field_system,
+                (units_follow_field_system, steer_units_system).chain(),
                 update_sprite_facing_system,
                 // apply_floor_friction_system,
             ),
@@ -39,6 +49,12 @@ impl Plugin for UnitsPlugin {
     Transform,
     Direction,
     Speed,
+    Steering,
+    Goal,
+    GoalTile,
+    Waypoint,
+    LongPath,
+    Health,
     RigidBody::Dynamic,
     Collider::circle(UNIT_DEFAULT_SIZE / 2.0),
     LinearVelocity::ZERO,
@@ -48,12 +64,11 @@ impl Plugin for UnitsPlugin {
         static_coefficient: 0.0,
         combine_rule: CoefficientCombine::Multiply,
     },
-    TranslationInterpolation,
-    LinearDamping(20.0)
+    TranslationInterpolation
 )]
 pub struct Unit;
 
-#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     East,
@@ -61,6 +76,21 @@ pub enum Direction {
     West,
 }
 impl Direction {
+    /// closest direction to a movement, Y points up like in the world
+    pub fn from_movement(movement: Vec2) -> Self {
+        if movement.x.abs() > movement.y.abs() {
+            if movement.x > 0.0 {
+                Direction::East
+            } else {
+                Direction::West
+            }
+        } else if movement.y > 0.0 {
+            Direction::North
+        } else {
+            Direction::South
+        }
+    }
+
     pub fn direction_to_vec2(&self) -> IVec2 {
         match self {
             Direction::North => IVec2 { x: 0, y: -1 },
@@ -69,6 +99,29 @@ impl Direction {
             Direction::West => IVec2 { x: -1, y: 0 },
         }
     }
+
+    pub fn rotated_clockwise(&self) -> Self {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        self.rotated_clockwise().rotated_clockwise()
+    }
+
+    /// rotates a direction expressed relative to a structure facing North into the structure's actual facing
+    pub fn rotate_by(&self, facing: Direction) -> Self {
+        match facing {
+            Direction::North => *self,
+            Direction::East => self.rotated_clockwise(),
+            Direction::South => self.opposite(),
+            Direction::West => self.opposite().rotated_clockwise(),
+        }
+    }
 }
 
 impl Default for Direction {
@@ -88,10 +141,29 @@ impl Default for Speed {
 #[derive(Component)]
 pub struct Player;
 
+pub fn spawn_unit<'a>(
+    commands: &'a mut Commands,
+    name: &str,
+    coordinates: Coordinates,
+    speed: Speed,
+) -> EntityCommands<'a> {
+    let absolute_coordinates = coord_to_absolute_coord(coordinates);
+    let mut transform =
+        Transform::from_xyz(absolute_coordinates.x, absolute_coordinates.y, UNIT_LAYER);
+    transform.scale *= 0.8;
+    commands.spawn((
+        Unit,
+        Name::new(name.to_owned()),
+        SpritePath("default.png".to_owned()),
+        transform,
+        speed,
+    ))
+}
+
 pub fn player_control_system(
     mut unit_query: Query<(&mut LinearVelocity, &mut Direction, &Speed), With<Player>>,
     input: Res<ButtonInput<KeyCode>>,
-    mut message_recalculate: MessageWriter<RecalculateFlowField>,
+    weather: Res<Weather>,
     time: Res<Time<Fixed>>,
 ) {
     let Ok((mut velocity, mut direction, speed)) = unit_query.single_mut() else {
@@ -99,7 +171,6 @@ pub fn player_control_system(
     };
 
     let mut delta = Vec2::ZERO;
-    let mut has_moved = false;
 
     if input.pressed(KeyCode::KeyW) || input.pressed(KeyCode::ArrowUp) {
         delta.y += 1.0;
@@ -121,19 +192,14 @@ pub fn player_control_system(
     // Normaliser le vecteur pour éviter que le mouvement diagonal
     // soit plus rapide (racine(1²+1²) = 1.414)
     if delta.length_squared() > 0.0 {
-        has_moved = true;
         delta = delta.normalize();
     }
 
     // Appliquer la vitesse
     let delta_time = time.delta_secs();
-    velocity.x = delta.x * speed.0 * delta_time;
-    velocity.y = delta.y * speed.0 * delta_time;
-
-    // TODO: change to put that after the collisions check
-    if has_moved {
-        message_recalculate.write_default();
-    }
+    let speed = speed.0 * weather.speed_multiplier();
+    velocity.x = delta.x * speed * delta_time;
+    velocity.y = delta.y * speed * delta_time;
 }
 
 // fn sync_coords_system(mut query: Query<(&mut Transform), With<Unit>>) {
@@ -156,103 +222,73 @@ pub fn player_control_system(
 //     }
 // }
 
+/// sets the velocity each unit wants from the flow field of its waypoint, the steering moves it
 pub fn units_follow_field_system(
-    // mut unit_query: Query<
-    //     (&mut LinearVelocity, &mut Direction, &Transform, &Speed),
-    //     (With<Unit>, Without<Player>),
-    // >,
     mut unit_query: Query<
         (
-            // &mut LinearVelocity,
-            &mut Direction,
-            Forces,
+            &mut Steering,
             &Transform,
             &Speed,
+            &Waypoint,
+            &GoalTile,
+            Option<&Emotions>,
+            Has<Flying>,
         ),
         (With<Unit>, Without<Player>),
     >,
-    flow_field: Res<FlowField>,
+    flow_fields: Res<FlowFields>,
+    map_manager: Res<MapManager>,
+    weather: Res<Weather>,
     time: Res<Time<Fixed>>,
 ) {
-    // const MAX_SPEED: f32 = 30.0;
-    const MAX_SPEED: f32 = 35.0;
-    const ARRIVAL_DISTANCE: f32 = TILE_SIZE.x * 0.25;
-
-    // for (mut velocity, mut direction, mut forces, transform, speed) in unit_query.iter_mut() {
-    for (mut direction, mut forces, transform, speed) in unit_query.iter_mut() {
+    for (mut steering, transform, speed, waypoint, goal_tile, emotions, is_flying) in
+        unit_query.iter_mut()
+    {
         // 1. Position actuelle de l'unité
         let current_pos_world = transform.translation.xy();
         let current_pos_abs = AbsoluteCoordinates {
             x: current_pos_world.x,
             y: current_pos_world.y,
         };
-        let current_tile = absolute_coord_to_tile_coord(current_pos_abs);
-
-        // 2. Trouver la prochaine tuile cible depuis le flow field
-        // ASSUREZ-VOUS d'avoir aussi fait la modification du FlowField
-        // pour qu'il contienne des TileCoordinates (waypoints)
-        if let Some(&next_tile) = flow_field.0.get(&current_tile) {
-            // 3. Calculer la position CIBLE (le centre de la prochaine tuile)
-            let target_pos_abs = tile_coord_to_absolute_coord(next_tile);
-            let target_pos_world: Vec2 = target_pos_abs.into();
-
-            // 4. Calculer la direction et la force vers la cible
-            let to_target_vec = target_pos_world - current_pos_world;
-            let distance = to_target_vec.length();
-
-            // if distance < ARRIVAL_DISTANCE {
-            //     // On freine activement l'unité pour la stabiliser
-            //     // MODIFIÉ : L'appel de fonction est identique, mais
-            //     // c'est sur l'objet `Forces`
-            //     forces.apply_force(-forces.linear_velocity() * 5.0); // Simple "damping"
-            //     continue;
-            // }
-
-            let direction_to_target = to_target_vec.normalize_or_zero();
 
-            // 5. Appliquer la FORCE
-            // MODIFIÉ : L'appel de fonction est identique
-            forces.apply_force(direction_to_target * speed.0);
+        // a scared unit runs away from its target instead
+        let behavior = emotions.map_or(Behavior::Calm, Emotions::behavior);
+        // même vitesse maximale que le joueur
+        steering.max_speed = speed.0
+            * weather.speed_multiplier()
+            * behavior.force_multiplier().abs()
+            * time.delta_secs();
 
-            // 6. Mettre à jour la direction du sprite (logique inchangée)
-            let abs_x = direction_to_target.x.abs();
-            let abs_y = direction_to_target.y.abs();
-
-            if abs_x > abs_y {
-                *direction = if direction_to_target.x > 0.0 {
-                    Direction::East
-                } else {
-                    Direction::West
-                };
-            } else {
-                // Note : J'ai inversé North/South car votre système de
-                // coordonnées Y semble inversé (le joueur va "vers le haut"
-                // avec `delta.y += 1.0` mais `absolute_coord_to_coord`
-                // utilise `-absolute_coord.y`). À VÉRIFIER.
-                // Si la direction du sprite est fausse, inversez North/South ici.
-                *direction = if direction_to_target.y > 0.0 {
-                    Direction::North
-                } else {
-                    Direction::South
-                };
-            }
-
-            // 7. BRIDER la vitesse (logique inchangée)
-            // if forces.linear_velocity().length_squared() > MAX_SPEED * MAX_SPEED {
-            //     *forces.linear_velocity_mut() =
-            //         forces.linear_velocity().normalize_or_zero() * MAX_SPEED;
-            // }
+        // 2. Trouver la prochaine tuile cible depuis le flow field
+        // chaque unité suit le flow field de son propre but, ou du prochain portail quand il est trop loin
+        // vers la tuile la plus lointaine du chemin qu'elle voit en ligne droite
+        // une unité volante va tout droit
+        let next_tile = if is_flying {
+            goal_tile.0
+        } else {
+            waypoint.0.and_then(|goal| {
+                flow_fields.farthest_visible_tile(goal, current_pos_abs, &map_manager.walkability)
+            })
+        };
+        let Some(next_tile) = next_tile else {
+            // Pas de chemin (on est sur la cible ou bloqué) : on freine
+            steering.desired_velocity = Vec2::ZERO;
+            continue;
+        };
 
-            // } else {
-            //     // Pas de chemin dans le flow field (on est sur la cible ou bloqué)
-            //     // On freine activement
-            //     // MODIFIÉ : L'appel de fonction est identique
-            //     forces.apply_force(-forces.linear_velocity() * 10.0);
+        // 3. Calculer la position CIBLE (le centre de la prochaine tuile)
+        let target_pos_world: Vec2 = tile_coord_to_absolute_coord(next_tile).into();
+        let to_target_vec = target_pos_world - current_pos_world;
 
-            //     if forces.linear_velocity().length_squared() < 0.1 {
-            //         *forces.linear_velocity_mut() = Vec2::ZERO;
-            //     }
-        }
+        // 4. Ralentir en arrivant sur le but final, pas sur les tuiles du chemin
+        let desired_speed = if goal_tile.0 == Some(next_tile) {
+            arrival_speed(steering.max_speed, to_target_vec.length())
+        } else {
+            steering.max_speed
+        };
+        steering.desired_velocity = to_target_vec.normalize_or_zero()
+            * behavior.force_multiplier().signum()
+            * desired_speed;
     }
 }
 
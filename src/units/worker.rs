@@ -0,0 +1,305 @@
+use crate::{
+    items::inventory::{
+        ChestInventory, FuelInventory, InputInventory, Inventory, ModuleInventory, OutputInventory,
+    },
+    logistics::jobs::{Job, JobKind, JobQueue},
+    map::{Footprint, MapManager, TILE_SIZE, absolute_coord_to_tile_coord, ghost::Ghost},
+    units::{
+        hierarchical::PortalGraph,
+        pathfinding::{Goal, closest_walkable_tile, straight_line_cost},
+    },
+};
+use avian2d::prelude::Sensor;
+use bevy::{ecs::query::QueryData, prelude::*};
+
+/// a worker acts on a structure from this distance to the edge of its footprint
+const WORKER_REACH: f32 = TILE_SIZE.x * 1.5;
+
+/// robot running the jobs of the JobQueue, it carries the items of one job at a time
+#[derive(Component, Default)]
+#[require(Inventory = Inventory::with_slots(2), ActiveJob)]
+pub struct Worker;
+
+/// carrier flying over the structures in a straight line instead of walking around them
+#[derive(Component, Default)]
+#[require(Sensor)]
+pub struct Flying;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStage {
+    /// going to pick the items up
+    ToSource,
+    /// carrying the items to the target
+    ToTarget,
+}
+
+/// job the worker is running, None when it waits for one
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct ActiveJob(pub Option<(Job, JobStage)>);
+
+/// a worker was spawned, or finished or dropped its job, since the last tick
+pub fn has_new_idle_worker(
+    worker_query: Query<&ActiveJob, (With<Worker>, Changed<ActiveJob>)>,
+) -> bool {
+    worker_query.iter().any(|active_job| active_job.0.is_none())
+}
+
+/// gives each job of the queue to the idle worker with the shortest path to its source
+/// only run when the queue is planned or a worker becomes idle : a job nobody can reach would search its paths again every tick
+pub fn assign_jobs_system(
+    mut job_queue: ResMut<JobQueue>,
+    mut worker_query: Query<(Entity, &Transform, &mut ActiveJob, &mut Goal), With<Worker>>,
+    flying_query: Query<(), With<Flying>>,
+    target_query: Query<&Transform>,
+    portal_graph: Res<PortalGraph>,
+    map_manager: Res<MapManager>,
+) {
+    let mut remaining = Vec::new();
+    for job in job_queue.0.drain(..) {
+        let Ok(source_transform) = target_query.get(job.from) else {
+            continue;
+        };
+        let source_tile = absolute_coord_to_tile_coord((*source_transform).into());
+        let closest_worker = worker_query
+            .iter()
+            .filter(|(_, _, active_job, _)| active_job.0.is_none())
+            // the items a worker carries are only emptied by itself
+            .filter(|(entity, ..)| !worker_query.contains(job.from) || *entity == job.from)
+            .filter_map(|(entity, transform, _, _)| {
+                let tile = absolute_coord_to_tile_coord((*transform).into());
+                let goal = closest_walkable_tile(source_tile, tile, &map_manager)?;
+                let cost = if flying_query.contains(entity) {
+                    straight_line_cost(tile, goal)
+                } else {
+                    portal_graph.path_cost(tile, goal, &map_manager.walkability)?
+                };
+                Some((cost, entity))
+            })
+            .min();
+        let Some((_, worker)) = closest_worker else {
+            remaining.push(job);
+            continue;
+        };
+        let (_, _, mut active_job, mut goal) = worker_query.get_mut(worker).unwrap();
+        if job.from == worker {
+            active_job.0 = Some((job, JobStage::ToTarget));
+            *goal = Goal::Entity(job.to);
+        } else {
+            active_job.0 = Some((job, JobStage::ToSource));
+            *goal = Goal::Entity(job.from);
+        }
+    }
+    job_queue.0 = remaining;
+}
+
+/// inventories of the structures a worker picks items up from or delivers them to
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub struct JobInventories {
+    output: Option<&'static mut OutputInventory>,
+    input: Option<&'static mut InputInventory>,
+    fuel: Option<&'static mut FuelInventory>,
+    module: Option<&'static mut ModuleInventory>,
+    chest: Option<&'static mut ChestInventory>,
+    carried: Option<&'static mut Inventory>,
+    ghost: Option<&'static mut Ghost>,
+}
+
+fn is_in_reach(worker: &Transform, target: &Transform, footprint: Option<&Footprint>) -> bool {
+    let half_size = footprint.map_or(1, |footprint| footprint.width.max(footprint.height)) as f32
+        * TILE_SIZE.x
+        / 2.0;
+    worker.translation.xy().distance(target.translation.xy()) <= half_size + WORKER_REACH
+}
+
+/// moves the items of the jobs once the workers reach their source and their target
+pub fn run_jobs_system(
+    mut worker_query: Query<(Entity, &Transform, &mut ActiveJob, &mut Goal), With<Worker>>,
+    target_query: Query<(&Transform, Option<&Footprint>)>,
+    mut inventories_query: Query<JobInventories>,
+) {
+    for (worker, transform, mut active_job, mut goal) in worker_query.iter_mut() {
+        let Some((job, stage)) = active_job.0 else {
+            continue;
+        };
+        let target = match stage {
+            JobStage::ToSource => job.from,
+            JobStage::ToTarget => job.to,
+        };
+        // the structure was removed, the carried items are brought to a chest by a later job
+        let Ok((target_transform, footprint)) = target_query.get(target) else {
+            active_job.0 = None;
+            *goal = Goal::Idle;
+            continue;
+        };
+        if !is_in_reach(transform, target_transform, footprint) {
+            continue;
+        }
+
+        match stage {
+            JobStage::ToSource => {
+                let Ok([mut source, mut carried]) =
+                    inventories_query.get_many_mut([job.from, worker])
+                else {
+                    continue;
+                };
+                let source_inventory = source
+                    .output
+                    .as_deref_mut()
+                    .map(|inventory| &mut inventory.0)
+                    .filter(|inventory| inventory.enough_quantity(job.item_stack))
+                    .or(source
+                        .chest
+                        .as_deref_mut()
+                        .map(|inventory| &mut inventory.0));
+                let carried = carried.carried.as_deref_mut().unwrap();
+                match source_inventory {
+                    Some(inventory)
+                        if inventory.enough_quantity(job.item_stack)
+                            && carried.add(job.item_stack).is_ok() =>
+                    {
+                        inventory.remove_quantity(job.item_stack);
+                        active_job.0 = Some((job, JobStage::ToTarget));
+                        *goal = Goal::Entity(job.to);
+                    }
+                    // the items were taken by someone else, the job is planned again if still needed
+                    _ => {
+                        active_job.0 = None;
+                        *goal = Goal::Idle;
+                    }
+                }
+            }
+            JobStage::ToTarget => {
+                let Ok([mut carried, mut target]) =
+                    inventories_query.get_many_mut([worker, job.to])
+                else {
+                    continue;
+                };
+                let carried = carried.carried.as_deref_mut().unwrap();
+                let mut item_stack = job.item_stack;
+                if let Some(ghost) = target.ghost.as_deref_mut() {
+                    item_stack.quantity = ghost.deliver(item_stack);
+                } else {
+                    let inventory = match job.kind {
+                        JobKind::Refuel => {
+                            target.fuel.as_deref_mut().map(|inventory| &mut inventory.0)
+                        }
+                        JobKind::Equip => target
+                            .module
+                            .as_deref_mut()
+                            .map(|inventory| &mut inventory.0),
+                        JobKind::Deliver => target
+                            .chest
+                            .as_deref_mut()
+                            .map(|inventory| &mut inventory.0),
+                        JobKind::Haul => match target.input.as_deref_mut() {
+                            Some(inventory) => Some(&mut inventory.0),
+                            None => target
+                                .chest
+                                .as_deref_mut()
+                                .map(|inventory| &mut inventory.0),
+                        },
+                        JobKind::Build => None,
+                    };
+                    if inventory.is_none_or(|inventory| inventory.add(item_stack).is_err()) {
+                        item_stack.quantity = 0;
+                    }
+                }
+                // what the target did not take stays in the worker until it is hauled to a chest
+                if item_stack.quantity > 0 {
+                    carried.remove_quantity(item_stack);
+                }
+                active_job.0 = None;
+                *goal = Goal::Idle;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        items::{ItemType, Quality, inventory::ItemStack},
+        map::{TileCoordinates, tile_coord_to_absolute_coord},
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn move_to(world: &mut World, entity: Entity, tile: TileCoordinates) {
+        let absolute_coord = tile_coord_to_absolute_coord(tile);
+        world.get_mut::<Transform>(entity).unwrap().translation =
+            Vec3::new(absolute_coord.x, absolute_coord.y, 0.0);
+    }
+
+    fn spawn_at(world: &mut World, tile: TileCoordinates, bundle: impl Bundle) -> Entity {
+        let entity = world.spawn((Transform::default(), bundle)).id();
+        move_to(world, entity, tile);
+        entity
+    }
+
+    #[test]
+    fn test_haul_job_carries_the_items_from_the_source_to_the_target() {
+        let mut world = World::new();
+        let iron_plates = ItemStack::new(ItemType::IronPlate, Quality::Standard, 5);
+        let mut chest_inventory = ChestInventory::default();
+        chest_inventory.0.add(iron_plates).unwrap();
+        let chest = spawn_at(&mut world, TileCoordinates { x: 0, y: 0 }, chest_inventory);
+        let machine = spawn_at(
+            &mut world,
+            TileCoordinates { x: 8, y: 0 },
+            InputInventory::default(),
+        );
+        let job = Job {
+            kind: JobKind::Haul,
+            from: chest,
+            to: machine,
+            item_stack: iron_plates,
+        };
+        let worker = spawn_at(
+            &mut world,
+            TileCoordinates { x: 4, y: 0 },
+            (
+                Worker,
+                ActiveJob(Some((job, JobStage::ToSource))),
+                Goal::Entity(chest),
+            ),
+        );
+
+        // out of reach of the chest
+        world.run_system_once(run_jobs_system).unwrap();
+        assert_eq!(
+            world.get::<ActiveJob>(worker).unwrap().0,
+            Some((job, JobStage::ToSource))
+        );
+
+        move_to(&mut world, worker, TileCoordinates { x: 1, y: 0 });
+        world.run_system_once(run_jobs_system).unwrap();
+        assert_eq!(
+            world.get::<Inventory>(worker).unwrap().slots,
+            vec![iron_plates]
+        );
+        assert_eq!(
+            world
+                .get::<ChestInventory>(chest)
+                .unwrap()
+                .0
+                .quantity(ItemType::IronPlate),
+            0
+        );
+        assert_eq!(
+            world.get::<ActiveJob>(worker).unwrap().0,
+            Some((job, JobStage::ToTarget))
+        );
+        assert_eq!(*world.get::<Goal>(worker).unwrap(), Goal::Entity(machine));
+
+        move_to(&mut world, worker, TileCoordinates { x: 7, y: 0 });
+        world.run_system_once(run_jobs_system).unwrap();
+        assert_eq!(
+            world.get::<InputInventory>(machine).unwrap().0.slots,
+            vec![iron_plates]
+        );
+        assert!(world.get::<Inventory>(worker).unwrap().slots.is_empty());
+        assert_eq!(world.get::<ActiveJob>(worker).unwrap().0, None);
+        assert_eq!(*world.get::<Goal>(worker).unwrap(), Goal::Idle);
+    }
+}